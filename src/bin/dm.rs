@@ -1,6 +1,13 @@
-use clap::{command, CommandFactory, Parser, Subcommand};
+use clap::{command, CommandFactory, Parser, Subcommand, ValueEnum};
 use log::{error, info, warn};
 use rs_utils::command_utils::run_command;
+use rs_utils::docker_utils::compose::ComposeFile;
+use rs_utils::docker_utils::container_info::{
+    ContainerConfig, ContainerInfo, DockerCommand, ImageInfo,
+};
+use rs_utils::docker_utils::engine::DockerClient;
+use rs_utils::file_utils::archive::ArchiveFormat;
+use rs_utils::file_utils::filter::FileFilter;
 use rs_utils::{docker_utils, file_utils, log_utils};
 use serde::Deserialize;
 use std::collections::HashMap;
@@ -37,6 +44,8 @@ enum Commands {
     },
     #[command(about = "清理Docker镜像并导出所有镜像")]
     Export {
+        #[arg(short, long, value_enum, help = "导出后压缩镜像")]
+        compress: Option<Compress>,
         #[arg(help = "默认导出至当前目录下的images")]
         path: Option<String>,
     },
@@ -44,11 +53,30 @@ enum Commands {
     Reverse {
         #[arg(short, long, help = "逆向解析完成后以解析出的命令重新创建容器")]
         rerun: bool,
+        #[arg(long, help = "保留与镜像默认值相同的环境变量、标签、命令等配置")]
+        full: bool,
+        #[arg(short, long, help = "每个选项单独一行，以反斜杠续行")]
+        multiline: bool,
+        #[arg(long, help = "同时以 JSON 参数数组的形式保存到 docker_commands.json")]
+        json: bool,
+        #[arg(
+            short,
+            long,
+            help = "同时生成 docker-compose.yml，depends_on 由同一自定义网络中环境变量、命令或链接对其他容器名称的引用推断"
+        )]
+        compose: bool,
         #[arg(help = "容器ID或名称")]
         names: Vec<String>,
     },
 }
 
+/// 镜像导出压缩格式
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Compress {
+    Gz,
+    Zst,
+}
+
 fn main() {
     log_utils::init_logger();
     let cli = Cli::parse();
@@ -74,22 +102,32 @@ fn main() {
                 let path = path.unwrap_or_else(|| "images".to_string());
                 import(&path).expect("Import failed!");
             }
-            Commands::Export { path } => {
+            Commands::Export { compress, path } => {
                 let path = path.unwrap_or_else(|| "images".to_string());
                 file_utils::create_directory(&path).expect("Create directory failed");
-                export(&path).expect("Export failed");
+                export(&path, compress).expect("Export failed");
             }
-            Commands::Reverse { rerun, names } => {
+            Commands::Reverse {
+                rerun,
+                full,
+                multiline,
+                json,
+                compose,
+                names,
+            } => {
                 let container_names: Vec<&str> = names.iter().map(AsRef::as_ref).collect();
-                match reverse(&container_names) {
+                match reverse(&container_names, full, compose) {
                     Ok(cmds) => {
-                        // warn!("{:?}",cmd);
                         let mut file = File::create("docker_commands.sh").unwrap();
-                        writeln!(file, "#!/bin/bash").expect("Failed to write file!");
-                        for (name, cmd) in cmds {
+                        writeln!(file, "#!/bin/sh").expect("Failed to write file!");
+                        let mut argv_map = serde_json::Map::new();
+                        for (name, docker_command) in cmds {
+                            let shell = docker_command.to_shell(multiline);
                             writeln!(file, "# {}", name).expect("Failed to write file!");
-                            writeln!(file, "{}", cmd.join(" ")).expect("Failed to write file!");
-                            info!("Generated docker command:\n{}", cmd.join(" "));
+                            writeln!(file, "{}", shell).expect("Failed to write file!");
+                            info!("Generated docker command:\n{}", shell);
+                            let cmd = docker_command.to_command();
+                            argv_map.insert(name.clone(), cmd.clone().into());
                             if rerun {
                                 docker_utils::container_stop(&[name.as_str()]).unwrap();
                                 docker_utils::container_remove(&[name.as_str()]).unwrap();
@@ -99,6 +137,12 @@ fn main() {
                             }
                         }
                         info!("Save command to docker_commands.sh successfully!");
+                        if json {
+                            let data = serde_json::to_string_pretty(&argv_map).unwrap();
+                            std::fs::write("docker_commands.json", data)
+                                .expect("Failed to write file!");
+                            info!("Save command to docker_commands.json successfully!");
+                        }
                     }
                     Err(e) => {
                         error!("Error to reverse container:{}", e)
@@ -110,11 +154,13 @@ fn main() {
 }
 
 fn build(path: &str, export: bool) -> Result<String, Error> {
-    let file_data = file_utils::file_data::FileData::new(path.to_string()).unwrap();
-    std::env::set_current_dir(&file_data.abs_path)?;
-    docker_utils::build(&file_data.filename)?;
+    let file_data = file_utils::file_data::FileData::open(path)?;
+    let dir = file_data.abs_path()?;
+    let name = file_data.file_name_lossy();
+    docker_utils::build_in(&dir, &name)?;
     if export {
-        docker_utils::save(&file_data.filename, ".")?;
+        // 镜像导出到构建目录中
+        docker_utils::save(&name, &dir)?;
     }
     Ok("".to_string())
 }
@@ -124,11 +170,17 @@ fn clean() -> Result<String, Error> {
 }
 
 fn import(path: &str) -> Result<String, Error> {
-    match file_utils::file_data::FileData::new(path.to_string()) {
-        Ok(data) => match file_utils::traverse_dir_files(data.abs_path.as_str(), true) {
+    // 只导入镜像压缩包，跳过说明文件等其他文件
+    let filter = FileFilter::new()
+        .include("*.tar")
+        .and_then(|f| f.include("*.tar.gz"))
+        .and_then(|f| f.include("*.tar.zst"))
+        .map_err(|e| Error::other(e.to_string()))?;
+    match file_utils::file_data::FileData::open(path) {
+        Ok(data) => match file_utils::traverse_dir_files_with_filter(data.path(), true, &filter) {
             Ok((_, files)) => {
                 for file in files {
-                    docker_utils::load(file.path.as_str())?;
+                    docker_utils::load(file.path())?;
                 }
             }
             Err(e) => {
@@ -142,41 +194,63 @@ fn import(path: &str) -> Result<String, Error> {
     Ok("".to_string())
 }
 
-fn export(path: &str) -> Result<String, Error> {
+fn export(path: &str, compress: Option<Compress>) -> Result<String, Error> {
     docker_utils::image_prune()?;
-    let images = docker_utils::image_list_formatted()?;
-    let images: Vec<&str> = images.lines().filter(|line| !line.is_empty()).collect();
-    for image in images {
-        if let Err(e) = docker_utils::save(image, path) {
+    // 通过 Engine API 获取镜像标签，跳过没有标签的悬空镜像
+    let runtime = tokio::runtime::Runtime::new()?;
+    let images = runtime.block_on(DockerClient::new()?.image_tags())?;
+    let dir = Path::new(path);
+    for image in images.iter().map(String::as_str) {
+        let result = match compress {
+            Some(Compress::Gz) => docker_utils::save_compressed(image, dir, ArchiveFormat::TarGz),
+            Some(Compress::Zst) => {
+                docker_utils::save_compressed(image, dir, ArchiveFormat::TarZst)
+            }
+            None => docker_utils::save(image, dir),
+        };
+        if let Err(e) = result {
             error!("Failed to save image {}: {}", image, e);
         }
     }
     Ok("Export success!".to_string())
 }
 
-fn reverse(names: &[&str]) -> Result<HashMap<String, Vec<String>>, Error> {
+fn reverse(
+    names: &[&str],
+    full: bool,
+    compose: bool,
+) -> Result<Vec<(String, DockerCommand)>, Error> {
     match docker_utils::container_inspect(names) {
         Ok(data) => {
-            let container_info_list: Vec<docker_utils::container_info::ContainerInfo> =
-                serde_json::from_str(data.as_str())?;
-            let mut command_map: HashMap<String, Vec<String>> = HashMap::new();
+            let container_info_list: Vec<ContainerInfo> = serde_json::from_str(data.as_str())?;
+            let images = if full {
+                HashMap::new()
+            } else {
+                image_configs(&container_info_list)
+            };
+            // 去掉镜像默认值，镜像已被删除时保留全部配置
+            let container_info_list: Vec<ContainerInfo> = container_info_list
+                .into_iter()
+                .map(|info| match images.get(&info.image) {
+                    Some(image) => info.without_image_defaults(image),
+                    None => info,
+                })
+                .collect();
+            if compose {
+                let yaml = ComposeFile::from_containers(&container_info_list).to_yaml()?;
+                std::fs::write("docker-compose.yml", yaml)?;
+                info!("Save compose file to docker-compose.yml successfully!");
+            }
+            let mut commands = Vec::new();
             for container_info in container_info_list {
-                let name = container_info.Name.clone();
-                let docker_command =
-                    docker_utils::container_info::DockerCommand::from(container_info);
-                let command = docker_command.to_command();
-                command_map.insert(name, command);
-                // match container_info.to_shell_command() {
-                //     Ok(command) => {
-                //         command_map.insert(container_info.Name, command);
-                //     }
-                //     Err(e) => {
-                //         error!("{}", e);
-                //         continue;
-                //     }
-                // }
+                let name = container_info.container_name().to_string();
+                let docker_command = DockerCommand::from(container_info);
+                for field in docker_command.unmapped() {
+                    warn!("{}: {} cannot be reversed", name, field);
+                }
+                commands.push((name, docker_command));
             }
-            Ok(command_map)
+            Ok(commands)
         }
         Err(e) => {
             error!("Failed to inspect container {:?}: {}", names, e);
@@ -184,3 +258,23 @@ fn reverse(names: &[&str]) -> Result<HashMap<String, Vec<String>>, Error> {
         }
     }
 }
+
+/// 容器所用镜像的默认配置，按镜像 ID 索引
+fn image_configs(containers: &[ContainerInfo]) -> HashMap<String, ContainerConfig> {
+    let mut ids: Vec<&str> = containers.iter().map(|c| c.image.as_str()).collect();
+    ids.sort();
+    ids.dedup();
+    let images: Vec<ImageInfo> = match docker_utils::image_inspect(&ids)
+        .and_then(|data| serde_json::from_str(&data).map_err(Error::from))
+    {
+        Ok(images) => images,
+        Err(e) => {
+            warn!("Failed to inspect images, keep image defaults: {}", e);
+            return HashMap::new();
+        }
+    };
+    images
+        .into_iter()
+        .map(|image| (image.id, image.config))
+        .collect()
+}
@@ -1,11 +1,13 @@
 use std::collections::HashMap;
 use std::fs;
 use std::io::Read;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use clap::Parser;
-use log::info;
+use log::{error, info};
 use rs_utils::{file_utils, log_utils};
 use rs_utils::build_utils::project::Project;
+use rs_utils::file_utils::workspace::Workspace;
+use rs_utils::git_utils::mirror::MirrorCache;
 
 /// 命令行参数结构体
 #[derive(Parser, Debug)]
@@ -19,13 +21,21 @@ struct Args {
     #[arg(short, long, help = "自动部署")]
     deploy: bool,
 
+    /// 忽略构建指纹，总是重新构建
+    #[arg(short, long, help = "强制构建")]
+    force: bool,
+
+    /// 保存构建指纹的目录
+    #[arg(long, default_value = ".pb-state", help = "状态目录")]
+    state_dir: String,
+
     /// 端口列表
     #[arg(short, long, help = "端口列表")]
     ports: Option<String>,
 
     /// 输入的文件或目录路径
     #[arg(help = "文件路径或项目目录路径")]
-    path: Option<String>,
+    path: Option<PathBuf>,
 }
 
 fn main() {
@@ -33,22 +43,26 @@ fn main() {
     let args = Args::parse();
     let concurrent_build = args.concurrent;
     let deploy = args.deploy;
+    let force = args.force;
+    let state_dir = Path::new(&args.state_dir).to_path_buf();
     let ports = args.ports.unwrap_or("".to_string());
-    let path = args.path.unwrap_or(".".to_string());
+    let path = args.path.unwrap_or_else(|| PathBuf::from("."));
 
     let port_list: Vec<String> = ports.split(',').map(|s| s.to_string()).collect();
-    info!("输入路径: {}", &path);
+    info!("输入路径: {}", path.display());
     info!("是否并发构建: {}", concurrent_build);
+    info!("是否强制构建: {}", force);
     info!("端口列表: {:?}", port_list);
 
-    let file_data = file_utils::file_data::FileData::new(path.clone()).unwrap();
+    let file_data = file_utils::file_data::FileData::open(path.clone()).unwrap();
 
     let mut project_list: Vec<Project> = Vec::new();
 
-    if file_data.metadata.is_dir() {
+    if file_data.is_dir() {
+        let abs_path = file_data.abs_path().expect("获取绝对路径出错");
         let b = Project::new(
-            file_data.abs_path.trim_start_matches(r"\\?\").to_string(),
-            file_data.filename,
+            &strip_verbatim(abs_path),
+            file_data.file_name_lossy(),
             port_list,
             "".to_string(),
             "".to_string(),
@@ -63,7 +77,7 @@ fn main() {
             fs::create_dir_all(&work_dir).expect("创建 projects 文件夹出错");
         }
 
-        let mut file = fs::File::open(&file_data.abs_path).expect("打开文件出错");
+        let mut file = fs::File::open(file_data.path()).expect("打开文件出错");
         let mut data = String::new();
         file.read_to_string(&mut data).expect("读取文件出错");
 
@@ -71,15 +85,16 @@ fn main() {
 
         for (key, b) in result {
             info!("Key: {}, Parsed Struct: {:?}", key, b);
-            let project_dir = work_dir.join(&b.name);
-            let project_dir_cleaned = project_dir.to_str().unwrap().trim_start_matches(r"\\?\");
-            let b1 = Project::new(
-                String::from(project_dir_cleaned),
+            let workspace = Workspace::open(&work_dir, &b.name).expect("创建项目目录出错");
+            let mut b1 = Project::new(
+                &strip_verbatim(workspace.path().to_path_buf()),
                 b.name,
                 b.ports.clone(),
                 b.repository.url.clone(),
                 b.repository.branch.clone(),
             );
+            b1.repository = b.repository;
+            b1.deploy = b.deploy;
             project_list.push(b1);
         }
     }
@@ -107,10 +122,18 @@ fn main() {
     // } else {
     info!("顺序构建");
     let projects = &mut project_list;
+    // 同一仓库的多个项目共享镜像，镜像每次运行只刷新一次
+    let mirror_cache = match MirrorCache::new(state_dir.join("mirrors")) {
+        Ok(cache) => Some(cache),
+        Err(e) => {
+            error!("创建镜像缓存失败：{}", e);
+            None
+        }
+    };
     projects.iter_mut().for_each(|project| {
-        project.get_source_code();
+        project.get_source_code_with(mirror_cache.as_ref());
         project.init_builder();
-        project.build();
+        project.build_if_changed(&state_dir, force);
         if deploy {
             project.deploy_to_docker();
         }
@@ -121,3 +144,11 @@ fn main() {
         info!("{}", b.build_message);
     }
 }
+
+/// 去掉 Windows 规范化路径的 `\\?\` 前缀，非 UTF-8 路径原样返回
+fn strip_verbatim(path: PathBuf) -> PathBuf {
+    match path.to_str() {
+        Some(s) => PathBuf::from(s.trim_start_matches(r"\\?\")),
+        None => path,
+    }
+}
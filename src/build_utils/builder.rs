@@ -1,19 +1,20 @@
+use crate::file_utils::copy::ReplaceOptions;
 use crate::{command_utils, docker_utils, file_utils};
-use log::info;
+use log::{info, warn};
 use std::fmt::Debug;
 use std::io::Error;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 pub(crate) trait Builder: Debug {
     fn build(&self) -> Result<String, Error>;
 }
 #[derive(Debug)]
 pub(crate) struct Maven {
-    path: String,
+    path: PathBuf,
 }
 
 impl Maven {
-    pub(crate) fn new(path: String) -> Self {
+    pub(crate) fn new(path: PathBuf) -> Self {
         Maven { path }
     }
 }
@@ -22,17 +23,17 @@ impl Builder for Maven {
     /// 执行 Maven 构建
     fn build(&self) -> Result<String, Error> {
         info!("构建Maven项目");
-        command_utils::run_command("mvn", &["clean", "package"])
+        command_utils::run_command_in(&self.path, "mvn", &["clean", "package"])
     }
 }
 
 #[derive(Debug)]
 pub(crate) struct Gradle {
-    path: String,
+    path: PathBuf,
 }
 
 impl Gradle {
-    pub(crate) fn new(path: String) -> Self {
+    pub(crate) fn new(path: PathBuf) -> Self {
         Gradle { path }
     }
 }
@@ -41,17 +42,17 @@ impl Builder for Gradle {
     /// 执行 Gradle 构建
     fn build(&self) -> Result<String, Error> {
         info!("构建Gradle项目");
-        command_utils::run_command("gradle", &["build"])
+        command_utils::run_command_in(&self.path, "gradle", &["build"])
     }
 }
 
 #[derive(Debug)]
 pub(crate) struct Python {
-    path: String,
+    path: PathBuf,
 }
 
 impl Python {
-    pub(crate) fn new(path: String) -> Self {
+    pub(crate) fn new(path: PathBuf) -> Self {
         Python { path }
     }
 }
@@ -60,7 +61,8 @@ impl Builder for Python {
     /// 执行 Python 构建
     fn build(&self) -> Result<String, Error> {
         info!("构建Python项目");
-        command_utils::run_command(
+        command_utils::run_command_in(
+            &self.path,
             "pip",
             &[
                 "install",
@@ -75,11 +77,11 @@ impl Builder for Python {
 
 #[derive(Debug)]
 pub(crate) struct Node {
-    path: String,
+    path: PathBuf,
 }
 
 impl Node {
-    pub(crate) fn new(path: String) -> Self {
+    pub(crate) fn new(path: PathBuf) -> Self {
         Node { path }
     }
 }
@@ -88,23 +90,29 @@ impl Builder for Node {
     /// 执行 Node.js 构建
     fn build(&self) -> Result<String, Error> {
         info!("构建Node项目");
-        command_utils::run_command(
+        command_utils::run_command_in(
+            &self.path,
             "npm",
             &["install", "--registry=https://registry.npmmirror.com"],
         )?;
-        command_utils::run_command("npm", &["run", "build"])?;
-        let work_dir = std::env::current_dir()?;
+        command_utils::run_command_in(&self.path, "npm", &["run", "build"])?;
+        let work_dir = &self.path;
         let source = Path::new("/root/node_file/Cesium.js");
         let target = work_dir.join("dist/cesium/Cesium.js");
-        if source.exists() && target.parent().map_or(false, |p| p.exists()) {
-            file_utils::replace(&source, &target).expect("替换文件失败");
-            println!("文件替换成功！");
+        let target_dir_exists = target.parent().is_some_and(|p| p.exists());
+        if source.exists() && target_dir_exists {
+            let options = ReplaceOptions::new().backup(true);
+            let report = file_utils::copy::atomic_replace(source, &target, &options)?;
+            info!(
+                "文件替换成功！{} 字节，校验和 {}",
+                report.bytes, report.checksum
+            );
         } else {
             if !source.exists() {
-                println!("源文件不存在：{}", source.display());
+                warn!("源文件不存在：{}", source.display());
             }
-            if !target.parent().map_or(false, |p| p.exists()) {
-                println!("目标目录不存在：{}", target.display());
+            if !target_dir_exists {
+                warn!("目标目录不存在：{}", target.display());
             }
         }
         Ok(String::from(""))
@@ -113,11 +121,11 @@ impl Builder for Node {
 
 #[derive(Debug)]
 pub(crate) struct Go {
-    path: String,
+    path: PathBuf,
 }
 
 impl Go {
-    pub(crate) fn new(path: String) -> Self {
+    pub(crate) fn new(path: PathBuf) -> Self {
         Go { path }
     }
 }
@@ -126,19 +134,23 @@ impl Builder for Go {
     /// 执行 Go 构建
     fn build(&self) -> Result<String, Error> {
         info!("构建Go项目");
-        command_utils::run_command("go", &["env", "-w", "GO111MODULE=on"])?;
-        command_utils::run_command("go", &["env", "-w", "GOPROXY=https://goproxy.cn,direct"])?;
-        command_utils::run_command("go", &["build"])
+        command_utils::run_command_in(&self.path, "go", &["env", "-w", "GO111MODULE=on"])?;
+        command_utils::run_command_in(
+            &self.path,
+            "go",
+            &["env", "-w", "GOPROXY=https://goproxy.cn,direct"],
+        )?;
+        command_utils::run_command_in(&self.path, "go", &["build"])
     }
 }
 
 #[derive(Debug)]
 pub(crate) struct C {
-    path: String,
+    path: PathBuf,
 }
 
 impl C {
-    pub(crate) fn new(path: String) -> Self {
+    pub(crate) fn new(path: PathBuf) -> Self {
         C { path }
     }
 }
@@ -147,18 +159,18 @@ impl Builder for C {
     /// 执行 C 构建
     fn build(&self) -> Result<String, Error> {
         info!("构建C项目");
-        command_utils::run_command("cmake", &[".."])?;
-        command_utils::run_command("make", &[])
+        command_utils::run_command_in(&self.path, "cmake", &[".."])?;
+        command_utils::run_command_in(&self.path, "make", &[])
     }
 }
 
 #[derive(Debug)]
 pub(crate) struct Rust {
-    path: String,
+    path: PathBuf,
 }
 
 impl Rust {
-    pub(crate) fn new(path: String) -> Self {
+    pub(crate) fn new(path: PathBuf) -> Self {
         Rust { path }
     }
 }
@@ -167,18 +179,18 @@ impl Builder for Rust {
     /// 执行 Rust 构建
     fn build(&self) -> Result<String, Error> {
         info!("构建Rust项目");
-        command_utils::run_command("cargo", &["build", "--release"])
+        command_utils::run_command_in(&self.path, "cargo", &["build", "--release"])
     }
 }
 
 #[derive(Debug)]
 pub(crate) struct Docker {
-    path: String,
+    path: PathBuf,
     name: String,
 }
 
 impl Docker {
-    pub(crate) fn new(path: String, name: String) -> Self {
+    pub(crate) fn new(path: PathBuf, name: String) -> Self {
         Docker { path, name }
     }
 }
@@ -187,6 +199,6 @@ impl Builder for Docker {
     /// 执行 Docker 构建
     fn build(&self) -> Result<String, Error> {
         info!("构建Docker项目");
-        docker_utils::build(&self.name)
+        docker_utils::build_in(&self.path, &self.name)
     }
 }
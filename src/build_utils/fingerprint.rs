@@ -0,0 +1,237 @@
+use crate::file_utils;
+use crate::file_utils::filter::FileFilter;
+use crate::file_utils::hash::{self, ContentHasher, HashAlgorithm};
+use crate::git_utils::repo::GitRepo;
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// 构建指纹，三部分都相同时认为项目没有变化
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Fingerprint {
+    /// HEAD 指向的提交 ID，不是 git 仓库时为 `None`
+    pub commit: Option<String>,
+    /// 源文件内容的哈希
+    pub files: String,
+    /// 构建配置的哈希
+    pub config: String,
+}
+
+impl Fingerprint {
+    /// 计算项目目录的指纹，`config` 为影响构建结果的配置
+    ///
+    /// `state_dir` 为保存指纹的目录，位于项目目录内时不计入指纹。
+    pub fn compute(dir: &Path, state_dir: &Path, config: &impl Serialize) -> io::Result<Self> {
+        let config = serde_json::to_vec(config)?;
+        let mut hasher = ContentHasher::new(HashAlgorithm::Sha256);
+        hasher.update(&config);
+        let (commit, files) = match GitRepo::open(dir) {
+            Ok(repo) => (repo.head()?, hash_tracked_files(dir, &repo)?),
+            Err(_) => (None, hash_dir(dir, state_dir)?),
+        };
+        Ok(Fingerprint {
+            commit,
+            files,
+            config: hasher.finalize(),
+        })
+    }
+
+    /// 读取保存的指纹，文件不存在时返回 `None`
+    pub fn load(path: &Path) -> io::Result<Option<Self>> {
+        match fs::read_to_string(path) {
+            Ok(data) => Ok(Some(serde_json::from_str(&data)?)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// 保存为 JSON 文件，目录不存在时创建
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        info!("保存构建指纹 {}", path.display());
+        Ok(())
+    }
+}
+
+/// 项目在状态目录中的指纹文件
+pub fn fingerprint_path(state_dir: &Path, name: &str) -> PathBuf {
+    state_dir.join(format!("{}.fingerprint.json", name))
+}
+
+/// 已跟踪文件在工作区中的内容哈希，包含未提交的修改
+fn hash_tracked_files(dir: &Path, repo: &GitRepo) -> io::Result<String> {
+    let mut hasher = ContentHasher::new(HashAlgorithm::Sha256);
+    for rel_path in repo.tracked_files()? {
+        let path = dir.join(&rel_path);
+        hasher.update(rel_path.to_string_lossy().as_bytes());
+        hasher.update(&[0]);
+        // 已删除的文件与内容为空的文件区分开
+        match hash::hash_file(&path, HashAlgorithm::Sha256) {
+            Ok(hash) => hasher.update(hash.as_bytes()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => hasher.update(b"deleted"),
+            // 子模块在索引中是目录
+            Err(_) if path.is_dir() => hasher.update(b"dir"),
+            Err(e) => return Err(e),
+        }
+        hasher.update(&[0]);
+    }
+    Ok(hasher.finalize())
+}
+
+/// 常见的构建输出，不是 git 仓库时不计入指纹，否则构建本身会改变指纹
+const BUILD_OUTPUTS: [&str; 5] = [
+    "**/target",
+    "**/node_modules",
+    "**/dist",
+    "**/build",
+    "**/Cargo.lock",
+];
+
+/// 不是 git 仓库时，按 `.gitignore` 过滤并跳过构建输出和状态目录后计算目录下所有文件的哈希
+fn hash_dir(dir: &Path, state_dir: &Path) -> io::Result<String> {
+    let mut patterns: Vec<String> = BUILD_OUTPUTS.iter().map(|s| s.to_string()).collect();
+    patterns.extend(relative_state_dir(dir, state_dir));
+    let mut filter = FileFilter::new().gitignore();
+    for pattern in &patterns {
+        filter = filter
+            .exclude(pattern)
+            .map_err(|e| io::Error::other(e.to_string()))?;
+    }
+    let (_, mut files) = file_utils::traverse_dir_files_with_filter(dir, true, &filter)?;
+    files.sort_by(|a, b| a.path().cmp(b.path()));
+    let mut hasher = ContentHasher::new(HashAlgorithm::Sha256);
+    for file in files {
+        let rel_path = file.path().strip_prefix(dir).unwrap_or(file.path());
+        hasher.update(rel_path.to_string_lossy().as_bytes());
+        hasher.update(&[0]);
+        hasher.update(hash::hash_file(file.path(), HashAlgorithm::Sha256)?.as_bytes());
+        hasher.update(&[0]);
+    }
+    Ok(hasher.finalize())
+}
+
+/// 状态目录相对于项目目录的排除通配符，不在项目目录内或尚不存在时返回 `None`
+fn relative_state_dir(dir: &Path, state_dir: &Path) -> Option<String> {
+    let dir = fs::canonicalize(dir).ok()?;
+    let state_dir = fs::canonicalize(state_dir).ok()?;
+    let rel_path = state_dir.strip_prefix(&dir).ok()?;
+    if rel_path.as_os_str().is_empty() {
+        return None;
+    }
+    Some(globset::escape(&rel_path.to_string_lossy()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git_utils::git;
+    use serde_json::json;
+
+    fn write(path: &Path, contents: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    #[test]
+    fn compute_tracks_sources_and_config_outside_git() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        write(&root.join("src/main.rs"), "fn main() {}");
+        write(&root.join(".gitignore"), "*.log\n");
+        let state = root.join(".pb-state");
+        let config = json!({ "name": "app" });
+
+        let first = Fingerprint::compute(root, &state, &config).unwrap();
+        assert_eq!(first.commit, None);
+        assert_eq!(Fingerprint::compute(root, &state, &config).unwrap(), first);
+
+        // 构建输出和被忽略的文件不影响指纹
+        write(&root.join("target/debug/app"), "binary");
+        write(&root.join("web/node_modules/pkg/index.js"), "module");
+        write(&root.join("crates/core/target/debug/core"), "binary");
+        write(&root.join("build.log"), "log");
+        assert_eq!(Fingerprint::compute(root, &state, &config).unwrap(), first);
+
+        let other = Fingerprint::compute(root, &state, &json!({ "name": "other" })).unwrap();
+        assert_eq!(other.files, first.files);
+        assert_ne!(other.config, first.config);
+
+        write(&root.join("src/main.rs"), "fn main() { println!() }");
+        let changed = Fingerprint::compute(root, &state, &config).unwrap();
+        assert_ne!(changed.files, first.files);
+        assert_eq!(changed.config, first.config);
+    }
+
+    #[test]
+    fn compute_uses_head_and_worktree_in_git() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        write(&root.join("a.txt"), "a");
+        git(root, &["init", "-q"]).unwrap();
+        git(root, &["add", "."]).unwrap();
+        git(
+            root,
+            &[
+                "-c",
+                "user.name=test",
+                "-c",
+                "user.email=test@example.com",
+                "commit",
+                "-q",
+                "-m",
+                "init",
+            ],
+        )
+        .unwrap();
+        let head = git(root, &["rev-parse", "HEAD"]).unwrap();
+        let state = root.join(".pb-state");
+
+        let first = Fingerprint::compute(root, &state, &"config").unwrap();
+        assert_eq!(first.commit.as_deref(), Some(head.trim()));
+        // 未跟踪的文件不计入，已跟踪文件的未提交修改计入
+        write(&root.join("untracked.txt"), "x");
+        assert_eq!(
+            Fingerprint::compute(root, &state, &"config").unwrap(),
+            first
+        );
+        write(&root.join("a.txt"), "changed");
+        assert_ne!(
+            Fingerprint::compute(root, &state, &"config").unwrap().files,
+            first.files
+        );
+    }
+
+    #[test]
+    fn state_dir_inside_project_is_excluded() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        write(&root.join("src/main.rs"), "fn main() {}");
+        let state = root.join(".pb-state");
+
+        let first = Fingerprint::compute(root, &state, &"config").unwrap();
+        first.save(&fingerprint_path(&state, "app")).unwrap();
+        assert_eq!(
+            Fingerprint::compute(root, &state, &"config").unwrap(),
+            first
+        );
+    }
+
+    #[test]
+    fn save_and_load_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = fingerprint_path(&dir.path().join("state"), "app");
+        assert_eq!(Fingerprint::load(&path).unwrap(), None);
+        let fingerprint = Fingerprint {
+            commit: Some("abc".to_string()),
+            files: "files".to_string(),
+            config: "config".to_string(),
+        };
+        fingerprint.save(&path).unwrap();
+        assert_eq!(Fingerprint::load(&path).unwrap(), Some(fingerprint));
+    }
+}
@@ -1,2 +1,3 @@
 mod builder;
+pub mod fingerprint;
 pub mod project;
@@ -1,9 +1,18 @@
 use std::fs;
-use std::path::Path;
-use log::{error, info};
+use std::path::{Path, PathBuf};
+use log::{error, info, warn};
 use serde::{Deserialize, Serialize};
-use crate::{docker_utils, git_utils};
+use crate::{command_utils, git_utils};
 use crate::build_utils::builder;
+use crate::build_utils::fingerprint::{self, Fingerprint};
+use crate::docker_utils::compose::ComposeProject;
+use crate::docker_utils::deploy::DeploySpec;
+use crate::git_utils::changelog::ReleaseNotes;
+use crate::git_utils::credentials::GitCredentials;
+use crate::git_utils::mirror::MirrorCache;
+use crate::git_utils::repo::{GitRepo, Revision};
+use crate::git_utils::sync::{self, SyncOptions, SyncReport};
+use std::io;
 
 /// 结构体定义: 存储仓库信息
 #[derive(Debug, Serialize, Deserialize, Default)]
@@ -12,43 +21,89 @@ pub struct Repository {
     pub url: String,
     #[serde(default)]
     pub branch: String,
+    /// 本地有未提交的修改时更新失败，而不是丢弃修改
+    #[serde(default)]
+    pub fail_on_changes: bool,
+    /// 私有仓库的访问凭据
+    #[serde(default)]
+    pub credentials: GitCredentials,
 }
 
 impl Repository {
     /// 创建一个新的仓库实例
     fn new(url: String, branch: String) -> Self {
-        Repository { url, branch }
+        Repository {
+            url,
+            branch,
+            fail_on_changes: false,
+            credentials: GitCredentials::default(),
+        }
     }
 
     /// 克隆仓库到指定路径
-    pub fn clone(&self, path: &str) {
-        match git_utils::clone_latest(&self.url, &self.branch, path) {
+    pub fn clone(&self, path: &Path) {
+        match git_utils::clone_with(&self.url, &self.branch, path, Some(1), &self.credentials) {
             Ok(s) => info!("{}", s),
             Err(e) => info!("{}", e),
         }
     }
 
-    /// 拉取最新的仓库更改
-    fn update(&self) {
-        match git_utils::fetch() {
+    /// 借助镜像缓存克隆仓库到指定路径
+    pub fn clone_from_cache(&self, cache: &MirrorCache, path: &Path) {
+        match cache.checkout(&self.url, &self.branch, path, &self.credentials) {
             Ok(s) => info!("{}", s),
-            Err(e) => info!("{}", e),
+            Err(e) => error!("从镜像克隆失败：{}", e),
         }
     }
+
+    /// 将已有检出同步到配置的分支的最新提交
+    pub fn update(&self, path: &Path) -> io::Result<SyncReport> {
+        let branch = if self.branch.is_empty() {
+            GitRepo::open(path)?.branch()?.ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidInput, "未配置分支且当前不在任何分支上")
+            })?
+        } else {
+            self.branch.clone()
+        };
+        let options = SyncOptions::new()
+            .fail_on_changes(self.fail_on_changes)
+            .credentials(self.credentials.clone());
+        sync::sync(path, &self.url, &branch, &options)
+    }
+}
+
+/// 影响构建结果的配置，用于计算构建指纹
+#[derive(Serialize)]
+struct BuildConfig<'a> {
+    name: &'a str,
+    ports: &'a [String],
+    url: &'a str,
+    branch: &'a str,
+    image: String,
+    builders: Vec<&'a str>,
 }
 
+/// 按需创建构建器
+type BuilderFactory<'a> = Box<dyn Fn() -> Box<dyn builder::Builder> + 'a>;
+
 /// 结构体定义: 存储构建器信息
 #[derive(Debug, Serialize, Deserialize, Default)]
 pub struct Project {
     #[serde(default)]
-    pub path: String,
+    pub path: PathBuf,
     pub name: String,
     #[serde(default)]
     pub ports: Vec<String>,
     #[serde(default)]
     pub repository: Repository,
+    /// 部署配置，未配置时按 `ports` 以默认方式启动
+    #[serde(default)]
+    pub deploy: Option<DeploySpec>,
     #[serde(default)]
     pub build_message: String,
+    /// 最近一次构建的代码版本
+    #[serde(default)]
+    pub revision: Option<Revision>,
     #[serde(skip_serializing, skip_deserializing)]
     pub builder_vec: Vec<(String, Box<dyn builder::Builder>)>,
 }
@@ -56,7 +111,7 @@ pub struct Project {
 impl Project {
     /// 创建一个新的项目实例
     pub fn new(
-        path: String,
+        path: &Path,
         name: String,
         ports: Vec<String>,
         url: String,
@@ -64,11 +119,13 @@ impl Project {
     ) -> Self {
         let repository = Repository::new(url, branch);
         let project = Project {
-            path: path.clone(),
+            path: path.to_path_buf(),
             name,
             ports,
             repository,
+            deploy: None,
             build_message: String::new(),
+            revision: None,
             builder_vec: Vec::new(),
         };
         project.init_info();
@@ -78,96 +135,122 @@ impl Project {
     /// 初始化构建器信息
     fn init_info(&self) {
         info!("初始化构建器！");
-        info!("项目路径：{}，项目名：{}", self.path, self.name);
+        info!("项目路径：{}，项目名：{}", self.path.display(), self.name);
         info!(
             "项目地址：{}，项目分支：{}",
-            self.repository.url, self.repository.branch
+            command_utils::redact(&self.repository.url),
+            self.repository.branch
         );
     }
 
     /// 克隆或拉取仓库
     pub fn get_source_code(&self) {
-        if !Path::new(&self.path).exists() {
+        self.get_source_code_with(None);
+    }
+
+    /// 克隆或拉取仓库，提供镜像缓存时从镜像克隆
+    pub fn get_source_code_with(&self, cache: Option<&MirrorCache>) {
+        if !self.path.exists() {
             //项目目录不存在
             match fs::create_dir_all(&self.path) {
-                Ok(_) => info!("路径创建成功：{}", &self.path),
+                Ok(_) => info!("路径创建成功：{}", self.path.display()),
                 Err(e) => error!("创建路径失败：{}", e),
             }
         }
         //项目目录存在
-        if Path::new(&self.path).join(".git").exists() {
-            //.git存在，进入项目目录，并获取最新代码
+        if self.path.join(".git").exists() {
+            //.git存在，获取最新代码
+            if self.repository.url.is_empty() {
+                info!("未配置仓库地址，跳过更新");
+                return;
+            }
             info!("拉取最新代码");
-            std::env::set_current_dir(&self.path).unwrap();
-            self.repository.update();
+            match self.repository.update(&self.path) {
+                Ok(report) if report.is_up_to_date() => info!("已是最新版本"),
+                Ok(report) => {
+                    info!(
+                        "更新到 {}，共 {} 个新提交",
+                        &report.current[..7],
+                        report.commits.len()
+                    );
+                    for commit in &report.commits {
+                        info!("  {} {} ({})", commit.short_id, commit.summary(), commit.author);
+                    }
+                }
+                Err(e) => error!("更新仓库失败：{}", e),
+            }
         } else {
             //.git不存在
             if !self.repository.url.is_empty() {
                 //项目地址不为空
-                info!("克隆仓库 {}", &self.path);
-                self.repository.clone(&self.path);
+                info!("克隆仓库 {}", self.path.display());
+                match cache {
+                    Some(cache) => self.repository.clone_from_cache(cache, &self.path),
+                    None => self.repository.clone(&self.path),
+                }
             }
         }
     }
 
     /// 初始化构建器
     pub fn init_builder(&mut self) {
-        let path_str = self.path.to_string();
-        let file_types: Vec<(&str, Box<dyn Fn() -> Box<dyn builder::Builder>>)> = vec![
+        let path = self.path.clone();
+        let image = self.deploy_spec().image_ref(&self.name);
+        let file_types: Vec<(&str, BuilderFactory)> = vec![
             (
                 "pom.xml",
                 Box::new(|| {
-                    Box::new(builder::Maven::new(path_str.clone())) as Box<dyn builder::Builder>
+                    Box::new(builder::Maven::new(path.clone())) as Box<dyn builder::Builder>
                 }),
             ),
             (
                 "build.gradle",
                 Box::new(|| {
-                    Box::new(builder::Gradle::new(path_str.clone())) as Box<dyn builder::Builder>
+                    Box::new(builder::Gradle::new(path.clone())) as Box<dyn builder::Builder>
                 }),
             ),
             (
                 "requirements.txt",
                 Box::new(|| {
-                    Box::new(builder::Python::new(path_str.clone())) as Box<dyn builder::Builder>
+                    Box::new(builder::Python::new(path.clone())) as Box<dyn builder::Builder>
                 }),
             ),
             (
                 "package.json",
                 Box::new(|| {
-                    Box::new(builder::Node::new(path_str.clone())) as Box<dyn builder::Builder>
+                    Box::new(builder::Node::new(path.clone())) as Box<dyn builder::Builder>
                 }),
             ),
             (
                 "go.mod",
                 Box::new(|| {
-                    Box::new(builder::Go::new(path_str.clone())) as Box<dyn builder::Builder>
+                    Box::new(builder::Go::new(path.clone())) as Box<dyn builder::Builder>
                 }),
             ),
             (
                 "CMakeLists.txt",
                 Box::new(|| {
-                    Box::new(builder::C::new(path_str.clone())) as Box<dyn builder::Builder>
+                    Box::new(builder::C::new(path.clone())) as Box<dyn builder::Builder>
                 }),
             ),
             (
                 "Cargo.toml",
                 Box::new(|| {
-                    Box::new(builder::Rust::new(path_str.clone())) as Box<dyn builder::Builder>
+                    Box::new(builder::Rust::new(path.clone())) as Box<dyn builder::Builder>
                 }),
             ),
             (
                 "Dockerfile",
                 Box::new(|| {
                     Box::new(builder::Docker::new(
-                        path_str.clone(),
-                        self.name.to_string(),
+                        path.clone(),
+                        image.clone(),
                     )) as Box<dyn builder::Builder>
                 }),
             ),
         ];
         for (file_type, create_builder) in file_types {
-            if Path::new(&format!("{}/{}", path_str, file_type)).exists() {
+            if self.path.join(file_type).exists() {
                 info!("发现文件 {}。", file_type);
                 self.builder_vec
                     .push((file_type.to_string(), create_builder()));
@@ -177,7 +260,6 @@ impl Project {
 
     /// 构建项目
     pub fn build(&mut self) {
-        std::env::set_current_dir(&self.path).unwrap();
         if self.builder_vec.is_empty() {
             error!("没有找到任何可构建的文件！");
             return;
@@ -186,16 +268,133 @@ impl Project {
             builder.build().expect("构建出错");
         }
 
-        info!("构建项目 {} 结束。", self.name);
-        self.build_message = format!("{}", self.name);
+        self.revision = self.current_revision();
+        match &self.revision {
+            Some(revision) => {
+                info!("构建项目 {} 结束，版本：{}。", self.name, revision);
+                self.build_message = format!("{} {}", self.name, revision);
+                if let Some(notes) = self.release_notes() {
+                    self.build_message.push_str("\n\n");
+                    self.build_message.push_str(&notes.to_markdown());
+                }
+            }
+            None => {
+                info!("构建项目 {} 结束。", self.name);
+                self.build_message = self.name.to_string();
+            }
+        }
+    }
+
+    /// 当前源码和构建配置的指纹，需在 `init_builder` 之后调用
+    ///
+    /// `state_dir` 为保存指纹的目录，位于项目目录内时不计入指纹。
+    pub fn fingerprint(&self, state_dir: &Path) -> io::Result<Fingerprint> {
+        let config = BuildConfig {
+            name: &self.name,
+            ports: &self.ports,
+            url: &self.repository.url,
+            branch: &self.repository.branch,
+            image: self.deploy_spec().image_ref(&self.name),
+            builders: self.builder_vec.iter().map(|(key, _)| key.as_str()).collect(),
+        };
+        Fingerprint::compute(&self.path, state_dir, &config)
+    }
+
+    /// 指纹与上次成功构建时相同则跳过构建，构建成功后将指纹保存到 `state_dir`
+    ///
+    /// `force` 为 `true` 时总是构建。
+    pub fn build_if_changed(&mut self, state_dir: &Path, force: bool) {
+        let path = fingerprint::fingerprint_path(state_dir, &self.name);
+        let current = match self.fingerprint(state_dir) {
+            Ok(current) => Some(current),
+            Err(e) => {
+                error!("计算项目 {} 的构建指纹失败：{}", self.name, e);
+                None
+            }
+        };
+        if !force {
+            let saved = Fingerprint::load(&path).unwrap_or_else(|e| {
+                error!("读取构建指纹 {} 失败：{}", path.display(), e);
+                None
+            });
+            if current.is_some() && current == saved {
+                self.revision = self.current_revision();
+                info!("项目 {} 没有变化，跳过构建。", self.name);
+                self.build_message = match &self.revision {
+                    Some(revision) => format!("{} {} 没有变化，跳过构建", self.name, revision),
+                    None => format!("{} 没有变化，跳过构建", self.name),
+                };
+                return;
+            }
+        }
+        self.build();
+        // 没有构建器时构建不会执行，不保存指纹
+        if self.builder_vec.is_empty() {
+            return;
+        }
+        if let Some(current) = current {
+            if let Err(e) = current.save(&path) {
+                error!("保存构建指纹 {} 失败：{}", path.display(), e);
+            }
+        }
+    }
+
+    /// 读取项目目录当前检出的代码版本，不是 git 仓库时返回 `None`
+    pub fn current_revision(&self) -> Option<Revision> {
+        let repo = GitRepo::open(&self.path).ok()?;
+        match repo.revision() {
+            Ok(revision) => revision,
+            Err(e) => {
+                error!("读取项目 {} 的代码版本失败：{}", self.name, e);
+                None
+            }
+        }
+    }
+
+    /// 自上一个版本标签以来的发布说明，不是 git 仓库时返回 `None`
+    pub fn release_notes(&self) -> Option<ReleaseNotes> {
+        let repo = GitRepo::open(&self.path).ok()?;
+        match ReleaseNotes::generate(&repo, "HEAD") {
+            Ok(notes) => Some(notes),
+            Err(e) => {
+                error!("生成项目 {} 的发布说明失败：{}", self.name, e);
+                None
+            }
+        }
     }
 
     /// 部署到docker
+    ///
+    /// 显式配置了 `deploy` 时按其启动单个容器；否则项目目录下有 compose 文件时
+    /// 按 compose 文件启动所有服务，都没有时以项目名启动单个容器。
     pub fn deploy_to_docker(&self) {
+        match (&self.deploy, ComposeProject::find(&self.path)) {
+            (None, Some(path)) => {
+                info!("项目{}按 {} 部署", self.name, path.display());
+                ComposeProject::load(&path)
+                    .and_then(|compose| compose.up(&[]))
+                    .expect("启动 compose 服务出错!");
+                return;
+            }
+            (Some(_), Some(path)) => warn!(
+                "项目{}同时配置了 deploy 和 {}，按 deploy 配置部署",
+                self.name,
+                path.display()
+            ),
+            _ => {}
+        }
         if !self.builder_vec.iter().any(|(key, _)| key == "Dockerfile") {
             error!("项目{}没有对应的Dockerfile文件，无法部署！", self.name);
         }
-        let port_list = self.ports.iter().map(|s| s.as_str()).collect::<Vec<&str>>();
-        docker_utils::container_rerun(&self.name, &port_list).expect("启动docker容器出错!");
+        self.deploy_spec()
+            .deploy(&self.name, &self.path)
+            .expect("启动docker容器出错!");
+    }
+
+    /// 部署配置，未配置 `deploy` 时由 `ports` 生成默认配置
+    pub fn deploy_spec(&self) -> DeploySpec {
+        self.deploy
+            .clone()
+            .unwrap_or_else(|| DeploySpec::from_ports(&self.ports))
     }
 }
@@ -0,0 +1,1243 @@
+use crate::command_utils::shell_split;
+use crate::docker_utils;
+use crate::docker_utils::container_info::{is_anonymous_volume, ContainerInfo};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::{env, fs, io};
+
+/// docker-compose 文件
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct ComposeFile {
+    /// 项目名，为空时使用 compose 文件所在目录名
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub name: String,
+    pub services: BTreeMap<String, Service>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub volumes: BTreeMap<String, VolumeDef>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub networks: BTreeMap<String, NetworkDef>,
+}
+
+/// 服务定义
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct Service {
+    pub image: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub container_name: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub hostname: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub user: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub working_dir: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub entrypoint: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub command: Vec<String>,
+    /// `KEY=VALUE` 形式的环境变量
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub environment: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub ports: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub volumes: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tmpfs: Vec<String>,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub network_mode: String,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub networks: BTreeMap<String, ServiceNetwork>,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub restart: String,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub labels: BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub depends_on: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub cap_add: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub cap_drop: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub extra_hosts: Vec<String>,
+    #[serde(skip_serializing_if = "is_false")]
+    pub privileged: bool,
+}
+
+/// 服务在某个网络中的配置
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct ServiceNetwork {
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<String>,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub ipv4_address: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub ipv6_address: String,
+}
+
+/// 顶层数据卷定义
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct VolumeDef {
+    /// 实际的卷名，不加项目名前缀
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub name: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub driver: String,
+    #[serde(skip_serializing_if = "is_false")]
+    pub external: bool,
+}
+
+/// 顶层网络定义
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct NetworkDef {
+    /// 实际的网络名，不加项目名前缀
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub name: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub driver: String,
+    #[serde(skip_serializing_if = "is_false")]
+    pub external: bool,
+}
+
+fn is_false(b: &bool) -> bool {
+    !*b
+}
+
+/// Docker 内置的网络，不需要在 compose 文件中声明
+const BUILTIN_NETWORKS: [&str; 3] = ["bridge", "host", "none"];
+
+impl ComposeFile {
+    /// 由容器详细信息生成 compose 文件，每个容器对应一个服务
+    ///
+    /// 卷和网络保留原名称，`com.docker.compose.*` 标签不会保留，避免与重新部署时
+    /// compose 自动添加的项目标签冲突。
+    ///
+    /// `depends_on` 按启发式规则推断：仅处于同一网络无法确定依赖方向，因此要求两个容器在
+    /// 同一个自定义网络中，且一个容器的环境变量、命令、入口点或链接以单词形式提到另一个容器
+    /// 的名称或网络别名，此时认为前者依赖后者；互相提到时只保留后创建的容器对先创建的容器的
+    /// 依赖。没有被提到的依赖需要手动补充。
+    pub fn from_containers(containers: &[ContainerInfo]) -> Self {
+        let mut compose = ComposeFile::default();
+        let names: Vec<String> = containers
+            .iter()
+            .map(|c| service_name(c.container_name()))
+            .collect();
+        for (info, name) in containers.iter().zip(&names) {
+            let service = service_from(info, &mut compose);
+            compose.services.insert(name.clone(), service);
+        }
+        for (i, info) in containers.iter().enumerate() {
+            let mut depends_on = Vec::new();
+            for (j, other) in containers.iter().enumerate() {
+                if i == j || !references(info, other) {
+                    continue;
+                }
+                if references(other, info) && info.created < other.created {
+                    continue;
+                }
+                depends_on.push(names[j].clone());
+            }
+            depends_on.sort();
+            if let Some(service) = compose.services.get_mut(&names[i]) {
+                service.depends_on = depends_on;
+            }
+        }
+        compose
+    }
+
+    /// 渲染为 YAML
+    pub fn to_yaml(&self) -> io::Result<String> {
+        serde_yaml::to_string(self).map_err(io::Error::other)
+    }
+
+    /// 解析 compose 文件内容，`vars` 用于变量替换，`dir` 为相对路径的基准目录
+    ///
+    /// 字符串值中的 `${VAR}`、`$VAR`、`${VAR:-默认值}` 等会被替换，`$$` 表示 `$` 本身。
+    /// 环境变量、标签、命令等的多种写法统一为本结构体的形式，`env_file` 的内容合并到
+    /// `environment` 之前。
+    pub fn parse(data: &str, vars: &HashMap<String, String>, dir: &Path) -> io::Result<Self> {
+        let mut value: Value = serde_yaml::from_str(data).map_err(invalid_data)?;
+        interpolate_value(&mut value, vars)?;
+        if let Some(services) = value.get_mut("services").and_then(Value::as_mapping_mut) {
+            for (_, service) in services.iter_mut() {
+                if let Some(service) = service.as_mapping_mut() {
+                    normalize_service(service, vars, dir)?;
+                }
+            }
+        }
+        for key in ["volumes", "networks"] {
+            if let Some(defs) = value.get_mut(key).and_then(Value::as_mapping_mut) {
+                for (_, def) in defs.iter_mut() {
+                    if def.is_null() {
+                        *def = Value::Mapping(Mapping::new());
+                    }
+                }
+            }
+        }
+        serde_yaml::from_value(value).map_err(invalid_data)
+    }
+
+    /// 按 `depends_on` 计算启动顺序，依赖的服务在前
+    ///
+    /// `services` 为空时包含所有服务，否则只包含指定服务及其传递依赖。依赖不存在或
+    /// 存在循环依赖时返回错误。
+    pub fn start_order(&self, services: &[&str]) -> io::Result<Vec<String>> {
+        let mut selected = BTreeSet::new();
+        let mut pending: Vec<&str> = if services.is_empty() {
+            self.services.keys().map(String::as_str).collect()
+        } else {
+            services.to_vec()
+        };
+        while let Some(name) = pending.pop() {
+            let service = self.services.get(name).ok_or_else(|| {
+                io::Error::new(io::ErrorKind::NotFound, format!("服务 {} 不存在", name))
+            })?;
+            if selected.insert(name.to_string()) {
+                pending.extend(service.depends_on.iter().map(String::as_str));
+            }
+        }
+
+        let mut order = Vec::new();
+        let mut done = BTreeSet::new();
+        while done.len() < selected.len() {
+            let ready: Vec<&String> = selected
+                .iter()
+                .filter(|name| !done.contains(*name))
+                .filter(|name| {
+                    self.services[*name]
+                        .depends_on
+                        .iter()
+                        .all(|dep| done.contains(dep))
+                })
+                .collect();
+            if ready.is_empty() {
+                let rest: Vec<&String> = selected.difference(&done).collect();
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("服务之间存在循环依赖：{:?}", rest),
+                ));
+            }
+            for name in ready {
+                order.push(name.clone());
+            }
+            done.extend(order[done.len()..].iter().cloned());
+        }
+        Ok(order)
+    }
+}
+
+/// 由单个容器生成服务，用到的数据卷和网络登记到顶层定义中
+fn service_from(info: &ContainerInfo, compose: &mut ComposeFile) -> Service {
+    let config = &info.config;
+    let host = &info.host_config;
+    let (volumes, tmpfs) = info.parse_mounts(&mut Vec::new());
+    for mount in &info.mounts {
+        if mount.kind == "volume" && !is_anonymous_volume(&mount.name) {
+            let driver = match mount.driver.as_str() {
+                "local" => String::new(),
+                driver => driver.to_string(),
+            };
+            compose.volumes.insert(
+                mount.name.clone(),
+                VolumeDef {
+                    name: mount.name.clone(),
+                    driver,
+                    external: false,
+                },
+            );
+        }
+    }
+    let mut network_mode = host.network_mode.clone();
+    let mut networks = BTreeMap::new();
+    match network_mode.as_str() {
+        "" | "default" | "bridge" => network_mode = "bridge".to_string(),
+        "host" | "none" => {}
+        mode if mode.starts_with("container:") => {}
+        _ => {
+            // 自定义网络以 networks 声明，可以同时加入多个网络
+            network_mode.clear();
+            for (name, endpoint) in &info.network_settings.networks {
+                if BUILTIN_NETWORKS.contains(&name.as_str()) {
+                    continue;
+                }
+                let (ipv4_address, ipv6_address) = match &endpoint.ipam_config {
+                    Some(ipam) => (ipam.ipv4_address.clone(), ipam.ipv6_address.clone()),
+                    None => (String::new(), String::new()),
+                };
+                networks.insert(
+                    name.clone(),
+                    ServiceNetwork {
+                        aliases: info.parse_aliases(endpoint),
+                        ipv4_address,
+                        ipv6_address,
+                    },
+                );
+                compose.networks.insert(
+                    name.clone(),
+                    NetworkDef {
+                        name: name.clone(),
+                        ..NetworkDef::default()
+                    },
+                );
+            }
+        }
+    }
+    Service {
+        image: config.image.clone(),
+        container_name: info.container_name().to_string(),
+        hostname: escape(&info.parse_hostname()),
+        user: escape(&config.user),
+        working_dir: escape(&config.working_dir),
+        entrypoint: config.entrypoint.iter().map(|s| escape(s)).collect(),
+        command: config.cmd.iter().map(|s| escape(s)).collect(),
+        environment: config.env.iter().map(|s| escape(s)).collect(),
+        ports: info.parse_port_bindings(),
+        volumes: volumes.iter().map(|s| escape(s)).collect(),
+        tmpfs,
+        network_mode,
+        networks,
+        restart: info.parse_restart_policy(),
+        labels: config
+            .labels
+            .iter()
+            .filter(|(k, _)| !k.starts_with("com.docker.compose."))
+            .map(|(k, v)| (k.clone(), escape(v)))
+            .collect(),
+        depends_on: Vec::new(),
+        cap_add: host.cap_add.clone(),
+        cap_drop: host.cap_drop.clone(),
+        extra_hosts: host.extra_hosts.clone(),
+        privileged: host.privileged,
+    }
+}
+
+/// `info` 与 `other` 在同一个自定义网络中，且 `info` 通过名称或别名引用了 `other`
+fn references(info: &ContainerInfo, other: &ContainerInfo) -> bool {
+    let mut names = BTreeSet::new();
+    names.insert(other.container_name().to_string());
+    let mut shared = false;
+    for (network, endpoint) in &other.network_settings.networks {
+        if BUILTIN_NETWORKS.contains(&network.as_str())
+            || !info.network_settings.networks.contains_key(network)
+        {
+            continue;
+        }
+        shared = true;
+        names.extend(other.parse_aliases(endpoint));
+    }
+    if !shared {
+        return false;
+    }
+    let texts = info
+        .config
+        .env
+        .iter()
+        .filter_map(|env| env.split_once('=').map(|(_, value)| value))
+        .chain(info.config.cmd.iter().map(String::as_str))
+        .chain(info.config.entrypoint.iter().map(String::as_str))
+        .chain(info.host_config.links.iter().map(String::as_str));
+    for text in texts {
+        let words = text.split(|c: char| !(c.is_ascii_alphanumeric() || "._-".contains(c)));
+        if words.into_iter().any(|word| names.contains(word)) {
+            return true;
+        }
+    }
+    false
+}
+
+/// 容器名转为服务名，服务名只能包含字母、数字和 `._-`
+fn service_name(container_name: &str) -> String {
+    container_name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || "._-".contains(c) {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// compose 会对 `$` 做变量替换，原样保留的值需要写作 `$$`
+fn escape(value: &str) -> String {
+    value.replace('$', "$$")
+}
+
+/// compose 文件的默认文件名，按优先级排列
+pub const COMPOSE_FILE_NAMES: [&str; 4] = [
+    "compose.yaml",
+    "compose.yml",
+    "docker-compose.yaml",
+    "docker-compose.yml",
+];
+
+/// 已加载的 compose 项目，通过 docker 命令启动、停止和重启服务
+///
+/// 资源命名与 `docker compose` 相同：容器为 `<项目>-<服务>-1`，网络和数据卷为
+/// `<项目>_<名称>`，指定了 `container_name` 或 `name` 时使用指定的名称。没有声明网络的服务
+/// 加入 `<项目>_default` 网络，服务名会作为网络别名。
+#[derive(Debug, Clone)]
+pub struct ComposeProject {
+    name: String,
+    dir: PathBuf,
+    file: ComposeFile,
+}
+
+impl ComposeProject {
+    /// 在目录下查找 compose 文件
+    pub fn find(dir: &Path) -> Option<PathBuf> {
+        COMPOSE_FILE_NAMES
+            .iter()
+            .map(|name| dir.join(name))
+            .find(|path| path.is_file())
+    }
+
+    /// 加载 compose 文件，同目录下的 `.env` 用于变量替换，进程环境变量优先
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let path = fs::canonicalize(path)?;
+        let dir = path.parent().unwrap_or(Path::new("/")).to_path_buf();
+        let mut vars = match fs::read_to_string(dir.join(".env")) {
+            Ok(data) => parse_env_file(&data, &HashMap::new())?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(e),
+        };
+        vars.extend(env::vars());
+        let file = ComposeFile::parse(&fs::read_to_string(&path)?, &vars, &dir)?;
+        let name = vars
+            .get("COMPOSE_PROJECT_NAME")
+            .cloned()
+            .filter(|name| !name.is_empty())
+            .or_else(|| Some(file.name.clone()).filter(|name| !name.is_empty()))
+            .unwrap_or_else(|| {
+                dir.file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default()
+            });
+        let name: String = name
+            .to_lowercase()
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == '_')
+            .collect();
+        info!("加载 compose 项目 {}：{}", name, path.display());
+        Ok(ComposeProject { name, dir, file })
+    }
+
+    /// 项目名
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// compose 文件内容
+    pub fn file(&self) -> &ComposeFile {
+        &self.file
+    }
+
+    /// 服务对应的容器名
+    pub fn container_name(&self, service: &str) -> String {
+        match self.file.services.get(service) {
+            Some(s) if !s.container_name.is_empty() => s.container_name.clone(),
+            _ => format!("{}-{}-1", self.name, service),
+        }
+    }
+
+    /// 创建并启动服务
+    ///
+    /// `services` 为空时启动所有服务，否则同时启动它们依赖的服务。不会比较配置是否变化，
+    /// 已存在的容器总是先停止、删除再重新创建，容器内未挂载到数据卷的数据会丢失。
+    pub fn up(&self, services: &[&str]) -> io::Result<()> {
+        let order = self.file.start_order(services)?;
+        for name in &order {
+            self.ensure_resources(&self.file.services[name])?;
+        }
+        for name in &order {
+            let container = self.container_name(name);
+            if docker_utils::container_exists(&container) {
+                docker_utils::container_stop(&[&container])?;
+                docker_utils::container_remove(&[&container])?;
+            }
+            let args = self.create_args(name)?;
+            let args: Vec<&str> = args.iter().map(String::as_str).collect();
+            docker_utils::container_create(&args)?;
+            // 第一个网络在创建时指定，其余网络创建后再连接
+            for (network, config) in self.service_networks(name).iter().skip(1) {
+                let mut aliases = vec![name.as_str()];
+                aliases.extend(config.aliases.iter().map(String::as_str));
+                docker_utils::network_connect(&self.network_name(network), &container, &aliases)?;
+            }
+            docker_utils::container_start(&[&container])?;
+        }
+        Ok(())
+    }
+
+    /// 停止并删除服务的容器，按启动顺序的逆序执行
+    ///
+    /// `services` 为空时删除所有服务，并删除项目创建的网络，数据卷保留。
+    pub fn down(&self, services: &[&str]) -> io::Result<()> {
+        let order = self.file.start_order(services)?;
+        for name in order.iter().rev() {
+            if !services.is_empty() && !services.contains(&name.as_str()) {
+                continue;
+            }
+            let container = self.container_name(name);
+            if docker_utils::container_exists(&container) {
+                docker_utils::container_stop(&[&container])?;
+                docker_utils::container_remove(&[&container])?;
+            }
+        }
+        if services.is_empty() {
+            for network in self.used_networks() {
+                let external = self.file.networks.get(&network).is_some_and(|n| n.external);
+                let name = self.network_name(&network);
+                if !external && docker_utils::network_exists(&name) {
+                    if let Err(e) = docker_utils::network_remove(&name) {
+                        warn!("删除网络 {} 失败：{}", name, e);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// 按启动顺序重启服务，`services` 为空时重启所有服务
+    ///
+    /// 只重启指定的服务，它们依赖的服务仅用于确定顺序。
+    pub fn restart(&self, services: &[&str]) -> io::Result<()> {
+        for name in self.file.start_order(services)? {
+            if services.is_empty() || services.contains(&name.as_str()) {
+                docker_utils::container_restart(&[&self.container_name(&name)])?;
+            }
+        }
+        Ok(())
+    }
+
+    /// 网络的实际名称
+    fn network_name(&self, key: &str) -> String {
+        match self.file.networks.get(key) {
+            Some(def) if !def.name.is_empty() => def.name.clone(),
+            Some(def) if def.external => key.to_string(),
+            _ => format!("{}_{}", self.name, key),
+        }
+    }
+
+    /// 数据卷的实际名称
+    fn volume_name(&self, key: &str) -> String {
+        match self.file.volumes.get(key) {
+            Some(def) if !def.name.is_empty() => def.name.clone(),
+            Some(def) if def.external => key.to_string(),
+            _ => format!("{}_{}", self.name, key),
+        }
+    }
+
+    /// 服务加入的网络，按名称排序，未指定网络模式和网络时为默认网络
+    fn service_networks(&self, service: &str) -> Vec<(String, ServiceNetwork)> {
+        let service = &self.file.services[service];
+        if !service.network_mode.is_empty() {
+            return Vec::new();
+        }
+        if service.networks.is_empty() {
+            return vec![("default".to_string(), ServiceNetwork::default())];
+        }
+        service
+            .networks
+            .iter()
+            .map(|(name, config)| (name.clone(), config.clone()))
+            .collect()
+    }
+
+    /// 所有服务用到的网络
+    fn used_networks(&self) -> BTreeSet<String> {
+        self.file
+            .services
+            .keys()
+            .flat_map(|name| self.service_networks(name))
+            .map(|(network, _)| network)
+            .collect()
+    }
+
+    /// 创建服务用到但还不存在的网络和数据卷
+    fn ensure_resources(&self, service: &Service) -> io::Result<()> {
+        let label = format!("com.docker.compose.project={}", self.name);
+        let networks: Vec<String> =
+            if service.network_mode.is_empty() && service.networks.is_empty() {
+                vec!["default".to_string()]
+            } else {
+                service.networks.keys().cloned().collect()
+            };
+        for key in networks {
+            let def = self.file.networks.get(&key).cloned().unwrap_or_default();
+            let name = self.network_name(&key);
+            if def.external || docker_utils::network_exists(&name) {
+                continue;
+            }
+            let driver = Some(def.driver.as_str()).filter(|d| !d.is_empty());
+            docker_utils::network_create(&name, driver, &[&label])?;
+        }
+        for volume in &service.volumes {
+            let key = match named_volume(volume) {
+                Some(key) => key,
+                None => continue,
+            };
+            let def = self.file.volumes.get(key).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("数据卷 {} 没有在顶层 volumes 中声明", key),
+                )
+            })?;
+            let name = self.volume_name(key);
+            if def.external || docker_utils::volume_exists(&name) {
+                continue;
+            }
+            let driver = Some(def.driver.as_str()).filter(|d| !d.is_empty());
+            docker_utils::volume_create(&name, driver, &[&label])?;
+        }
+        Ok(())
+    }
+
+    /// `docker create` 的参数
+    fn create_args(&self, name: &str) -> io::Result<Vec<String>> {
+        let service = &self.file.services[name];
+        if service.image.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("服务 {} 没有指定镜像", name),
+            ));
+        }
+        let mut args = vec!["--name".to_string(), self.container_name(name)];
+        let mut push = |flag: &str, value: &str| {
+            args.push(flag.to_string());
+            args.push(value.to_string());
+        };
+        push(
+            "--label",
+            &format!("com.docker.compose.project={}", self.name),
+        );
+        push("--label", &format!("com.docker.compose.service={}", name));
+        let strings = [
+            ("--hostname", &service.hostname),
+            ("--user", &service.user),
+            ("--workdir", &service.working_dir),
+            ("--restart", &service.restart),
+        ];
+        for (flag, value) in strings {
+            if !value.is_empty() {
+                push(flag, value);
+            }
+        }
+        if let Some(entrypoint) = service.entrypoint.first() {
+            push("--entrypoint", entrypoint);
+        }
+        match service.network_mode.strip_prefix("service:") {
+            Some(other) => push(
+                "--network",
+                &format!("container:{}", self.container_name(other)),
+            ),
+            None if !service.network_mode.is_empty() => push("--network", &service.network_mode),
+            None => {}
+        }
+        if let Some((network, config)) = self.service_networks(name).first() {
+            push("--network", &self.network_name(network));
+            push("--network-alias", name);
+            for alias in &config.aliases {
+                push("--network-alias", alias);
+            }
+            if !config.ipv4_address.is_empty() {
+                push("--ip", &config.ipv4_address);
+            }
+            if !config.ipv6_address.is_empty() {
+                push("--ip6", &config.ipv6_address);
+            }
+        }
+        for volume in &service.volumes {
+            let volume = match named_volume(volume) {
+                Some(key) => format!("{}{}", self.volume_name(key), &volume[key.len()..]),
+                None => resolve_bind(volume, &self.dir),
+            };
+            push("-v", &volume);
+        }
+        let lists = [
+            ("-p", &service.ports),
+            ("--tmpfs", &service.tmpfs),
+            ("-e", &service.environment),
+            ("--cap-add", &service.cap_add),
+            ("--cap-drop", &service.cap_drop),
+            ("--add-host", &service.extra_hosts),
+        ];
+        for (flag, values) in lists {
+            for value in values {
+                push(flag, value);
+            }
+        }
+        for (key, value) in &service.labels {
+            push("--label", &format!("{}={}", key, value));
+        }
+        if service.privileged {
+            args.push("--privileged".to_string());
+        }
+        args.push(service.image.clone());
+        args.extend(service.entrypoint.iter().skip(1).cloned());
+        args.extend(service.command.iter().cloned());
+        Ok(args)
+    }
+}
+
+/// 短格式挂载中的命名数据卷，例如 `data:/data` 中的 `data`
+fn named_volume(volume: &str) -> Option<&str> {
+    let (source, _) = volume.split_once(':')?;
+    let is_path = source.is_empty()
+        || source.starts_with('/')
+        || source.starts_with('.')
+        || source.starts_with('~')
+        || source.contains('/');
+    if is_path {
+        None
+    } else {
+        Some(source)
+    }
+}
+
+/// 绑定挂载中的相对路径以 compose 文件所在目录为基准
+pub(crate) fn resolve_bind(volume: &str, dir: &Path) -> String {
+    match volume.split_once(':') {
+        Some((source, rest)) if source.starts_with('.') => {
+            let source = source.strip_prefix("./").unwrap_or(source);
+            let path = if source == "." {
+                dir.to_path_buf()
+            } else {
+                dir.join(source)
+            };
+            format!("{}:{}", path.display(), rest)
+        }
+        Some((source, rest)) if source.starts_with('~') => match env::var("HOME") {
+            Ok(home) => format!("{}{}:{}", home, &source[1..], rest),
+            Err(_) => volume.to_string(),
+        },
+        _ => volume.to_string(),
+    }
+}
+
+/// 解析 `.env` 格式的变量文件
+///
+/// 支持注释、`export` 前缀和引号：单引号内原样保留，双引号和不带引号的值会用已定义的
+/// 变量做替换，不带引号的值中 ` #` 之后为注释。
+pub fn parse_env_file(
+    data: &str,
+    vars: &HashMap<String, String>,
+) -> io::Result<HashMap<String, String>> {
+    let mut env_vars = HashMap::new();
+    for line in data.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let (key, value) = match line.split_once('=') {
+            Some((key, value)) => (key.trim(), value.trim()),
+            None => continue,
+        };
+        let mut scope = vars.clone();
+        scope.extend(env_vars.clone());
+        let value = if let Some(inner) = value.strip_prefix('\'').and_then(|v| v.strip_suffix('\''))
+        {
+            inner.to_string()
+        } else if let Some(inner) = value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+            interpolate(&inner.replace("\\n", "\n").replace("\\\"", "\""), &scope)?
+        } else {
+            let value = value.split(" #").next().unwrap_or_default().trim_end();
+            interpolate(value, &scope)?
+        };
+        env_vars.insert(key.to_string(), value);
+    }
+    Ok(env_vars)
+}
+
+/// 替换字符串中的变量
+///
+/// 支持 `$VAR`、`${VAR}`、`${VAR:-默认值}`、`${VAR-默认值}`、`${VAR:?错误}`、`${VAR?错误}`、
+/// `${VAR:+替换值}`、`${VAR+替换值}`，`$$` 表示 `$` 本身。未定义的变量替换为空字符串。
+pub fn interpolate(text: &str, vars: &HashMap<String, String>) -> io::Result<String> {
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(i) = rest.find('$') {
+        output.push_str(&rest[..i]);
+        rest = &rest[i + 1..];
+        if let Some(tail) = rest.strip_prefix('$') {
+            output.push('$');
+            rest = tail;
+        } else if let Some(tail) = rest.strip_prefix('{') {
+            // 找到匹配的右括号，默认值中可以嵌套变量
+            let mut depth = 1;
+            let end = tail
+                .char_indices()
+                .find(|(_, c)| {
+                    match c {
+                        '{' => depth += 1,
+                        '}' => depth -= 1,
+                        _ => {}
+                    }
+                    depth == 0
+                })
+                .map(|(i, _)| i)
+                .ok_or_else(|| invalid_data(format!("变量缺少右括号：{}", text)))?;
+            output.push_str(&substitute(&tail[..end], vars)?);
+            rest = &tail[end + 1..];
+        } else {
+            let end = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            if end == 0 || rest.starts_with(|c: char| c.is_ascii_digit()) {
+                output.push('$');
+            } else {
+                output.push_str(&lookup(&rest[..end], vars));
+                rest = &rest[end..];
+            }
+        }
+    }
+    output.push_str(rest);
+    Ok(output)
+}
+
+/// 替换 `${...}` 中的表达式
+fn substitute(expr: &str, vars: &HashMap<String, String>) -> io::Result<String> {
+    let end = expr
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .unwrap_or(expr.len());
+    let (name, op) = expr.split_at(end);
+    if name.is_empty() {
+        return Err(invalid_data(format!("无效的变量：${{{}}}", expr)));
+    }
+    let value = vars.get(name);
+    let set = value.is_some();
+    let non_empty = value.is_some_and(|v| !v.is_empty());
+    if op.is_empty() {
+        return Ok(lookup(name, vars));
+    }
+    let (op, arg) = [":-", ":?", ":+", "-", "?", "+"]
+        .iter()
+        .find_map(|prefix| op.strip_prefix(prefix).map(|arg| (*prefix, arg)))
+        .ok_or_else(|| invalid_data(format!("无效的变量：${{{}}}", expr)))?;
+    match op {
+        ":-" if !non_empty => interpolate(arg, vars),
+        "-" if !set => interpolate(arg, vars),
+        ":?" if !non_empty => Err(invalid_data(format!("变量 {} 未设置：{}", name, arg))),
+        "?" if !set => Err(invalid_data(format!("变量 {} 未设置：{}", name, arg))),
+        ":+" if non_empty => interpolate(arg, vars),
+        "+" if set => interpolate(arg, vars),
+        ":+" | "+" => Ok(String::new()),
+        _ => Ok(lookup(name, vars)),
+    }
+}
+
+fn lookup(name: &str, vars: &HashMap<String, String>) -> String {
+    match vars.get(name) {
+        Some(value) => value.clone(),
+        None => {
+            warn!("变量 {} 未设置，使用空字符串", name);
+            String::new()
+        }
+    }
+}
+
+/// 替换 YAML 中所有字符串值的变量，键不做替换
+fn interpolate_value(value: &mut Value, vars: &HashMap<String, String>) -> io::Result<()> {
+    match value {
+        Value::String(s) => *s = interpolate(s, vars)?,
+        Value::Sequence(seq) => {
+            for item in seq {
+                interpolate_value(item, vars)?;
+            }
+        }
+        Value::Mapping(map) => {
+            for (_, item) in map.iter_mut() {
+                interpolate_value(item, vars)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// 把服务定义中的各种写法统一为 [`Service`] 的形式
+fn normalize_service(
+    service: &mut Mapping,
+    vars: &HashMap<String, String>,
+    dir: &Path,
+) -> io::Result<()> {
+    for key in ["command", "entrypoint"] {
+        if let Some(Value::String(line)) = service.get(key) {
+            let words = shell_split(line).into_iter().map(Value::String).collect();
+            service.insert(key.into(), Value::Sequence(words));
+        }
+    }
+    for key in ["tmpfs", "env_file", "dns"] {
+        if let Some(value @ Value::String(_)) = service.get(key) {
+            service.insert(key.into(), Value::Sequence(vec![value.clone()]));
+        }
+    }
+
+    // env_file 在前，environment 中的同名变量覆盖它
+    let mut environment = Vec::new();
+    if let Some(files) = service.remove("env_file") {
+        for file in files.as_sequence().into_iter().flatten() {
+            let path = match file {
+                Value::String(path) => path.clone(),
+                Value::Mapping(map) => map
+                    .get("path")
+                    .and_then(Value::as_str)
+                    .unwrap_or_default()
+                    .to_string(),
+                _ => continue,
+            };
+            let data = fs::read_to_string(dir.join(&path))?;
+            let mut file_vars: Vec<(String, String)> =
+                parse_env_file(&data, vars)?.into_iter().collect();
+            file_vars.sort();
+            environment.extend(file_vars.into_iter().map(|(k, v)| format!("{}={}", k, v)));
+        }
+    }
+    match service.remove("environment") {
+        Some(Value::Mapping(map)) => {
+            for (key, value) in map {
+                let key = scalar_string(&key);
+                match value {
+                    Value::Null => environment.extend(pass_through(&key, vars)),
+                    value => environment.push(format!("{}={}", key, scalar_string(&value))),
+                }
+            }
+        }
+        Some(Value::Sequence(seq)) => {
+            for item in seq {
+                let item = scalar_string(&item);
+                if item.contains('=') {
+                    environment.push(item);
+                } else {
+                    environment.extend(pass_through(&item, vars));
+                }
+            }
+        }
+        _ => {}
+    }
+    if !environment.is_empty() {
+        let environment = environment.into_iter().map(Value::String).collect();
+        service.insert("environment".into(), Value::Sequence(environment));
+    }
+
+    if let Some(Value::Sequence(seq)) = service.get("labels") {
+        let mut labels = Mapping::new();
+        for item in seq {
+            let item = scalar_string(item);
+            let (key, value) = item.split_once('=').unwrap_or((&item, ""));
+            labels.insert(key.into(), value.into());
+        }
+        service.insert("labels".into(), Value::Mapping(labels));
+    }
+    if let Some(Value::Mapping(map)) = service.get("extra_hosts") {
+        let hosts = map
+            .iter()
+            .map(|(host, ip)| {
+                Value::String(format!("{}:{}", scalar_string(host), scalar_string(ip)))
+            })
+            .collect();
+        service.insert("extra_hosts".into(), Value::Sequence(hosts));
+    }
+    if let Some(Value::Mapping(map)) = service.get("depends_on") {
+        let names = map.keys().cloned().collect();
+        service.insert("depends_on".into(), Value::Sequence(names));
+    }
+    match service.get_mut("networks") {
+        Some(Value::Sequence(seq)) => {
+            let networks = seq
+                .iter()
+                .map(|name| (name.clone(), Value::Mapping(Mapping::new())))
+                .collect();
+            service.insert("networks".into(), Value::Mapping(networks));
+        }
+        Some(Value::Mapping(map)) => {
+            for (_, config) in map.iter_mut() {
+                if config.is_null() {
+                    *config = Value::Mapping(Mapping::new());
+                }
+            }
+        }
+        _ => {}
+    }
+    if let Some(Value::Sequence(ports)) = service.get_mut("ports") {
+        for port in ports.iter_mut() {
+            *port = Value::String(match &*port {
+                Value::Mapping(map) => long_port(map),
+                port => scalar_string(port),
+            });
+        }
+    }
+    if let Some(Value::Sequence(volumes)) = service.get_mut("volumes") {
+        for volume in volumes.iter_mut() {
+            *volume = Value::String(match &*volume {
+                Value::Mapping(map) => long_volume(map),
+                volume => scalar_string(volume),
+            });
+        }
+    }
+    // 其余列表中的数字、布尔值转为字符串
+    for key in [
+        "command",
+        "entrypoint",
+        "cap_add",
+        "cap_drop",
+        "tmpfs",
+        "depends_on",
+    ] {
+        if let Some(Value::Sequence(seq)) = service.get_mut(key) {
+            for item in seq.iter_mut() {
+                *item = Value::String(scalar_string(item));
+            }
+        }
+    }
+    if let Some(Value::Mapping(labels)) = service.get_mut("labels") {
+        for (_, value) in labels.iter_mut() {
+            *value = Value::String(scalar_string(value));
+        }
+    }
+    for key in ["restart", "user", "hostname", "container_name", "image"] {
+        if let Some(value) = service.get_mut(key) {
+            *value = Value::String(scalar_string(value));
+        }
+    }
+    Ok(())
+}
+
+/// 只写变量名的环境变量取宿主机的值，未设置时忽略
+fn pass_through(key: &str, vars: &HashMap<String, String>) -> Option<String> {
+    vars.get(key).map(|value| format!("{}={}", key, value))
+}
+
+/// 长格式端口映射转为 `[host_ip:]published:target/protocol`
+fn long_port(map: &Mapping) -> String {
+    let field = |key: &str| map.get(key).map(scalar_string).unwrap_or_default();
+    let mut port = field("target");
+    let protocol = field("protocol");
+    if !protocol.is_empty() {
+        port = format!("{}/{}", port, protocol);
+    }
+    let published = field("published");
+    if !published.is_empty() {
+        port = format!("{}:{}", published, port);
+    }
+    let host_ip = field("host_ip");
+    if !host_ip.is_empty() {
+        port = format!("{}:{}", host_ip, port);
+    }
+    port
+}
+
+/// 长格式挂载转为 `source:target[:ro]`
+fn long_volume(map: &Mapping) -> String {
+    let field = |key: &str| map.get(key).map(scalar_string).unwrap_or_default();
+    let source = field("source");
+    let mut volume = if source.is_empty() {
+        field("target")
+    } else {
+        format!("{}:{}", source, field("target"))
+    };
+    if map.get("read_only").and_then(Value::as_bool) == Some(true) {
+        volume.push_str(if source.is_empty() { "" } else { ":ro" });
+    }
+    volume
+}
+
+/// 标量转为字符串，YAML 中的端口号、布尔值等都按字符串使用
+fn scalar_string(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Number(n) => n.to_string(),
+        Value::Bool(b) => b.to_string(),
+        Value::Null => String::new(),
+        value => serde_yaml::to_string(value)
+            .unwrap_or_default()
+            .trim_end()
+            .to_string(),
+    }
+}
+
+fn invalid_data(e: impl ToString) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn container(value: serde_json::Value) -> ContainerInfo {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn from_containers_infers_depends_on_and_drops_compose_labels() {
+        let db = container(serde_json::json!({
+            "Id": "aaa",
+            "Name": "/db",
+            "Created": "2024-01-01T00:00:00Z",
+            "HostConfig": { "NetworkMode": "backend" },
+            "Config": {
+                "Image": "postgres",
+                "Labels": {
+                    "com.docker.compose.project": "old",
+                    "team": "data"
+                }
+            },
+            "NetworkSettings": { "Networks": { "backend": { "Aliases": ["database"] } } }
+        }));
+        let web = container(serde_json::json!({
+            "Id": "bbb",
+            "Name": "/web",
+            "Created": "2024-01-02T00:00:00Z",
+            "HostConfig": { "NetworkMode": "backend" },
+            "Config": {
+                "Image": "app",
+                "Env": ["DB_URL=postgres://u:p@database:5432/x"]
+            },
+            "NetworkSettings": { "Networks": { "backend": {} } }
+        }));
+        // 不在同一网络中，即使提到了名称也不认为有依赖
+        let other = container(serde_json::json!({
+            "Id": "ccc",
+            "Name": "/other",
+            "HostConfig": { "NetworkMode": "bridge" },
+            "Config": { "Image": "app", "Cmd": ["ping", "db"] }
+        }));
+
+        let compose = ComposeFile::from_containers(&[db, web, other]);
+        assert_eq!(compose.services["web"].depends_on, vec!["db"]);
+        assert!(compose.services["db"].depends_on.is_empty());
+        assert!(compose.services["other"].depends_on.is_empty());
+        let labels = &compose.services["db"].labels;
+        assert_eq!(labels.len(), 1);
+        assert_eq!(labels["team"], "data");
+        assert!(compose.networks.contains_key("backend"));
+    }
+
+    fn vars(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    fn parse(data: &str) -> ComposeFile {
+        ComposeFile::parse(data, &HashMap::new(), Path::new("/srv")).unwrap()
+    }
+
+    #[test]
+    fn start_order_puts_dependencies_first() {
+        let compose = parse(
+            "
+services:
+  web: { image: app, depends_on: [api, cache] }
+  api: { image: app, depends_on: { db: { condition: service_started } } }
+  db: { image: postgres }
+  cache: { image: redis }
+  worker: { image: app, depends_on: [db] }
+",
+        );
+        let order = compose.start_order(&[]).unwrap();
+        let position = |name: &str| order.iter().position(|n| n == name).unwrap();
+        assert_eq!(order.len(), 5);
+        assert!(position("db") < position("api"));
+        assert!(position("api") < position("web"));
+        assert!(position("cache") < position("web"));
+        assert!(position("db") < position("worker"));
+
+        assert_eq!(compose.start_order(&["api"]).unwrap(), vec!["db", "api"]);
+        let err = compose.start_order(&["missing"]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn start_order_detects_cycles() {
+        let compose = parse(
+            "
+services:
+  a: { image: x, depends_on: [b] }
+  b: { image: x, depends_on: [c] }
+  c: { image: x, depends_on: [a] }
+  d: { image: x }
+",
+        );
+        let err = compose.start_order(&[]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(compose.start_order(&["a"]).is_err());
+        // 只涉及无关服务时不受循环影响
+        assert_eq!(compose.start_order(&["d"]).unwrap(), vec!["d"]);
+    }
+
+    #[test]
+    fn interpolate_supports_defaults_and_errors() {
+        let vars = vars(&[("NAME", "app"), ("EMPTY", "")]);
+        let cases = [
+            ("$NAME-${NAME}", "app-app"),
+            ("$$NAME", "$NAME"),
+            ("${EMPTY:-def}|${EMPTY-def}", "def|"),
+            ("${UNSET:-${NAME}x}", "appx"),
+            ("${NAME:+set}|${EMPTY:+set}|${EMPTY+set}", "set||set"),
+            ("cost $5", "cost $5"),
+            ("${UNSET}", ""),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(interpolate(text, &vars).unwrap(), expected, "{}", text);
+        }
+        assert!(interpolate("${EMPTY:?required}", &vars).is_err());
+        assert!(interpolate("${EMPTY?required}", &vars).is_ok());
+        assert!(interpolate("${NAME", &vars).is_err());
+    }
+
+    #[test]
+    fn parse_env_file_handles_quotes_and_comments() {
+        let data = "
+# comment
+export HOST=db
+URL=postgres://$HOST/x  # trailing
+SINGLE='$HOST stays'
+DOUBLE=\"line\\n${HOST}\"
+INVALID LINE
+";
+        let env = parse_env_file(data, &HashMap::new()).unwrap();
+        assert_eq!(env["HOST"], "db");
+        assert_eq!(env["URL"], "postgres://db/x");
+        assert_eq!(env["SINGLE"], "$HOST stays");
+        assert_eq!(env["DOUBLE"], "line\ndb");
+        assert_eq!(env.len(), 4);
+    }
+
+    #[test]
+    fn parse_interpolates_and_normalizes_services() {
+        let vars = vars(&[("TAG", "1.2")]);
+        let compose = ComposeFile::parse(
+            "
+services:
+  app:
+    image: app:${TAG}
+    environment: { A: 1, B: null }
+    volumes: [./data:/data]
+    command: echo $$HOME
+",
+            &vars,
+            Path::new("/srv"),
+        )
+        .unwrap();
+        let app = &compose.services["app"];
+        assert_eq!(app.image, "app:1.2");
+        assert_eq!(app.environment, vec!["A=1"]);
+        assert_eq!(app.volumes, vec!["./data:/data"]);
+        assert_eq!(
+            resolve_bind(&app.volumes[0], Path::new("/srv")),
+            "/srv/data:/data"
+        );
+        assert_eq!(app.command, vec!["echo", "$HOME"]);
+    }
+}
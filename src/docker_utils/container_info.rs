@@ -1,184 +1,1263 @@
-use serde::Deserialize;
+use crate::command_utils::shell_quote;
+use crate::docker_utils::engine::nullable;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use std::collections::HashMap;
-use std::path::Path;
 
-#[derive(Deserialize, Debug)]
-struct Mount {
-    Source: String,
-    Destination: String,
-    Mode: String,
+// `docker inspect` 输出的类型化模型。
+//
+// 所有结构体都带 `#[serde(default)]`，缺失的字段取默认值，未知字段被忽略，
+// 列表和映射为 `null` 时视为空，因此不同版本 Docker 的输出都能解析。
+
+/// 容器详细信息，对应 `docker container inspect` 的一项
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "PascalCase", default)]
+pub struct ContainerInfo {
+    pub id: String,
+    pub created: String,
+    pub path: String,
+    #[serde(deserialize_with = "nullable")]
+    pub args: Vec<String>,
+    #[serde(deserialize_with = "nullable")]
+    pub state: ContainerState,
+    /// 镜像 ID
+    pub image: String,
+    pub resolv_conf_path: String,
+    pub hostname_path: String,
+    pub hosts_path: String,
+    pub log_path: String,
+    /// 容器名，带前导 `/`
+    pub name: String,
+    pub restart_count: i64,
+    pub driver: String,
+    pub platform: String,
+    pub mount_label: String,
+    pub process_label: String,
+    pub app_armor_profile: String,
+    #[serde(rename = "ExecIDs", deserialize_with = "nullable")]
+    pub exec_ids: Vec<String>,
+    #[serde(deserialize_with = "nullable")]
+    pub host_config: HostConfig,
+    #[serde(deserialize_with = "nullable")]
+    pub mounts: Vec<Mount>,
+    #[serde(deserialize_with = "nullable")]
+    pub config: ContainerConfig,
+    #[serde(deserialize_with = "nullable")]
+    pub network_settings: NetworkSettings,
 }
 
-#[derive(Deserialize, Debug)]
-struct PortBinding {
-    HostPort: String,
+/// 容器运行状态
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "PascalCase", default)]
+pub struct ContainerState {
+    pub status: String,
+    pub running: bool,
+    pub paused: bool,
+    pub restarting: bool,
+    #[serde(rename = "OOMKilled")]
+    pub oom_killed: bool,
+    pub dead: bool,
+    pub pid: i64,
+    pub exit_code: i64,
+    pub error: String,
+    pub started_at: String,
+    pub finished_at: String,
+    pub health: Option<Health>,
 }
 
-#[derive(Deserialize, Debug)]
-struct RestartPolicy {
-    Name: String,
+/// 健康检查状态
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "PascalCase", default)]
+pub struct Health {
+    pub status: String,
+    pub failing_streak: i64,
+    #[serde(deserialize_with = "nullable")]
+    pub log: Vec<HealthLog>,
 }
 
-#[derive(Deserialize, Debug)]
-struct HostConfig {
-    PortBindings: HashMap<String, Vec<PortBinding>>,
-    RestartPolicy: RestartPolicy,
-    AutoRemove: bool,
-    Privileged: bool,
-    PublishAllPorts: bool,
+/// 一次健康检查的结果
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "PascalCase", default)]
+pub struct HealthLog {
+    pub start: String,
+    pub end: String,
+    pub exit_code: i64,
+    pub output: String,
 }
 
-#[derive(Deserialize, Debug)]
-struct Config {
-    User: Option<String>,
-    Env: Option<Vec<String>>,
-    Cmd: Option<Vec<String>>,
-    Image: String,
+/// 容器配置，与镜像配置（`docker image inspect` 的 `Config`）结构相同
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "PascalCase", default)]
+pub struct ContainerConfig {
+    pub hostname: String,
+    pub domainname: String,
+    pub user: String,
+    pub attach_stdin: bool,
+    pub attach_stdout: bool,
+    pub attach_stderr: bool,
+    /// 暴露的端口，键为 `80/tcp` 形式，值总是空对象
+    #[serde(deserialize_with = "nullable")]
+    pub exposed_ports: HashMap<String, Value>,
+    pub tty: bool,
+    pub open_stdin: bool,
+    pub stdin_once: bool,
+    #[serde(deserialize_with = "nullable")]
+    pub env: Vec<String>,
+    #[serde(deserialize_with = "nullable")]
+    pub cmd: Vec<String>,
+    pub healthcheck: Option<HealthConfig>,
+    pub args_escaped: bool,
+    pub image: String,
+    #[serde(deserialize_with = "nullable")]
+    pub volumes: HashMap<String, Value>,
+    pub working_dir: String,
+    #[serde(deserialize_with = "nullable")]
+    pub entrypoint: Vec<String>,
+    pub network_disabled: bool,
+    pub mac_address: String,
+    #[serde(deserialize_with = "nullable")]
+    pub on_build: Vec<String>,
+    #[serde(deserialize_with = "nullable")]
+    pub labels: HashMap<String, String>,
+    pub stop_signal: String,
+    pub stop_timeout: Option<i64>,
+    #[serde(deserialize_with = "nullable")]
+    pub shell: Vec<String>,
 }
 
-#[derive(Deserialize, Debug)]
-pub struct ContainerInfo {
-    pub Name: String,
-    Config: Config,
-    HostConfig: HostConfig,
-    Mounts: Vec<Mount>,
+/// 健康检查配置，时间单位为纳秒
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase", default)]
+pub struct HealthConfig {
+    /// `["NONE"]`、`["CMD", ...]` 或 `["CMD-SHELL", "命令"]`
+    #[serde(deserialize_with = "nullable")]
+    pub test: Vec<String>,
+    pub interval: i64,
+    pub timeout: i64,
+    pub start_period: i64,
+    pub start_interval: i64,
+    pub retries: i64,
+}
+
+/// 主机相关配置
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "PascalCase", default)]
+pub struct HostConfig {
+    #[serde(deserialize_with = "nullable")]
+    pub binds: Vec<String>,
+    #[serde(rename = "ContainerIDFile")]
+    pub container_id_file: String,
+    #[serde(deserialize_with = "nullable")]
+    pub log_config: LogConfig,
+    pub network_mode: String,
+    #[serde(deserialize_with = "nullable")]
+    pub port_bindings: HashMap<String, Option<Vec<PortBinding>>>,
+    #[serde(deserialize_with = "nullable")]
+    pub restart_policy: RestartPolicy,
+    pub auto_remove: bool,
+    pub volume_driver: String,
+    #[serde(deserialize_with = "nullable")]
+    pub volumes_from: Vec<String>,
+    #[serde(deserialize_with = "nullable")]
+    pub cap_add: Vec<String>,
+    #[serde(deserialize_with = "nullable")]
+    pub cap_drop: Vec<String>,
+    pub cgroupns_mode: String,
+    #[serde(deserialize_with = "nullable")]
+    pub dns: Vec<String>,
+    #[serde(deserialize_with = "nullable")]
+    pub dns_options: Vec<String>,
+    #[serde(deserialize_with = "nullable")]
+    pub dns_search: Vec<String>,
+    #[serde(deserialize_with = "nullable")]
+    pub extra_hosts: Vec<String>,
+    #[serde(deserialize_with = "nullable")]
+    pub group_add: Vec<String>,
+    pub ipc_mode: String,
+    pub cgroup: String,
+    #[serde(deserialize_with = "nullable")]
+    pub links: Vec<String>,
+    pub oom_score_adj: i64,
+    pub pid_mode: String,
+    pub privileged: bool,
+    pub publish_all_ports: bool,
+    pub readonly_rootfs: bool,
+    #[serde(deserialize_with = "nullable")]
+    pub security_opt: Vec<String>,
+    #[serde(deserialize_with = "nullable")]
+    pub storage_opt: HashMap<String, String>,
+    #[serde(deserialize_with = "nullable")]
+    pub tmpfs: HashMap<String, String>,
+    #[serde(rename = "UTSMode")]
+    pub uts_mode: String,
+    pub userns_mode: String,
+    pub shm_size: i64,
+    #[serde(deserialize_with = "nullable")]
+    pub sysctls: HashMap<String, String>,
+    pub runtime: String,
+    pub isolation: String,
+    pub cpu_shares: i64,
+    pub memory: i64,
+    pub nano_cpus: i64,
+    pub cgroup_parent: String,
+    pub blkio_weight: i64,
+    pub cpu_period: i64,
+    pub cpu_quota: i64,
+    pub cpu_realtime_period: i64,
+    pub cpu_realtime_runtime: i64,
+    pub cpuset_cpus: String,
+    pub cpuset_mems: String,
+    #[serde(deserialize_with = "nullable")]
+    pub devices: Vec<DeviceMapping>,
+    #[serde(deserialize_with = "nullable")]
+    pub device_cgroup_rules: Vec<String>,
+    pub memory_reservation: i64,
+    pub memory_swap: i64,
+    pub memory_swappiness: Option<i64>,
+    pub oom_kill_disable: Option<bool>,
+    pub pids_limit: Option<i64>,
+    #[serde(deserialize_with = "nullable")]
+    pub ulimits: Vec<Ulimit>,
+    pub init: Option<bool>,
+    #[serde(deserialize_with = "nullable")]
+    pub masked_paths: Vec<String>,
+    #[serde(deserialize_with = "nullable")]
+    pub readonly_paths: Vec<String>,
+}
+
+/// 日志驱动配置
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "PascalCase", default)]
+pub struct LogConfig {
+    #[serde(rename = "Type")]
+    pub driver: String,
+    #[serde(deserialize_with = "nullable")]
+    pub config: HashMap<String, String>,
+}
+
+/// 端口映射
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase", default)]
+pub struct PortBinding {
+    pub host_ip: String,
+    pub host_port: String,
+}
+
+/// 重启策略
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "PascalCase", default)]
+pub struct RestartPolicy {
+    pub name: String,
+    pub maximum_retry_count: i64,
+}
+
+/// 设备映射
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "PascalCase", default)]
+pub struct DeviceMapping {
+    pub path_on_host: String,
+    pub path_in_container: String,
+    pub cgroup_permissions: String,
+}
+
+/// 资源限制
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "PascalCase", default)]
+pub struct Ulimit {
+    pub name: String,
+    pub soft: i64,
+    pub hard: i64,
+}
+
+/// 挂载点
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "PascalCase", default)]
+pub struct Mount {
+    /// `bind`、`volume`、`tmpfs` 等
+    #[serde(rename = "Type")]
+    pub kind: String,
+    /// 数据卷名称，绑定挂载为空
+    pub name: String,
+    pub source: String,
+    pub destination: String,
+    pub driver: String,
+    pub mode: String,
+    #[serde(rename = "RW")]
+    pub rw: bool,
+    pub propagation: String,
+}
+
+/// 网络配置
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "PascalCase", default)]
+pub struct NetworkSettings {
+    pub bridge: String,
+    #[serde(rename = "SandboxID")]
+    pub sandbox_id: String,
+    pub sandbox_key: String,
+    #[serde(deserialize_with = "nullable")]
+    pub ports: HashMap<String, Option<Vec<PortBinding>>>,
+    #[serde(rename = "IPAddress")]
+    pub ip_address: String,
+    pub gateway: String,
+    pub mac_address: String,
+    #[serde(deserialize_with = "nullable")]
+    pub networks: HashMap<String, EndpointSettings>,
+}
+
+/// 容器在某个网络中的配置
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "PascalCase", default)]
+pub struct EndpointSettings {
+    #[serde(rename = "IPAMConfig")]
+    pub ipam_config: Option<IpamConfig>,
+    #[serde(deserialize_with = "nullable")]
+    pub links: Vec<String>,
+    #[serde(deserialize_with = "nullable")]
+    pub aliases: Vec<String>,
+    pub mac_address: String,
+    #[serde(rename = "NetworkID")]
+    pub network_id: String,
+    #[serde(rename = "EndpointID")]
+    pub endpoint_id: String,
+    pub gateway: String,
+    #[serde(rename = "IPAddress")]
+    pub ip_address: String,
+    #[serde(rename = "IPPrefixLen")]
+    pub ip_prefix_len: i64,
+    #[serde(rename = "IPv6Gateway")]
+    pub ipv6_gateway: String,
+    #[serde(rename = "GlobalIPv6Address")]
+    pub global_ipv6_address: String,
+    #[serde(rename = "DNSNames", deserialize_with = "nullable")]
+    pub dns_names: Vec<String>,
+}
+
+/// 手动指定的网络地址
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct IpamConfig {
+    #[serde(rename = "IPv4Address")]
+    pub ipv4_address: String,
+    #[serde(rename = "IPv6Address")]
+    pub ipv6_address: String,
+}
+
+/// 镜像详细信息，对应 `docker image inspect` 的一项
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "PascalCase", default)]
+pub struct ImageInfo {
+    pub id: String,
+    #[serde(deserialize_with = "nullable")]
+    pub repo_tags: Vec<String>,
+    #[serde(deserialize_with = "nullable")]
+    pub repo_digests: Vec<String>,
+    pub created: String,
+    pub author: String,
+    pub architecture: String,
+    pub os: String,
+    pub size: i64,
+    /// 镜像的默认容器配置
+    #[serde(deserialize_with = "nullable")]
+    pub config: ContainerConfig,
 }
 
 impl ContainerInfo {
+    /// 去掉前导 `/` 的容器名
+    pub fn container_name(&self) -> &str {
+        self.name.trim_start_matches('/')
+    }
+
+    /// 12 位短 ID
+    pub fn short_id(&self) -> &str {
+        &self.id[..self.id.len().min(12)]
+    }
+
+    /// 容器标签
+    pub fn labels(&self) -> &HashMap<String, String> {
+        &self.config.labels
+    }
+
+    /// 去掉与镜像默认配置相同的设置，只保留创建容器时指定的部分
+    ///
+    /// 环境变量和标签逐项比较；入口点与镜像相同时不再指定，此时命令也与镜像相同则一并省略。
+    /// 入口点不同时必须保留命令，因为 `--entrypoint` 会清空镜像的默认命令。
+    pub fn without_image_defaults(&self, image: &ContainerConfig) -> ContainerInfo {
+        let mut info = self.clone();
+        let config = &mut info.config;
+        config.env.retain(|env| !image.env.contains(env));
+        config
+            .labels
+            .retain(|key, value| image.labels.get(key) != Some(value));
+        if config.entrypoint == image.entrypoint {
+            config.entrypoint.clear();
+            if config.cmd == image.cmd {
+                config.cmd.clear();
+            }
+        }
+        if config.user == image.user {
+            config.user.clear();
+        }
+        if config.working_dir == image.working_dir {
+            config.working_dir.clear();
+        }
+        if config.healthcheck == image.healthcheck {
+            config.healthcheck = None;
+        }
+        if config.stop_signal == image.stop_signal {
+            config.stop_signal.clear();
+        }
+        info
+    }
+
     fn parse_container_name(&self) -> String {
-        self.Name.trim_start_matches('/').to_string()
+        self.container_name().to_string()
     }
-    fn parse_privileged(&self) -> bool {
-        self.HostConfig.Privileged
+    pub(crate) fn parse_hostname(&self) -> String {
+        let mode = &self.host_config.network_mode;
+        // 默认主机名是短 ID，共享网络命名空间时不能指定主机名
+        if self.id.starts_with(&self.config.hostname)
+            || mode == "host"
+            || mode.starts_with("container:")
+        {
+            return String::new();
+        }
+        self.config.hostname.clone()
     }
-    fn parse_publish_all_ports(&self) -> bool {
-        self.HostConfig.PublishAllPorts
+    fn parse_entrypoint(&self) -> Option<String> {
+        self.config.entrypoint.first().cloned()
     }
-    fn parse_auto_remove(&self) -> bool {
-        self.HostConfig.AutoRemove
+    fn parse_cmd(&self) -> Vec<String> {
+        // `--entrypoint` 只接受一个参数，其余部分放在命令前面
+        let mut cmd: Vec<String> = self.config.entrypoint.iter().skip(1).cloned().collect();
+        cmd.extend(self.config.cmd.iter().cloned());
+        cmd
     }
-    fn parse_user(&self) -> String {
-        if let Some(user) = &self.Config.User {
-            if !user.is_empty() {
-                return String::from(user);
+    /// 重启策略，例如 `always`、`on-failure:3`，不重启时为空
+    pub(crate) fn parse_restart_policy(&self) -> String {
+        let policy = &self.host_config.restart_policy;
+        match policy.name.as_str() {
+            "" | "no" => String::new(),
+            "on-failure" if policy.maximum_retry_count > 0 => {
+                format!("on-failure:{}", policy.maximum_retry_count)
             }
+            name => name.to_string(),
         }
-        "".to_string()
     }
-    fn parse_envs(&self) -> Vec<String> {
-        if let Some(env_vars) = &self.Config.Env {
-            return env_vars.clone();
-        }
-        Vec::new()
+    fn parse_labels(&self) -> Vec<String> {
+        sorted_pairs(&self.config.labels, "=")
     }
-    fn parse_mounts(&self) -> Vec<String> {
+    pub(crate) fn parse_mounts(&self, unmapped: &mut Vec<String>) -> (Vec<String>, Vec<String>) {
         let mut mounts = Vec::new();
-        for mount in &self.Mounts {
-            if !Path::new(&mount.Destination).is_absolute() {
-                // 非绝对路径时挂载匿名卷
-                mounts.push(mount.Destination.clone());
+        let mut tmpfs = sorted_pairs(&self.host_config.tmpfs, ":");
+        for tmpfs in tmpfs.iter_mut() {
+            if let Some(path) = tmpfs.strip_suffix(':') {
+                *tmpfs = path.to_string();
+            }
+        }
+        for mount in &self.mounts {
+            let mut options: Vec<&str> = mount.mode.split(',').filter(|o| !o.is_empty()).collect();
+            if !mount.rw && !options.contains(&"ro") {
+                options.push("ro");
+            }
+            let options = if options.is_empty() {
+                String::new()
             } else {
-                let volume = format!(
-                    "{}:{}{}",
-                    mount.Source,
-                    mount.Destination,
-                    if mount.Mode.is_empty() {
-                        "".to_string()
+                format!(":{}", options.join(","))
+            };
+            match mount.kind.as_str() {
+                "bind" => {
+                    let mut volume = format!("{}:{}{}", mount.source, mount.destination, options);
+                    if !mount.propagation.is_empty() && mount.propagation != "rprivate" {
+                        volume.push_str(if options.is_empty() { ":" } else { "," });
+                        volume.push_str(&mount.propagation);
+                    }
+                    mounts.push(volume);
+                }
+                "volume" => {
+                    if !mount.driver.is_empty() && mount.driver != "local" {
+                        unmapped.push(format!("Mounts[{}].Driver", mount.destination));
+                    }
+                    if is_anonymous_volume(&mount.name) {
+                        // 匿名卷只需要挂载点
+                        mounts.push(mount.destination.clone());
                     } else {
-                        format!(":{}", mount.Mode)
+                        mounts.push(format!("{}:{}{}", mount.name, mount.destination, options));
                     }
-                );
-                mounts.push(volume);
+                }
+                "tmpfs" => {
+                    if !self.host_config.tmpfs.contains_key(&mount.destination) {
+                        tmpfs.push(mount.destination.clone());
+                    }
+                }
+                kind => unmapped.push(format!("Mounts[{}].Type={}", mount.destination, kind)),
             }
         }
-        mounts
+        (mounts, tmpfs)
+    }
+    /// 网络别名，自动添加的容器名和短 ID 不需要作为别名
+    pub(crate) fn parse_aliases(&self, endpoint: &EndpointSettings) -> Vec<String> {
+        endpoint
+            .aliases
+            .iter()
+            .filter(|a| *a != self.container_name() && !self.id.starts_with(a.as_str()))
+            .cloned()
+            .collect()
     }
-    fn parse_port_bindings(&self) -> Vec<String> {
+    pub(crate) fn parse_port_bindings(&self) -> Vec<String> {
         let mut port_bindings = Vec::new();
-        for (port, bindings) in &self.HostConfig.PortBindings {
-            for binding in bindings {
-                port_bindings.push(format!("{}:{}", binding.HostPort, port));
+        for (port, bindings) in &self.host_config.port_bindings {
+            for binding in bindings.iter().flatten() {
+                let port_binding = match binding.host_ip.as_str() {
+                    "" => format!("{}:{}", binding.host_port, port),
+                    ip if ip.contains(':') => format!("[{}]:{}:{}", ip, binding.host_port, port),
+                    ip => format!("{}:{}:{}", ip, binding.host_port, port),
+                };
+                port_bindings.push(port_binding);
             }
         }
+        port_bindings.sort();
         port_bindings
     }
-    fn parse_restart_policy(&self) -> String {
-        format!("--restart={}", self.HostConfig.RestartPolicy.Name)
+    fn parse_network(&self, unmapped: &mut Vec<String>) -> Network {
+        let mode = self.host_config.network_mode.as_str();
+        let primary = match mode {
+            "" | "default" => "bridge",
+            mode => mode,
+        };
+        let mut network = Network::default();
+        if primary != "bridge" {
+            network.name = primary.to_string();
+        }
+        if let Some(endpoint) = self.network_settings.networks.get(primary) {
+            network.aliases = self.parse_aliases(endpoint);
+            if let Some(ipam) = &endpoint.ipam_config {
+                network.ip = ipam.ipv4_address.clone();
+                network.ipv6 = ipam.ipv6_address.clone();
+            }
+        }
+        let mut others: Vec<&String> = self
+            .network_settings
+            .networks
+            .keys()
+            .filter(|name| *name != primary)
+            .collect();
+        others.sort();
+        for name in others {
+            unmapped.push(format!("NetworkSettings.Networks.{}", name));
+        }
+        network
+    }
+    fn parse_log(&self) -> (String, Vec<String>) {
+        let log = &self.host_config.log_config;
+        let driver = if log.driver != "json-file" || !log.config.is_empty() {
+            log.driver.clone()
+        } else {
+            String::new()
+        };
+        (driver, sorted_pairs(&log.config, "="))
+    }
+    fn parse_devices(&self) -> Vec<String> {
+        self.host_config
+            .devices
+            .iter()
+            .map(|d| {
+                let mut device = d.path_on_host.clone();
+                if d.path_in_container != d.path_on_host || d.cgroup_permissions != "rwm" {
+                    device.push(':');
+                    device.push_str(&d.path_in_container);
+                }
+                if !d.cgroup_permissions.is_empty() && d.cgroup_permissions != "rwm" {
+                    device.push(':');
+                    device.push_str(&d.cgroup_permissions);
+                }
+                device
+            })
+            .collect()
+    }
+    fn parse_links(&self) -> Vec<String> {
+        // 形如 `/db:/web/db`，还原为 `db:db`
+        self.host_config
+            .links
+            .iter()
+            .filter_map(|link| {
+                let (name, alias) = link.split_once(':')?;
+                let alias = alias.rsplit('/').next()?;
+                Some(format!("{}:{}", name.trim_start_matches('/'), alias))
+            })
+            .collect()
+    }
+    fn parse_namespaces(&self) -> Vec<(&'static str, String)> {
+        let host = &self.host_config;
+        let mut namespaces = Vec::new();
+        let mut push = |flag, value: &str, skip: &[&str]| {
+            if !value.is_empty() && !skip.contains(&value) {
+                namespaces.push((flag, value.to_string()));
+            }
+        };
+        push("--pid", &host.pid_mode, &[]);
+        push("--ipc", &host.ipc_mode, &["private", "shareable"]);
+        push("--uts", &host.uts_mode, &[]);
+        push("--userns", &host.userns_mode, &[]);
+        push("--runtime", &host.runtime, &["runc"]);
+        push("--cgroup-parent", &host.cgroup_parent, &[]);
+        push("--isolation", &host.isolation, &["default"]);
+        namespaces
+    }
+    fn parse_resources(&self) -> Vec<(&'static str, String)> {
+        let host = &self.host_config;
+        let mut resources = Vec::new();
+        let mut push = |flag, value: i64| {
+            if value > 0 {
+                resources.push((flag, value.to_string()));
+            }
+        };
+        push("--memory", host.memory);
+        push("--memory-reservation", host.memory_reservation);
+        push("--cpu-shares", host.cpu_shares);
+        push("--cpu-period", host.cpu_period);
+        push("--cpu-quota", host.cpu_quota);
+        push("--cpu-rt-period", host.cpu_realtime_period);
+        push("--cpu-rt-runtime", host.cpu_realtime_runtime);
+        push("--blkio-weight", host.blkio_weight);
+        push("--pids-limit", host.pids_limit.unwrap_or_default());
+        // 默认 64MiB
+        if host.shm_size != 64 * 1024 * 1024 {
+            push("--shm-size", host.shm_size);
+        }
+        if host.memory_swap != 0 {
+            resources.push(("--memory-swap", host.memory_swap.to_string()));
+        }
+        if let Some(swappiness) = host.memory_swappiness.filter(|s| *s >= 0) {
+            resources.push(("--memory-swappiness", swappiness.to_string()));
+        }
+        if host.nano_cpus > 0 {
+            resources.push(("--cpus", (host.nano_cpus as f64 / 1e9).to_string()));
+        }
+        if !host.cpuset_cpus.is_empty() {
+            resources.push(("--cpuset-cpus", host.cpuset_cpus.clone()));
+        }
+        if !host.cpuset_mems.is_empty() {
+            resources.push(("--cpuset-mems", host.cpuset_mems.clone()));
+        }
+        if host.oom_score_adj != 0 {
+            resources.push(("--oom-score-adj", host.oom_score_adj.to_string()));
+        }
+        resources
+    }
+    fn parse_ulimits(&self) -> Vec<String> {
+        self.host_config
+            .ulimits
+            .iter()
+            .map(|u| {
+                if u.soft == u.hard {
+                    format!("{}={}", u.name, u.soft)
+                } else {
+                    format!("{}={}:{}", u.name, u.soft, u.hard)
+                }
+            })
+            .collect()
+    }
+    fn parse_healthcheck(&self, unmapped: &mut Vec<String>) -> Vec<(&'static str, String)> {
+        let mut options = Vec::new();
+        let health = match &self.config.healthcheck {
+            Some(health) => health,
+            None => return options,
+        };
+        match health.test.first().map(String::as_str) {
+            Some("NONE") => {
+                options.push(("--no-healthcheck", String::new()));
+                return options;
+            }
+            Some("CMD-SHELL") => options.push(("--health-cmd", health.test[1..].join(" "))),
+            Some("CMD") => {
+                // `--health-cmd` 只支持 shell 形式，按参数转义后拼接，经 shell 拆分后参数不变
+                unmapped.push("Config.Healthcheck.Test".to_string());
+                let command: Vec<String> = health.test[1..]
+                    .iter()
+                    .map(|arg| shell_quote(arg))
+                    .collect();
+                options.push(("--health-cmd", command.join(" ")));
+            }
+            _ => {}
+        }
+        let durations = [
+            ("--health-interval", health.interval),
+            ("--health-timeout", health.timeout),
+            ("--health-start-period", health.start_period),
+            ("--health-start-interval", health.start_interval),
+        ];
+        for (flag, nanos) in durations {
+            if nanos > 0 {
+                options.push((flag, format_duration(nanos)));
+            }
+        }
+        if health.retries > 0 {
+            options.push(("--health-retries", health.retries.to_string()));
+        }
+        options
+    }
+    fn parse_unmapped(&self) -> Vec<String> {
+        let host = &self.host_config;
+        let mut unmapped = Vec::new();
+        if !host.storage_opt.is_empty() {
+            unmapped.push("HostConfig.StorageOpt".to_string());
+        }
+        if !host.volume_driver.is_empty() {
+            unmapped.push("HostConfig.VolumeDriver".to_string());
+        }
+        if !host.container_id_file.is_empty() {
+            unmapped.push("HostConfig.ContainerIDFile".to_string());
+        }
+        unmapped
     }
-    fn parse_image(&self) -> String {
-        self.Config.Image.clone()
+}
+
+/// 容器所在的主网络
+#[derive(Debug, Default)]
+struct Network {
+    /// 为空表示默认的 bridge 网络
+    name: String,
+    aliases: Vec<String>,
+    ip: String,
+    ipv6: String,
+}
+
+/// 按键排序后以 `sep` 连接键值
+fn sorted_pairs(map: &HashMap<String, String>, sep: &str) -> Vec<String> {
+    let mut pairs: Vec<String> = map
+        .iter()
+        .map(|(k, v)| format!("{}{}{}", k, sep, v))
+        .collect();
+    pairs.sort();
+    pairs
+}
+
+/// 匿名卷的名称是 64 位十六进制数
+pub(crate) fn is_anonymous_volume(name: &str) -> bool {
+    name.len() == 64 && name.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// 纳秒转为 Go 时长格式，例如 `30s`、`500ms`
+fn format_duration(nanos: i64) -> String {
+    if nanos % 1_000_000_000 == 0 {
+        format!("{}s", nanos / 1_000_000_000)
+    } else if nanos % 1_000_000 == 0 {
+        format!("{}ms", nanos / 1_000_000)
+    } else if nanos % 1_000 == 0 {
+        format!("{}us", nanos / 1_000)
+    } else {
+        format!("{}ns", nanos)
     }
 }
 
+/// 由 `docker inspect` 还原的 `docker run` 命令
 pub struct DockerCommand {
     container_name: String,
+    hostname: String,
+    domainname: String,
+    user: String,
+    working_dir: String,
+    entrypoint: Option<String>,
     privileged: bool,
     publish_all_ports: bool,
     auto_remove: bool,
+    tty: bool,
+    interactive: bool,
+    read_only: bool,
+    init: bool,
     restart_policy: String,
-    user: String,
+    network: Network,
+    mac_address: String,
+    links: Vec<String>,
     envs: Vec<String>,
+    labels: Vec<String>,
     mounts: Vec<String>,
+    volumes_from: Vec<String>,
+    tmpfs: Vec<String>,
     port_bindings: Vec<String>,
+    log_driver: String,
+    log_opts: Vec<String>,
+    cap_add: Vec<String>,
+    cap_drop: Vec<String>,
+    devices: Vec<String>,
+    device_cgroup_rules: Vec<String>,
+    security_opts: Vec<String>,
+    sysctls: Vec<String>,
+    group_add: Vec<String>,
+    namespaces: Vec<(&'static str, String)>,
+    resources: Vec<(&'static str, String)>,
+    oom_kill_disable: bool,
+    ulimits: Vec<String>,
+    extra_hosts: Vec<String>,
+    dns: Vec<String>,
+    dns_options: Vec<String>,
+    dns_search: Vec<String>,
+    healthcheck: Vec<(&'static str, String)>,
+    stop_signal: String,
+    stop_timeout: Option<i64>,
     image: String,
+    cmd: Vec<String>,
+    unmapped: Vec<String>,
 }
 
 impl DockerCommand {
     pub fn from(info: ContainerInfo) -> Self {
+        let mut unmapped = info.parse_unmapped();
+        let network = info.parse_network(&mut unmapped);
+        let (mounts, tmpfs) = info.parse_mounts(&mut unmapped);
+        let healthcheck = info.parse_healthcheck(&mut unmapped);
+        let (log_driver, log_opts) = info.parse_log();
+        let host = &info.host_config;
         DockerCommand {
             container_name: info.parse_container_name(),
-            privileged: info.parse_privileged(),
-            publish_all_ports: info.parse_publish_all_ports(),
-            auto_remove: info.parse_auto_remove(),
+            hostname: info.parse_hostname(),
+            domainname: info.config.domainname.clone(),
+            user: info.config.user.clone(),
+            working_dir: info.config.working_dir.clone(),
+            entrypoint: info.parse_entrypoint(),
+            privileged: host.privileged,
+            publish_all_ports: host.publish_all_ports,
+            auto_remove: host.auto_remove,
+            tty: info.config.tty,
+            interactive: info.config.open_stdin,
+            read_only: host.readonly_rootfs,
+            init: host.init.unwrap_or_default(),
             restart_policy: info.parse_restart_policy(),
-            user: info.parse_user(),
-            envs: info.parse_envs(),
-            mounts: info.parse_mounts(),
+            network,
+            mac_address: info.config.mac_address.clone(),
+            links: info.parse_links(),
+            envs: info.config.env.clone(),
+            labels: info.parse_labels(),
+            mounts,
+            volumes_from: host.volumes_from.clone(),
+            tmpfs,
             port_bindings: info.parse_port_bindings(),
-            image: info.parse_image(),
+            log_driver,
+            log_opts,
+            cap_add: host.cap_add.clone(),
+            cap_drop: host.cap_drop.clone(),
+            devices: info.parse_devices(),
+            device_cgroup_rules: host.device_cgroup_rules.clone(),
+            security_opts: host.security_opt.clone(),
+            sysctls: sorted_pairs(&host.sysctls, "="),
+            group_add: host.group_add.clone(),
+            namespaces: info.parse_namespaces(),
+            resources: info.parse_resources(),
+            oom_kill_disable: host.oom_kill_disable.unwrap_or_default(),
+            ulimits: info.parse_ulimits(),
+            extra_hosts: host.extra_hosts.clone(),
+            dns: host.dns.clone(),
+            dns_options: host.dns_options.clone(),
+            dns_search: host.dns_search.clone(),
+            healthcheck,
+            stop_signal: info.config.stop_signal.clone(),
+            stop_timeout: info.config.stop_timeout,
+            image: info.config.image.clone(),
+            cmd: info.parse_cmd(),
+            unmapped,
         }
     }
 
-    pub fn to_command(&self) -> Vec<String> {
-        let mut command: Vec<String> =
-            vec!["docker".to_string(), "run".to_string(), "-d".to_string()];
+    /// 保真度报告：有非默认值但无法用 `docker run` 参数还原的 inspect 字段
+    pub fn unmapped(&self) -> &[String] {
+        &self.unmapped
+    }
+
+    /// 按选项分组的参数，每组是一个选项及其取值
+    fn options(&self) -> Vec<Vec<String>> {
+        let mut options: Vec<Vec<String>> = vec![vec!["-d".to_string()]];
+        let mut push = |flag: &str, value: &str| {
+            if value.is_empty() {
+                options.push(vec![flag.to_string()]);
+            } else {
+                options.push(vec![flag.to_string(), value.to_string()]);
+            }
+        };
         //添加容器名称
-        command.push("--name".to_string());
-        command.push(self.container_name.clone());
-        //是否添加高级权限
-        if self.privileged {
-            command.push("--privileged".to_string());
-        }
-        //是否映射所有端口
-        if self.publish_all_ports {
-            command.push("-P".to_string());
-        }
-        //是否自动移除
-        if self.auto_remove {
-            command.push("--rm".to_string());
-        }
-        //添加用户
-        if !self.user.is_empty() {
-            command.push("-u".to_string());
-            command.push(self.user.to_string());
-        }
-        //添加环境变量
-        for env in &self.envs {
-            command.push("-e".to_string());
-            command.push(env.to_string());
-        }
-        //添加挂载卷
-        for mount in &self.mounts {
-            command.push("-v".to_string());
-            command.push(mount.to_string());
+        push("--name", &self.container_name);
+        // 添加主机名、用户和工作目录
+        let strings = [
+            ("--hostname", &self.hostname),
+            ("--domainname", &self.domainname),
+            ("-u", &self.user),
+            ("-w", &self.working_dir),
+        ];
+        for (flag, value) in strings {
+            if !value.is_empty() {
+                push(flag, value);
+            }
+        }
+        if let Some(entrypoint) = &self.entrypoint {
+            push("--entrypoint", entrypoint);
+        }
+        //是否添加高级权限、映射所有端口、自动移除等开关
+        let switches = [
+            ("--privileged", self.privileged),
+            ("-P", self.publish_all_ports),
+            ("--rm", self.auto_remove),
+            ("-t", self.tty),
+            ("-i", self.interactive),
+            ("--read-only", self.read_only),
+            ("--init", self.init),
+            ("--oom-kill-disable", self.oom_kill_disable),
+        ];
+        for (flag, enabled) in switches {
+            if enabled {
+                push(flag, "");
+            }
+        }
+        // 添加重启策略
+        if !self.restart_policy.is_empty() {
+            push("--restart", &self.restart_policy);
+        }
+        // 添加网络
+        let network = [
+            ("--network", &self.network.name),
+            ("--ip", &self.network.ip),
+            ("--ip6", &self.network.ipv6),
+            ("--mac-address", &self.mac_address),
+        ];
+        for (flag, value) in network {
+            if !value.is_empty() {
+                push(flag, value);
+            }
+        }
+        let lists = [
+            ("--network-alias", &self.network.aliases),
+            ("--link", &self.links),
+            // 添加端口映射
+            ("-p", &self.port_bindings),
+            //添加挂载卷
+            ("-v", &self.mounts),
+            ("--volumes-from", &self.volumes_from),
+            ("--tmpfs", &self.tmpfs),
+            //添加环境变量
+            ("-e", &self.envs),
+            ("--label", &self.labels),
+        ];
+        for (flag, values) in lists {
+            for value in values {
+                push(flag, value);
+            }
+        }
+        // 添加日志驱动
+        if !self.log_driver.is_empty() {
+            push("--log-driver", &self.log_driver);
         }
-        // 添加端口映射
-        for port_binding in &self.port_bindings {
-            command.push("-p".to_string());
-            command.push(port_binding.to_string());
+        let lists = [
+            ("--log-opt", &self.log_opts),
+            ("--cap-add", &self.cap_add),
+            ("--cap-drop", &self.cap_drop),
+            ("--device", &self.devices),
+            ("--device-cgroup-rule", &self.device_cgroup_rules),
+            ("--security-opt", &self.security_opts),
+            ("--sysctl", &self.sysctls),
+            ("--group-add", &self.group_add),
+        ];
+        for (flag, values) in lists {
+            for value in values {
+                push(flag, value);
+            }
+        }
+        // 添加命名空间和资源限制
+        for (flag, value) in self.namespaces.iter().chain(&self.resources) {
+            push(flag, value);
+        }
+        let lists = [
+            ("--ulimit", &self.ulimits),
+            ("--add-host", &self.extra_hosts),
+            ("--dns", &self.dns),
+            ("--dns-option", &self.dns_options),
+            ("--dns-search", &self.dns_search),
+        ];
+        for (flag, values) in lists {
+            for value in values {
+                push(flag, value);
+            }
+        }
+        // 添加健康检查
+        for (flag, value) in &self.healthcheck {
+            push(flag, value);
         }
-        // 添加镜像名称
+        if !self.stop_signal.is_empty() {
+            push("--stop-signal", &self.stop_signal);
+        }
+        if let Some(timeout) = self.stop_timeout {
+            push("--stop-timeout", &timeout.to_string());
+        }
+        options
+    }
+
+    pub fn to_command(&self) -> Vec<String> {
+        let mut command: Vec<String> = vec!["docker".to_string(), "run".to_string()];
+        command.extend(self.options().into_iter().flatten());
+        // 添加镜像名称和命令
         command.push(self.image.clone());
+        command.extend(self.cmd.iter().cloned());
         command
     }
+
+    /// 渲染为可直接执行的 shell 命令，参数按 POSIX shell 规则引用
+    ///
+    /// `multiline` 为真时每个选项单独一行，以 `\` 续行，镜像和命令在最后一行。
+    pub fn to_shell(&self, multiline: bool) -> String {
+        let quote = |args: &[String]| -> String {
+            args.iter()
+                .map(|arg| shell_quote(arg))
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        let mut lines = vec!["docker run".to_string()];
+        lines.extend(self.options().iter().map(|option| quote(option)));
+        let mut tail = vec![self.image.clone()];
+        tail.extend(self.cmd.iter().cloned());
+        lines.push(quote(&tail));
+        if multiline {
+            lines.join(" \\\n    ")
+        } else {
+            lines.join(" ")
+        }
+    }
+
+    /// 渲染为 JSON 字符串数组形式的 argv，便于程序直接执行
+    pub fn to_json(&self) -> String {
+        Value::from(self.to_command()).to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command_utils::shell_split;
+
+    fn container(value: Value) -> ContainerInfo {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn to_shell_round_trips_to_command() {
+        let info = container(serde_json::json!({
+            "Id": "0123456789abcdef",
+            "Name": "/web",
+            "HostConfig": { "NetworkMode": "bridge" },
+            "Config": {
+                "Hostname": "0123456789ab",
+                "Image": "app:1",
+                "Env": ["GREETING=it's $HOME", "EMPTY="],
+                "Cmd": ["sh", "-c", "echo \"$GREETING\"; exit 0"]
+            }
+        }));
+        let command = DockerCommand::from(info);
+        for multiline in [false, true] {
+            let shell = command.to_shell(multiline).replace("\\\n", " ");
+            assert_eq!(shell_split(&shell), command.to_command());
+        }
+        let argv: Vec<String> = serde_json::from_str(&command.to_json()).unwrap();
+        assert_eq!(argv, command.to_command());
+    }
+
+    fn web() -> ContainerInfo {
+        container(serde_json::json!({
+            "Id": "0123456789abcdef",
+            "Name": "/web",
+            "HostConfig": {
+                "NetworkMode": "backend",
+                "PortBindings": {
+                    "80/tcp": [{ "HostIp": "", "HostPort": "8080" }],
+                    "443/tcp": [{ "HostIp": "127.0.0.1", "HostPort": "8443" }]
+                },
+                "RestartPolicy": { "Name": "on-failure", "MaximumRetryCount": 3 },
+                "Memory": 536870912,
+                "StorageOpt": { "size": "10G" },
+                "LogConfig": { "Type": "json-file", "Config": {} }
+            },
+            "Mounts": [
+                {
+                    "Type": "bind",
+                    "Source": "/srv/conf",
+                    "Destination": "/etc/app",
+                    "RW": false,
+                    "Propagation": "rprivate"
+                },
+                {
+                    "Type": "volume",
+                    "Name": "data",
+                    "Source": "/var/lib/docker/volumes/data/_data",
+                    "Destination": "/data",
+                    "Driver": "nfs",
+                    "RW": true
+                }
+            ],
+            "Config": {
+                "Hostname": "0123456789ab",
+                "Image": "app:1",
+                "Env": ["MODE=prod"],
+                "Labels": { "b": "2", "a": "1" },
+                "Entrypoint": ["/entry.sh", "--verbose"],
+                "Cmd": ["serve"],
+                "Healthcheck": {
+                    "Test": ["CMD", "curl", "-f", "http://localhost/?a=1&b=2"],
+                    "Interval": 30_000_000_000_i64,
+                    "Retries": 3
+                }
+            },
+            "NetworkSettings": {
+                "Networks": {
+                    "backend": { "Aliases": ["web", "api", "0123456789ab"] },
+                    "metrics": {}
+                }
+            }
+        }))
+    }
+
+    #[test]
+    fn to_command_reverses_run_options() {
+        let command = DockerCommand::from(web());
+        let expected = [
+            "docker",
+            "run",
+            "-d",
+            "--name",
+            "web",
+            "--entrypoint",
+            "/entry.sh",
+            "--restart",
+            "on-failure:3",
+            "--network",
+            "backend",
+            "--network-alias",
+            "api",
+            "-p",
+            "127.0.0.1:8443:443/tcp",
+            "-p",
+            "8080:80/tcp",
+            "-v",
+            "/srv/conf:/etc/app:ro",
+            "-v",
+            "data:/data",
+            "-e",
+            "MODE=prod",
+            "--label",
+            "a=1",
+            "--label",
+            "b=2",
+            "--memory",
+            "536870912",
+            "--health-cmd",
+            "curl -f 'http://localhost/?a=1&b=2'",
+            "--health-interval",
+            "30s",
+            "--health-retries",
+            "3",
+            "app:1",
+            "--verbose",
+            "serve",
+        ];
+        assert_eq!(command.to_command(), expected);
+        // 执行形式的健康检查经 shell 拆分后与原参数相同
+        let health_cmd = &expected[expected.iter().position(|a| *a == "--health-cmd").unwrap() + 1];
+        assert_eq!(
+            shell_split(health_cmd),
+            ["curl", "-f", "http://localhost/?a=1&b=2"]
+        );
+    }
+
+    #[test]
+    fn unmapped_reports_lossy_fields() {
+        let command = DockerCommand::from(web());
+        assert_eq!(
+            command.unmapped(),
+            [
+                "HostConfig.StorageOpt",
+                "NetworkSettings.Networks.metrics",
+                "Mounts[/data].Driver",
+                "Config.Healthcheck.Test",
+            ]
+        );
+        let plain = container(serde_json::json!({
+            "Id": "abc",
+            "Name": "/plain",
+            "Config": { "Image": "busybox" }
+        }));
+        let command = DockerCommand::from(plain);
+        assert!(command.unmapped().is_empty());
+        assert_eq!(
+            command.to_command(),
+            ["docker", "run", "-d", "--name", "plain", "busybox"]
+        );
+    }
+
+    #[test]
+    fn without_image_defaults_keeps_overrides() {
+        let image: ContainerConfig = serde_json::from_value(serde_json::json!({
+            "Env": ["PATH=/usr/bin", "MODE=dev"],
+            "Labels": { "maintainer": "me", "version": "1" },
+            "Entrypoint": ["/entry.sh", "--verbose"],
+            "Cmd": ["serve"],
+            "User": "app",
+            "WorkingDir": "/app",
+            "StopSignal": "SIGTERM"
+        }))
+        .unwrap();
+        let info = container(serde_json::json!({
+            "Id": "abc",
+            "Name": "/web",
+            "Config": {
+                "Image": "app:1",
+                "Env": ["PATH=/usr/bin", "MODE=prod"],
+                "Labels": { "maintainer": "me", "version": "2" },
+                "Entrypoint": ["/entry.sh", "--verbose"],
+                "Cmd": ["serve"],
+                "User": "root",
+                "WorkingDir": "/app",
+                "StopSignal": "SIGTERM"
+            }
+        }));
+        let stripped = info.without_image_defaults(&image);
+        let config = &stripped.config;
+        assert_eq!(config.env, vec!["MODE=prod"]);
+        assert_eq!(config.labels.len(), 1);
+        assert_eq!(config.labels["version"], "2");
+        assert!(config.entrypoint.is_empty());
+        assert!(config.cmd.is_empty());
+        assert_eq!(config.user, "root");
+        assert!(config.working_dir.is_empty());
+        assert!(config.stop_signal.is_empty());
+        assert_eq!(
+            DockerCommand::from(stripped).to_command(),
+            [
+                "docker",
+                "run",
+                "-d",
+                "--name",
+                "web",
+                "-u",
+                "root",
+                "-e",
+                "MODE=prod",
+                "--label",
+                "version=2",
+                "app:1"
+            ]
+        );
+    }
+
+    #[test]
+    fn without_image_defaults_keeps_cmd_when_entrypoint_changes() {
+        let image: ContainerConfig = serde_json::from_value(serde_json::json!({
+            "Entrypoint": ["/entry.sh"],
+            "Cmd": ["serve"]
+        }))
+        .unwrap();
+        let info = container(serde_json::json!({
+            "Id": "abc",
+            "Name": "/web",
+            "Config": { "Image": "app", "Entrypoint": ["/other.sh"], "Cmd": ["serve"] }
+        }));
+        let config = info.without_image_defaults(&image).config;
+        // `--entrypoint` 会清空镜像的默认命令，命令必须保留
+        assert_eq!(config.entrypoint, vec!["/other.sh"]);
+        assert_eq!(config.cmd, vec!["serve"]);
+    }
 }
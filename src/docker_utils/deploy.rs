@@ -0,0 +1,298 @@
+use crate::docker_utils;
+use crate::docker_utils::compose::resolve_bind;
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io;
+use std::path::Path;
+
+/// 单容器部署配置，写在项目 YAML 的 `deploy` 字段中
+///
+/// ```yaml
+/// deploy:
+///   tag: "1.2"
+///   ports: ["8080:80", "127.0.0.1:9090:9090"]
+///   env: { RUST_LOG: info }
+///   env_files: [".env"]
+///   volumes: ["./data:/data", "logs:/var/log/app"]
+///   networks: [backend]
+///   restart: unless-stopped
+///   resources: { cpus: 1.5, memory: 512m }
+///   labels: { team: infra }
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct DeploySpec {
+    /// 镜像名，为空时使用项目名
+    pub image: String,
+    /// 镜像标签，为空时为 `latest`
+    pub tag: String,
+    /// 容器名，为空时使用项目名
+    pub container_name: String,
+    /// 端口映射，例如 `8080:80`、`127.0.0.1:8080:80/tcp`，只写一个端口时宿主机使用相同端口，
+    /// 空字符串会被忽略
+    pub ports: Vec<String>,
+    pub env: BTreeMap<String, String>,
+    /// 环境变量文件，相对路径以项目目录为基准
+    pub env_files: Vec<String>,
+    /// 挂载，例如 `./data:/data`、`logs:/var/log/app:ro`，相对路径以项目目录为基准
+    pub volumes: Vec<String>,
+    /// 加入的网络，不存在时创建，第一个网络在创建容器时指定
+    pub networks: Vec<String>,
+    /// 重启策略，例如 `always`、`unless-stopped`、`on-failure:3`
+    pub restart: String,
+    pub resources: Resources,
+    pub labels: BTreeMap<String, String>,
+}
+
+/// 资源限制，为空时不限制
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct Resources {
+    /// CPU 核数，例如 `1.5`
+    pub cpus: Option<f64>,
+    /// 内存上限，例如 `512m`、`2g`
+    pub memory: String,
+    /// 内存软限制
+    pub memory_reservation: String,
+    /// 内存加交换分区上限，`-1` 表示不限制交换分区
+    pub memory_swap: String,
+    pub cpu_shares: Option<i64>,
+    pub pids_limit: Option<i64>,
+}
+
+impl DeploySpec {
+    /// 与以前的默认启动方式相同：每个端口映射到宿主机相同端口，并挂载宿主机时区
+    pub fn from_ports(ports: &[String]) -> Self {
+        DeploySpec {
+            ports: ports.to_vec(),
+            volumes: vec!["/etc/localtime:/etc/localtime:ro".to_string()],
+            ..DeploySpec::default()
+        }
+    }
+
+    /// 带标签的镜像名
+    pub fn image_ref(&self, project: &str) -> String {
+        let image = if self.image.is_empty() {
+            project
+        } else {
+            &self.image
+        };
+        let tag = if self.tag.is_empty() {
+            "latest"
+        } else {
+            &self.tag
+        };
+        format!("{}:{}", image, tag)
+    }
+
+    /// 容器名
+    pub fn container_name(&self, project: &str) -> String {
+        if self.container_name.is_empty() {
+            project.to_string()
+        } else {
+            self.container_name.clone()
+        }
+    }
+
+    /// `docker create` 的参数，`dir` 为项目目录
+    pub fn create_args(&self, project: &str, dir: &Path) -> Vec<String> {
+        let mut args = vec!["--name".to_string(), self.container_name(project)];
+        let mut push = |flag: &str, value: &str| {
+            args.push(flag.to_string());
+            args.push(value.to_string());
+        };
+        if !self.restart.is_empty() {
+            push("--restart", &self.restart);
+        }
+        if let Some(network) = self.networks.first() {
+            push("--network", network);
+        }
+        for port in self
+            .ports
+            .iter()
+            .map(|p| p.trim())
+            .filter(|p| !p.is_empty())
+        {
+            if port.contains(':') {
+                push("-p", port);
+            } else {
+                // 协议后缀只能写在容器端口之后，例如 `8080:8080/tcp`
+                let (number, protocol) = match port.split_once('/') {
+                    Some((number, protocol)) => (number, format!("/{}", protocol)),
+                    None => (port, String::new()),
+                };
+                push("-p", &format!("{}:{}{}", number, number, protocol));
+            }
+        }
+        for volume in &self.volumes {
+            push("-v", &resolve_bind(volume, dir));
+        }
+        for file in &self.env_files {
+            push("--env-file", &dir.join(file).to_string_lossy());
+        }
+        for (key, value) in &self.env {
+            push("-e", &format!("{}={}", key, value));
+        }
+        for (key, value) in &self.labels {
+            push("--label", &format!("{}={}", key, value));
+        }
+        let resources = &self.resources;
+        if let Some(cpus) = resources.cpus {
+            push("--cpus", &cpus.to_string());
+        }
+        let limits = [
+            ("--memory", &resources.memory),
+            ("--memory-reservation", &resources.memory_reservation),
+            ("--memory-swap", &resources.memory_swap),
+        ];
+        for (flag, value) in limits {
+            if !value.is_empty() {
+                push(flag, value);
+            }
+        }
+        if let Some(shares) = resources.cpu_shares {
+            push("--cpu-shares", &shares.to_string());
+        }
+        if let Some(limit) = resources.pids_limit {
+            push("--pids-limit", &limit.to_string());
+        }
+        args.push(self.image_ref(project));
+        args
+    }
+
+    /// 重新创建并启动容器，已存在的同名容器会先被删除
+    pub fn deploy(&self, project: &str, dir: &Path) -> io::Result<()> {
+        let container = self.container_name(project);
+        info!("部署容器 {}，镜像 {}", container, self.image_ref(project));
+        if docker_utils::container_exists(&container) {
+            docker_utils::container_stop(&[&container])?;
+            docker_utils::container_remove(&[&container])?;
+        }
+        for network in &self.networks {
+            if !docker_utils::network_exists(network) {
+                docker_utils::network_create(network, None, &[])?;
+            }
+        }
+        let args = self.create_args(project, dir);
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        docker_utils::container_create(&args)?;
+        for network in self.networks.iter().skip(1) {
+            docker_utils::network_connect(network, &container, &[])?;
+        }
+        docker_utils::container_start(&[&container])?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 取出某个选项的所有值
+    fn values(args: &[String], flag: &str) -> Vec<String> {
+        args.windows(2)
+            .filter(|pair| pair[0] == flag)
+            .map(|pair| pair[1].clone())
+            .collect()
+    }
+
+    #[test]
+    fn ports_map_to_same_host_port() {
+        let spec = DeploySpec {
+            ports: vec![
+                "8080".to_string(),
+                "8080/tcp".to_string(),
+                " 53/udp ".to_string(),
+                "127.0.0.1:9090:90".to_string(),
+                "".to_string(),
+            ],
+            ..DeploySpec::default()
+        };
+        let args = spec.create_args("app", Path::new("/srv/app"));
+        assert_eq!(
+            values(&args, "-p"),
+            [
+                "8080:8080",
+                "8080:8080/tcp",
+                "53:53/udp",
+                "127.0.0.1:9090:90"
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_port_list_from_command_line_is_ignored() {
+        let ports: Vec<String> = "".split(',').map(String::from).collect();
+        let args = DeploySpec::from_ports(&ports).create_args("app", Path::new("/srv/app"));
+        assert!(values(&args, "-p").is_empty());
+        assert_eq!(values(&args, "-v"), ["/etc/localtime:/etc/localtime:ro"]);
+    }
+
+    #[test]
+    fn create_args_render_options() {
+        let spec = DeploySpec {
+            image: "registry.example.com/team/app".to_string(),
+            tag: "1.2".to_string(),
+            container_name: "app-1".to_string(),
+            env: BTreeMap::from([
+                ("B".to_string(), "2".to_string()),
+                ("A".to_string(), "x=1".to_string()),
+            ]),
+            env_files: vec![".env".to_string()],
+            volumes: vec![
+                "./data:/data".to_string(),
+                "logs:/var/log/app:ro".to_string(),
+            ],
+            networks: vec!["backend".to_string(), "frontend".to_string()],
+            restart: "on-failure:3".to_string(),
+            resources: Resources {
+                cpus: Some(1.5),
+                memory: "512m".to_string(),
+                pids_limit: Some(100),
+                ..Resources::default()
+            },
+            ..DeploySpec::default()
+        };
+        let dir = Path::new("/srv/app");
+        let args = spec.create_args("app", dir);
+        assert_eq!(&args[..2], ["--name", "app-1"]);
+        assert_eq!(values(&args, "--restart"), ["on-failure:3"]);
+        // 只有第一个网络在创建时指定，其余的在启动前连接
+        assert_eq!(values(&args, "--network"), ["backend"]);
+        assert_eq!(values(&args, "-e"), ["A=x=1", "B=2"]);
+        assert_eq!(
+            values(&args, "-v"),
+            [
+                format!("{}:/data", dir.join("data").display()),
+                "logs:/var/log/app:ro".to_string()
+            ]
+        );
+        assert_eq!(
+            values(&args, "--env-file"),
+            [dir.join(".env").display().to_string()]
+        );
+        assert_eq!(values(&args, "--cpus"), ["1.5"]);
+        assert_eq!(values(&args, "--memory"), ["512m"]);
+        assert_eq!(values(&args, "--pids-limit"), ["100"]);
+        assert_eq!(
+            args.last().map(String::as_str),
+            Some("registry.example.com/team/app:1.2")
+        );
+    }
+
+    #[test]
+    fn defaults_use_project_name() {
+        let spec = DeploySpec::default();
+        assert_eq!(spec.image_ref("app"), "app:latest");
+        assert_eq!(spec.container_name("app"), "app");
+        let args = spec.create_args("app", Path::new("."));
+        assert_eq!(args, ["--name", "app", "app:latest"]);
+
+        let tagged = DeploySpec {
+            tag: "v2".to_string(),
+            ..DeploySpec::default()
+        };
+        assert_eq!(tagged.image_ref("app"), "app:v2");
+    }
+}
@@ -0,0 +1,608 @@
+use crate::docker_utils::container_info::{ContainerInfo, ImageInfo};
+use bytes::Bytes;
+use http_body_util::{BodyExt, Full};
+use hyper::header::{CONTENT_TYPE, HOST};
+use hyper::{Method, Request, StatusCode};
+use hyper_util::rt::TokioIo;
+use log::{debug, warn};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::env;
+use std::io::{Error, ErrorKind};
+use std::path::{Path, PathBuf};
+use tokio::net::UnixStream;
+
+/// 默认的 Docker 守护进程套接字
+pub const DEFAULT_SOCKET: &str = "/var/run/docker.sock";
+
+/// 容器列表项，对应 `GET /containers/json`
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(rename_all = "PascalCase", default)]
+pub struct ContainerSummary {
+    pub id: String,
+    #[serde(deserialize_with = "nullable")]
+    pub names: Vec<String>,
+    pub image: String,
+    #[serde(rename = "ImageID")]
+    pub image_id: String,
+    pub command: String,
+    pub created: i64,
+    pub state: String,
+    pub status: String,
+    #[serde(deserialize_with = "nullable")]
+    pub ports: Vec<PortSummary>,
+    #[serde(deserialize_with = "nullable")]
+    pub labels: HashMap<String, String>,
+}
+
+impl ContainerSummary {
+    /// 去掉前导 `/` 的第一个容器名
+    pub fn name(&self) -> &str {
+        self.names
+            .first()
+            .map(|n| n.trim_start_matches('/'))
+            .unwrap_or_default()
+    }
+}
+
+/// 容器端口
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(rename_all = "PascalCase", default)]
+pub struct PortSummary {
+    #[serde(rename = "IP")]
+    pub ip: Option<String>,
+    pub private_port: u16,
+    pub public_port: Option<u16>,
+    #[serde(rename = "Type")]
+    pub protocol: String,
+}
+
+/// 镜像列表项，对应 `GET /images/json`
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(rename_all = "PascalCase", default)]
+pub struct ImageSummary {
+    pub id: String,
+    pub parent_id: String,
+    #[serde(deserialize_with = "nullable")]
+    pub repo_tags: Vec<String>,
+    #[serde(deserialize_with = "nullable")]
+    pub repo_digests: Vec<String>,
+    pub created: i64,
+    pub size: i64,
+    #[serde(deserialize_with = "nullable")]
+    pub labels: HashMap<String, String>,
+}
+
+/// 网络，对应 `GET /networks`
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(rename_all = "PascalCase", default)]
+pub struct Network {
+    pub name: String,
+    pub id: String,
+    pub driver: String,
+    pub scope: String,
+    pub internal: bool,
+    pub attachable: bool,
+    #[serde(deserialize_with = "nullable")]
+    pub labels: HashMap<String, String>,
+}
+
+/// 数据卷，对应 `GET /volumes`
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(rename_all = "PascalCase", default)]
+pub struct Volume {
+    pub name: String,
+    pub driver: String,
+    pub mountpoint: String,
+    pub scope: String,
+    #[serde(deserialize_with = "nullable")]
+    pub labels: HashMap<String, String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "PascalCase", default)]
+struct VolumeList {
+    volumes: Option<Vec<Volume>>,
+}
+
+/// 守护进程版本，对应 `GET /version`
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(rename_all = "PascalCase", default)]
+pub struct SystemVersion {
+    pub version: String,
+    pub api_version: String,
+    pub min_api_version: String,
+    pub os: String,
+    pub arch: String,
+    pub kernel_version: String,
+}
+
+/// 创建资源后返回的 ID
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(rename_all = "PascalCase", default)]
+pub struct CreateResponse {
+    pub id: String,
+    pub warnings: Option<Vec<String>>,
+}
+
+/// 清理结果
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(rename_all = "PascalCase", default)]
+pub struct PruneResponse {
+    pub space_reclaimed: u64,
+}
+
+/// Docker Engine API 客户端，通过 Unix 套接字与守护进程通信
+///
+/// 每个请求建立一个新连接，不依赖 `docker` 命令行。
+#[derive(Debug, Clone)]
+pub struct DockerClient {
+    socket: PathBuf,
+    api_version: Option<String>,
+}
+
+impl DockerClient {
+    /// 使用 `DOCKER_HOST` 指定的 `unix://` 套接字，未设置时使用 `/var/run/docker.sock`
+    ///
+    /// `DOCKER_HOST` 为 `tcp://` 等其他协议时返回错误，只支持 Unix 套接字。
+    pub fn new() -> Result<Self, Error> {
+        let socket = match env::var("DOCKER_HOST") {
+            Ok(host) if !host.is_empty() => match host.strip_prefix("unix://") {
+                Some(path) => PathBuf::from(path),
+                None => {
+                    return Err(Error::new(
+                        ErrorKind::Unsupported,
+                        format!(
+                            "unsupported DOCKER_HOST {}, only unix:// is supported",
+                            host
+                        ),
+                    ))
+                }
+            },
+            _ => PathBuf::from(DEFAULT_SOCKET),
+        };
+        Ok(DockerClient::with_socket(socket))
+    }
+
+    /// 使用指定的套接字，可指向测试用的模拟服务
+    pub fn with_socket(socket: impl Into<PathBuf>) -> Self {
+        DockerClient {
+            socket: socket.into(),
+            api_version: None,
+        }
+    }
+
+    /// 固定 API 版本，例如 `1.43`，请求路径会加上 `/v1.43` 前缀
+    pub fn api_version(mut self, version: &str) -> Self {
+        self.api_version = Some(version.to_string());
+        self
+    }
+
+    /// 套接字路径
+    pub fn socket(&self) -> &Path {
+        &self.socket
+    }
+
+    /// 检查守护进程是否可用
+    pub async fn ping(&self) -> Result<(), Error> {
+        self.send(Method::GET, "/_ping", &[], None)
+            .await
+            .map(|_| ())
+    }
+
+    /// 守护进程版本
+    pub async fn version(&self) -> Result<SystemVersion, Error> {
+        self.get("/version", &[]).await
+    }
+
+    /// 容器列表，`all` 为 `true` 时包括已停止的容器
+    pub async fn containers(&self, all: bool) -> Result<Vec<ContainerSummary>, Error> {
+        self.get("/containers/json", &[("all", bool_str(all))])
+            .await
+    }
+
+    /// 容器详细信息
+    pub async fn inspect_container(&self, id: &str) -> Result<ContainerInfo, Error> {
+        self.get(&format!("/containers/{}/json", id), &[]).await
+    }
+
+    /// 创建容器，`config` 为 `POST /containers/create` 的请求体
+    pub async fn create_container(
+        &self,
+        name: &str,
+        config: &Value,
+    ) -> Result<CreateResponse, Error> {
+        self.post("/containers/create", &[("name", name)], Some(config))
+            .await
+    }
+
+    /// 启动容器
+    pub async fn start_container(&self, id: &str) -> Result<(), Error> {
+        self.post_empty(&format!("/containers/{}/start", id), &[])
+            .await
+    }
+
+    /// 停止容器，`timeout` 秒后强制停止
+    pub async fn stop_container(&self, id: &str, timeout: Option<u32>) -> Result<(), Error> {
+        let timeout = timeout.map(|t| t.to_string());
+        let query: Vec<(&str, &str)> = timeout.iter().map(|t| ("t", t.as_str())).collect();
+        self.post_empty(&format!("/containers/{}/stop", id), &query)
+            .await
+    }
+
+    /// 重启容器
+    pub async fn restart_container(&self, id: &str) -> Result<(), Error> {
+        self.post_empty(&format!("/containers/{}/restart", id), &[])
+            .await
+    }
+
+    /// 强制停止容器
+    pub async fn kill_container(&self, id: &str) -> Result<(), Error> {
+        self.post_empty(&format!("/containers/{}/kill", id), &[])
+            .await
+    }
+
+    /// 删除容器，`force` 为 `true` 时删除运行中的容器
+    pub async fn remove_container(&self, id: &str, force: bool) -> Result<(), Error> {
+        self.send(
+            Method::DELETE,
+            &format!("/containers/{}", id),
+            &[("force", bool_str(force))],
+            None,
+        )
+        .await
+        .map(|_| ())
+    }
+
+    /// 镜像列表
+    pub async fn images(&self) -> Result<Vec<ImageSummary>, Error> {
+        self.get("/images/json", &[]).await
+    }
+
+    /// 所有镜像的 `仓库:标签`，不含 `<none>:<none>`
+    pub async fn image_tags(&self) -> Result<Vec<String>, Error> {
+        Ok(self
+            .images()
+            .await?
+            .into_iter()
+            .flat_map(|image| image.repo_tags)
+            .filter(|tag| tag != "<none>:<none>")
+            .collect())
+    }
+
+    /// 镜像详细信息
+    pub async fn inspect_image(&self, name: &str) -> Result<ImageInfo, Error> {
+        self.get(&format!("/images/{}/json", name), &[]).await
+    }
+
+    /// 拉取镜像，等待拉取完成
+    pub async fn pull_image(&self, name: &str) -> Result<(), Error> {
+        let (image, tag) = split_tag(name);
+        let body = self
+            .send(
+                Method::POST,
+                "/images/create",
+                &[("fromImage", image), ("tag", tag)],
+                None,
+            )
+            .await?;
+        // 拉取进度以多行 JSON 返回，失败时状态码仍为 200，错误在 `error` 字段中
+        for line in body.split(|b| *b == b'\n') {
+            if let Ok(progress) = serde_json::from_slice::<Value>(line) {
+                if let Some(error) = progress.get("error").and_then(|e| e.as_str()) {
+                    return Err(Error::other(format!("pull {} failed: {}", name, error)));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// 删除镜像
+    pub async fn remove_image(&self, name: &str, force: bool) -> Result<(), Error> {
+        self.send(
+            Method::DELETE,
+            &format!("/images/{}", name),
+            &[("force", bool_str(force))],
+            None,
+        )
+        .await
+        .map(|_| ())
+    }
+
+    /// 清理悬空镜像
+    pub async fn prune_images(&self) -> Result<PruneResponse, Error> {
+        self.post("/images/prune", &[], None).await
+    }
+
+    /// 网络列表
+    pub async fn networks(&self) -> Result<Vec<Network>, Error> {
+        self.get("/networks", &[]).await
+    }
+
+    /// 网络详细信息
+    pub async fn inspect_network(&self, id: &str) -> Result<Network, Error> {
+        self.get(&format!("/networks/{}", id), &[]).await
+    }
+
+    /// 创建网络
+    pub async fn create_network(&self, name: &str, driver: &str) -> Result<CreateResponse, Error> {
+        let body = json!({ "Name": name, "Driver": driver });
+        self.post("/networks/create", &[], Some(&body)).await
+    }
+
+    /// 删除网络
+    pub async fn remove_network(&self, id: &str) -> Result<(), Error> {
+        self.send(Method::DELETE, &format!("/networks/{}", id), &[], None)
+            .await
+            .map(|_| ())
+    }
+
+    /// 数据卷列表
+    pub async fn volumes(&self) -> Result<Vec<Volume>, Error> {
+        let list: VolumeList = self.get("/volumes", &[]).await?;
+        Ok(list.volumes.unwrap_or_default())
+    }
+
+    /// 创建数据卷
+    pub async fn create_volume(&self, name: &str, driver: &str) -> Result<Volume, Error> {
+        let body = json!({ "Name": name, "Driver": driver });
+        self.post("/volumes/create", &[], Some(&body)).await
+    }
+
+    /// 删除数据卷
+    pub async fn remove_volume(&self, name: &str, force: bool) -> Result<(), Error> {
+        self.send(
+            Method::DELETE,
+            &format!("/volumes/{}", name),
+            &[("force", bool_str(force))],
+            None,
+        )
+        .await
+        .map(|_| ())
+    }
+
+    async fn get<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        query: &[(&str, &str)],
+    ) -> Result<T, Error> {
+        let body = self.send(Method::GET, path, query, None).await?;
+        parse_json(&body)
+    }
+
+    async fn post<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        query: &[(&str, &str)],
+        body: Option<&Value>,
+    ) -> Result<T, Error> {
+        let body = self.send(Method::POST, path, query, body).await?;
+        parse_json(&body)
+    }
+
+    async fn post_empty(&self, path: &str, query: &[(&str, &str)]) -> Result<(), Error> {
+        self.send(Method::POST, path, query, None).await.map(|_| ())
+    }
+
+    /// 发送请求并读取完整响应体，非 2xx 状态码转换为错误
+    async fn send(
+        &self,
+        method: Method,
+        path: &str,
+        query: &[(&str, &str)],
+        body: Option<&Value>,
+    ) -> Result<Bytes, Error> {
+        let uri = self.uri(path, query)?;
+        debug!("Docker API {} {}", method, uri);
+
+        let stream = UnixStream::connect(&self.socket).await.map_err(|e| {
+            Error::new(
+                e.kind(),
+                format!("connect to {} failed: {}", self.socket.display(), e),
+            )
+        })?;
+        let (mut sender, connection) = hyper::client::conn::http1::handshake(TokioIo::new(stream))
+            .await
+            .map_err(http_error)?;
+        tokio::spawn(async move {
+            if let Err(e) = connection.await {
+                warn!("Docker API connection error: {}", e);
+            }
+        });
+
+        let body = match body {
+            Some(body) => Bytes::from(serde_json::to_vec(body)?),
+            None => Bytes::new(),
+        };
+        let request = Request::builder()
+            .method(method)
+            .uri(&uri)
+            .header(HOST, "docker")
+            .header(CONTENT_TYPE, "application/json")
+            .body(Full::new(body))
+            .map_err(|e| Error::new(ErrorKind::InvalidInput, e.to_string()))?;
+        let response = sender.send_request(request).await.map_err(http_error)?;
+        let status = response.status();
+        let bytes = response
+            .into_body()
+            .collect()
+            .await
+            .map_err(http_error)?
+            .to_bytes();
+        if !status.is_success() {
+            return Err(api_error(status, &uri, &bytes));
+        }
+        Ok(bytes)
+    }
+
+    fn uri(&self, path: &str, query: &[(&str, &str)]) -> Result<String, Error> {
+        let prefix = match &self.api_version {
+            Some(version) => format!("/v{}", version.trim_start_matches('v')),
+            None => String::new(),
+        };
+        // 借助 URL 解析器完成路径和查询参数的编码
+        let mut url = reqwest::Url::parse(&format!("http://docker{}{}", prefix, path))
+            .map_err(|e| Error::new(ErrorKind::InvalidInput, e.to_string()))?;
+        if !query.is_empty() {
+            url.query_pairs_mut().extend_pairs(query);
+        }
+        let mut uri = url.path().to_string();
+        if let Some(query) = url.query() {
+            uri.push('?');
+            uri.push_str(query);
+        }
+        Ok(uri)
+    }
+}
+
+/// 把 `null` 当作默认值，Docker 对空列表和空映射常返回 `null`
+pub(crate) fn nullable<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Default + Deserialize<'de>,
+{
+    Ok(Option::<T>::deserialize(deserializer)?.unwrap_or_default())
+}
+
+fn parse_json<T: DeserializeOwned>(body: &[u8]) -> Result<T, Error> {
+    serde_json::from_slice(body).map_err(|e| Error::new(ErrorKind::InvalidData, e.to_string()))
+}
+
+fn http_error(e: impl std::fmt::Display) -> Error {
+    Error::other(format!("Docker API error: {}", e))
+}
+
+/// 将错误状态码转换为对应的错误类型，错误信息取自响应体的 `message` 字段
+fn api_error(status: StatusCode, uri: &str, body: &[u8]) -> Error {
+    let message = serde_json::from_slice::<Value>(body)
+        .ok()
+        .and_then(|v| v.get("message").and_then(|m| m.as_str()).map(String::from))
+        .unwrap_or_else(|| String::from_utf8_lossy(body).trim().to_string());
+    let kind = match status {
+        StatusCode::NOT_FOUND => ErrorKind::NotFound,
+        StatusCode::CONFLICT => ErrorKind::AlreadyExists,
+        StatusCode::BAD_REQUEST => ErrorKind::InvalidInput,
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => ErrorKind::PermissionDenied,
+        _ => ErrorKind::Other,
+    };
+    Error::new(kind, format!("{} {}: {}", status.as_u16(), uri, message))
+}
+
+fn bool_str(value: bool) -> &'static str {
+    if value {
+        "true"
+    } else {
+        "false"
+    }
+}
+
+/// 拆分 `镜像:标签`，没有标签时使用 `latest`，不会把仓库地址中的端口当作标签
+///
+/// 带摘要的镜像名（`镜像@sha256:...`）整体作为镜像名，标签为空。
+fn split_tag(name: &str) -> (&str, &str) {
+    if name.contains('@') {
+        return (name, "");
+    }
+    match name.rsplit_once(':') {
+        Some((image, tag)) if !tag.contains('/') => (image, tag),
+        _ => (name, "latest"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::UnixListener;
+
+    /// 模拟守护进程：按请求行返回预设的状态码和响应体
+    fn mock_server(socket: &Path, routes: Vec<(&'static str, u16, &'static str)>) {
+        let listener = UnixListener::bind(socket).unwrap();
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut request = Vec::new();
+                let mut buf = [0u8; 1024];
+                while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                    let n = stream.read(&mut buf).await.unwrap();
+                    if n == 0 {
+                        break;
+                    }
+                    request.extend_from_slice(&buf[..n]);
+                }
+                let request = String::from_utf8_lossy(&request);
+                let line = request.lines().next().unwrap_or_default();
+                let (status, body) = routes
+                    .iter()
+                    .find(|(prefix, _, _)| line.starts_with(prefix))
+                    .map(|(_, status, body)| (*status, *body))
+                    .unwrap_or((500, r#"{"message":"unexpected request"}"#));
+                let response = format!(
+                    "HTTP/1.1 {} X\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+    }
+
+    #[tokio::test]
+    async fn client_against_mock_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("docker.sock");
+        mock_server(
+            &socket,
+            vec![
+                (
+                    "GET /v1.43/containers/json?all=true ",
+                    200,
+                    r#"[{"Id":"abc","Names":["/web"],"Image":"nginx","Ports":null,"Labels":null}]"#,
+                ),
+                (
+                    "GET /v1.43/images/nginx:latest/json ",
+                    200,
+                    r#"{"Id":"sha256:1","RepoTags":["nginx:latest"],"Config":{"Env":["A=1"]}}"#,
+                ),
+                (
+                    "GET /v1.43/containers/missing/json ",
+                    404,
+                    r#"{"message":"No such container: missing"}"#,
+                ),
+                ("DELETE /v1.43/containers/web?force=false ", 409, "in use"),
+            ],
+        );
+        let client = DockerClient::with_socket(&socket).api_version("1.43");
+
+        let containers = client.containers(true).await.unwrap();
+        assert_eq!(containers.len(), 1);
+        assert_eq!(containers[0].name(), "web");
+        assert!(containers[0].ports.is_empty());
+
+        let image = client.inspect_image("nginx:latest").await.unwrap();
+        assert_eq!(image.repo_tags, vec!["nginx:latest"]);
+        assert_eq!(image.config.env, vec!["A=1"]);
+
+        let err = client.inspect_container("missing").await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+        assert!(err.to_string().contains("No such container: missing"));
+
+        let err = client.remove_container("web", false).await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::AlreadyExists);
+        assert!(err.to_string().starts_with("409 "));
+    }
+
+    #[test]
+    fn split_tag_keeps_registry_port() {
+        assert_eq!(split_tag("nginx"), ("nginx", "latest"));
+        assert_eq!(split_tag("nginx:1.25"), ("nginx", "1.25"));
+        assert_eq!(
+            split_tag("registry:5000/app"),
+            ("registry:5000/app", "latest")
+        );
+        assert_eq!(split_tag("app@sha256:abc"), ("app@sha256:abc", ""));
+    }
+}
@@ -1,8 +1,15 @@
+pub mod compose;
 pub mod container_info;
+pub mod deploy;
+pub mod engine;
 
 use crate::command_utils;
+use crate::file_utils::archive::{self, ArchiveFormat, ArchiveOptions};
 use log::info;
+use std::fs;
 use std::io::Error;
+use std::path::{Path, PathBuf};
+use std::process::Command;
 
 pub fn docker_run_command(args: &[&str]) -> Result<String, Error> {
     info!("执行自定义docker命令");
@@ -41,6 +48,98 @@ pub fn container_inspect(name: &[&str]) -> Result<String, Error> {
     command_utils::run_command("docker", &args)
 }
 
+/// 获取镜像详细信息
+pub fn image_inspect(images: &[&str]) -> Result<String, Error> {
+    info!("获取镜像 {:?}详细信息", images);
+    let mut args = vec!["image", "inspect"];
+    args.extend_from_slice(images);
+    command_utils::run_command_checked("docker", &args)
+}
+
+/// 容器是否存在
+pub fn container_exists(name: &str) -> bool {
+    command_utils::run_command_checked("docker", &["container", "inspect", name]).is_ok()
+}
+
+/// 创建docker容器，`args` 为 `docker create` 之后的参数
+pub fn container_create(args: &[&str]) -> Result<String, Error> {
+    info!("创建容器 {:?}", args);
+    let mut create_args = vec!["create"];
+    create_args.extend_from_slice(args);
+    command_utils::run_command_checked("docker", &create_args)
+}
+
+/// 启动docker容器
+pub fn container_start(containers: &[&str]) -> Result<String, Error> {
+    info!("启动容器 {:?}", containers);
+    let mut args = vec!["start"];
+    args.extend_from_slice(containers);
+    command_utils::run_command_checked("docker", &args)
+}
+
+/// 重启docker容器
+pub fn container_restart(containers: &[&str]) -> Result<String, Error> {
+    info!("重启容器 {:?}", containers);
+    let mut args = vec!["restart"];
+    args.extend_from_slice(containers);
+    command_utils::run_command_checked("docker", &args)
+}
+
+/// 网络是否存在
+pub fn network_exists(name: &str) -> bool {
+    command_utils::run_command_checked("docker", &["network", "inspect", name]).is_ok()
+}
+
+/// 创建docker网络，`labels` 为 `key=value` 形式
+pub fn network_create(name: &str, driver: Option<&str>, labels: &[&str]) -> Result<String, Error> {
+    info!("创建网络 {}", name);
+    let mut args = vec!["network", "create"];
+    if let Some(driver) = driver {
+        args.extend_from_slice(&["--driver", driver]);
+    }
+    for label in labels {
+        args.extend_from_slice(&["--label", label]);
+    }
+    args.push(name);
+    command_utils::run_command_checked("docker", &args)
+}
+
+/// 删除docker网络
+pub fn network_remove(name: &str) -> Result<String, Error> {
+    info!("删除网络 {}", name);
+    command_utils::run_command_checked("docker", &["network", "rm", name])
+}
+
+/// 把容器连接到网络
+pub fn network_connect(network: &str, container: &str, aliases: &[&str]) -> Result<String, Error> {
+    info!("连接容器 {} 到网络 {}", container, network);
+    let mut args = vec!["network", "connect"];
+    for alias in aliases {
+        args.extend_from_slice(&["--alias", alias]);
+    }
+    args.extend_from_slice(&[network, container]);
+    command_utils::run_command_checked("docker", &args)
+}
+
+/// 数据卷是否存在
+pub fn volume_exists(name: &str) -> bool {
+    command_utils::run_command_checked("docker", &["volume", "inspect", name]).is_ok()
+}
+
+/// 创建docker数据卷
+pub fn volume_create(name: &str, driver: Option<&str>, labels: &[&str]) -> Result<String, Error> {
+    info!("创建数据卷 {}", name);
+    let mut args = vec!["volume", "create"];
+    if let Some(driver) = driver {
+        args.extend_from_slice(&["--driver", driver]);
+    }
+    for label in labels {
+        args.extend_from_slice(&["--label", label]);
+    }
+    args.push(name);
+    command_utils::run_command_checked("docker", &args)
+}
+
 /// 获取docker镜像列表
 pub fn image_list_formatted() -> Result<String, Error> {
     info!("列出格式化的镜像列表");
@@ -63,19 +162,46 @@ pub fn build(name: &str) -> Result<String, Error> {
     command_utils::run_command("docker", &args)
 }
 
-/// 导出Docker镜像
-pub fn save(name: &str, path: &str) -> Result<String, Error> {
+/// 在指定目录下构建Docker镜像，不改变当前进程的工作目录
+pub fn build_in(dir: &Path, name: &str) -> Result<String, Error> {
+    info!("构建镜像 {}，目录 {}", name, dir.display());
+    let args = vec!["build", "-t", name, "."];
+    command_utils::run_command_in(dir, "docker", &args)
+}
+
+/// 导出Docker镜像到 `dir` 目录下的 `<镜像名>.tar`
+pub fn save(name: &str, dir: &Path) -> Result<String, Error> {
     info!("导出镜像 {}", name);
-    let filename = format!("{}/{}.tar", path, name.replace(':', "_").replace('/', "_"));
-    let args = vec!["save", "-o", &filename, name];
-    command_utils::run_command("docker", &args)
+    let mut command = Command::new("docker");
+    command
+        .args(["save", "-o"])
+        .arg(image_archive(name, dir, "tar"))
+        .arg(name);
+    command_utils::run_checked(command)
+}
+
+/// 导出Docker镜像并压缩为 `.tar.gz` 或 `.tar.zst`
+pub fn save_compressed(name: &str, dir: &Path, format: ArchiveFormat) -> Result<String, Error> {
+    let output = save(name, dir)?;
+    let tar_path = image_archive(name, dir, "tar");
+    let target = image_archive(name, dir, format.extension());
+    info!("压缩镜像 {} -> {}", tar_path.display(), target.display());
+    archive::compress_file(&tar_path, &target, format, &ArchiveOptions::new())?;
+    fs::remove_file(&tar_path)?;
+    Ok(output)
+}
+
+/// 镜像导出文件的路径，镜像名中的 `:` 和 `/` 替换为 `_`
+fn image_archive(name: &str, dir: &Path, extension: &str) -> PathBuf {
+    dir.join(format!("{}.{}", name.replace([':', '/'], "_"), extension))
 }
 
 /// 导入Docker镜像
-pub fn load(path: &str) -> Result<String, Error> {
-    info!("导入镜像 {}", path);
-    let args = vec!["load", "-i", path];
-    command_utils::run_command("docker", &args)
+pub fn load(path: &Path) -> Result<String, Error> {
+    info!("导入镜像 {}", path.display());
+    let mut command = Command::new("docker");
+    command.args(["load", "-i"]).arg(path);
+    command_utils::run_checked(command)
 }
 
 /// 清理docker镜像
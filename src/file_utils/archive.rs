@@ -0,0 +1,539 @@
+use crate::file_utils::filter::FileFilter;
+use crate::file_utils::traverse_dir_files_with_filter;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use log::{info, warn};
+use std::fs;
+use std::io::{self, Read, Seek, Write};
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// 可复现压缩包默认使用的时间：1980-01-01 00:00:00 UTC，也是 zip 支持的最早时间
+const DETERMINISTIC_MTIME: u64 = 315_532_800;
+
+/// 压缩包格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    Tar,
+    TarGz,
+    TarZst,
+    Zip,
+}
+
+impl ArchiveFormat {
+    /// 根据文件扩展名识别格式
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_str()?.to_lowercase();
+        if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(ArchiveFormat::TarGz)
+        } else if name.ends_with(".tar.zst") || name.ends_with(".tzst") {
+            Some(ArchiveFormat::TarZst)
+        } else if name.ends_with(".tar") {
+            Some(ArchiveFormat::Tar)
+        } else if name.ends_with(".zip") {
+            Some(ArchiveFormat::Zip)
+        } else {
+            None
+        }
+    }
+
+    /// 格式对应的扩展名
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ArchiveFormat::Tar => "tar",
+            ArchiveFormat::TarGz => "tar.gz",
+            ArchiveFormat::TarZst => "tar.zst",
+            ArchiveFormat::Zip => "zip",
+        }
+    }
+}
+
+/// 压缩包创建与解压选项
+#[derive(Debug, Default)]
+pub struct ArchiveOptions {
+    filter: FileFilter,
+    mtime: Option<SystemTime>,
+    level: Option<i32>,
+}
+
+impl ArchiveOptions {
+    /// 默认选项：保留文件原始修改时间和权限
+    pub fn new() -> Self {
+        ArchiveOptions::default()
+    }
+
+    /// 只打包或解压满足过滤条件的条目
+    pub fn filter(mut self, filter: FileFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// 生成可复现的压缩包
+    ///
+    /// 所有条目使用同一修改时间，优先取环境变量 `SOURCE_DATE_EPOCH`，
+    /// 并清除属主信息，相同内容多次打包得到的文件完全一致。
+    pub fn deterministic(mut self) -> Self {
+        let epoch = std::env::var("SOURCE_DATE_EPOCH")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(DETERMINISTIC_MTIME)
+            .max(DETERMINISTIC_MTIME);
+        self.mtime = Some(UNIX_EPOCH + Duration::from_secs(epoch));
+        self
+    }
+
+    /// 为所有条目指定修改时间
+    pub fn mtime(mut self, mtime: SystemTime) -> Self {
+        self.mtime = Some(mtime);
+        self
+    }
+
+    /// 压缩级别，gzip 为 0-9，zstd 为 1-22，zip 为 0-9
+    pub fn level(mut self, level: i32) -> Self {
+        self.level = Some(level);
+        self
+    }
+}
+
+/// 待打包的条目
+struct ArchiveEntry {
+    path: PathBuf,
+    rel_path: PathBuf,
+    metadata: fs::Metadata,
+}
+
+/// 打包目录，条目按路径排序，返回打包的条目数
+pub fn create_archive(
+    source: &Path,
+    archive: &Path,
+    format: ArchiveFormat,
+    options: &ArchiveOptions,
+) -> io::Result<usize> {
+    let entries = collect_entries(source, options)?;
+    let file = fs::File::create(archive)?;
+    let result = match format {
+        ArchiveFormat::Tar => write_tar(file, &entries, options).and_then(|mut w| w.flush()),
+        ArchiveFormat::TarGz => {
+            let level = options.level.map_or(Compression::default(), |l| {
+                Compression::new(l.clamp(0, 9) as u32)
+            });
+            write_tar(GzEncoder::new(file, level), &entries, options)
+                .and_then(|w| w.finish())
+                .map(|_| ())
+        }
+        ArchiveFormat::TarZst => {
+            let encoder = zstd::Encoder::new(file, options.level.unwrap_or(0))?;
+            write_tar(encoder, &entries, options)
+                .and_then(|w| w.finish())
+                .map(|_| ())
+        }
+        ArchiveFormat::Zip => write_zip(file, &entries, options),
+    };
+    if let Err(e) = result {
+        let _ = fs::remove_file(archive);
+        return Err(e);
+    }
+    info!(
+        "打包 {} -> {}，共 {} 个条目",
+        source.display(),
+        archive.display(),
+        entries.len()
+    );
+    Ok(entries.len())
+}
+
+/// 解压到指定目录，返回解压的条目数
+///
+/// 绝对路径或包含 `..` 的条目会被拒绝，防止写出目标目录。
+pub fn extract_archive(
+    archive: &Path,
+    target: &Path,
+    format: ArchiveFormat,
+    options: &ArchiveOptions,
+) -> io::Result<usize> {
+    fs::create_dir_all(target)?;
+    let file = fs::File::open(archive)?;
+    let count = match format {
+        ArchiveFormat::Tar => extract_tar(file, target, options)?,
+        ArchiveFormat::TarGz => extract_tar(GzDecoder::new(file), target, options)?,
+        ArchiveFormat::TarZst => extract_tar(zstd::Decoder::new(file)?, target, options)?,
+        ArchiveFormat::Zip => extract_zip(file, target, options)?,
+    };
+    info!(
+        "解压 {} -> {}，共 {} 个条目",
+        archive.display(),
+        target.display(),
+        count
+    );
+    Ok(count)
+}
+
+/// 将单个文件压缩为 gzip 或 zstd 流，例如把 `image.tar` 压缩为 `image.tar.gz`
+pub fn compress_file(
+    source: &Path,
+    target: &Path,
+    format: ArchiveFormat,
+    options: &ArchiveOptions,
+) -> io::Result<u64> {
+    let mut reader = fs::File::open(source)?;
+    let file = fs::File::create(target)?;
+    let result = match format {
+        ArchiveFormat::TarGz => {
+            let level = options.level.map_or(Compression::default(), |l| {
+                Compression::new(l.clamp(0, 9) as u32)
+            });
+            let mut encoder = GzEncoder::new(file, level);
+            io::copy(&mut reader, &mut encoder).and_then(|n| encoder.finish().map(|_| n))
+        }
+        ArchiveFormat::TarZst => {
+            let mut encoder = zstd::Encoder::new(file, options.level.unwrap_or(0))?;
+            io::copy(&mut reader, &mut encoder).and_then(|n| encoder.finish().map(|_| n))
+        }
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{:?} is not a stream compression format", format),
+        )),
+    };
+    if result.is_err() {
+        let _ = fs::remove_file(target);
+    }
+    result
+}
+
+/// 收集目录下满足过滤条件的条目并按相对路径排序
+fn collect_entries(source: &Path, options: &ArchiveOptions) -> io::Result<Vec<ArchiveEntry>> {
+    let (dirs, files) = traverse_dir_files_with_filter(source, true, &options.filter)?;
+    let mut entries = Vec::with_capacity(dirs.len() + files.len());
+    for data in dirs.iter().chain(files.iter()) {
+        let rel_path = data
+            .path()
+            .strip_prefix(source)
+            .unwrap_or(data.path())
+            .to_path_buf();
+        entries.push(ArchiveEntry {
+            path: data.path().to_path_buf(),
+            rel_path,
+            metadata: data.metadata()?.clone(),
+        });
+    }
+    entries.sort_by(|a, b| a.rel_path.cmp(&b.rel_path));
+    Ok(entries)
+}
+
+fn write_tar<W: Write>(
+    writer: W,
+    entries: &[ArchiveEntry],
+    options: &ArchiveOptions,
+) -> io::Result<W> {
+    let mut builder = tar::Builder::new(writer);
+    let mode = if options.mtime.is_some() {
+        tar::HeaderMode::Deterministic
+    } else {
+        tar::HeaderMode::Complete
+    };
+    for entry in entries {
+        let mut header = tar::Header::new_gnu();
+        header.set_metadata_in_mode(&entry.metadata, mode);
+        if let Some(mtime) = options.mtime {
+            header.set_mtime(unix_seconds(mtime));
+        }
+        if entry.metadata.is_dir() {
+            header.set_size(0);
+            builder.append_data(&mut header, &entry.rel_path, io::empty())?;
+        } else {
+            let file = fs::File::open(&entry.path)?;
+            builder.append_data(&mut header, &entry.rel_path, file)?;
+        }
+    }
+    builder.into_inner()
+}
+
+fn write_zip<W: Write + Seek>(
+    writer: W,
+    entries: &[ArchiveEntry],
+    options: &ArchiveOptions,
+) -> io::Result<()> {
+    let mut zip = zip::ZipWriter::new(writer);
+    for entry in entries {
+        let mtime = options
+            .mtime
+            .or_else(|| entry.metadata.modified().ok())
+            .unwrap_or(UNIX_EPOCH + Duration::from_secs(DETERMINISTIC_MTIME));
+        let mut file_options = zip::write::SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Deflated)
+            .compression_level(options.level.map(|l| l.clamp(0, 9) as i64))
+            .last_modified_time(zip_datetime(mtime));
+        if let Some(mode) = unix_mode(&entry.metadata) {
+            file_options = file_options.unix_permissions(mode);
+        }
+        // zip 中统一使用 `/` 作为路径分隔符
+        let name = entry
+            .rel_path
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        if entry.metadata.is_dir() {
+            zip.add_directory(name, file_options).map_err(zip_error)?;
+        } else {
+            zip.start_file(name, file_options).map_err(zip_error)?;
+            let mut file = fs::File::open(&entry.path)?;
+            io::copy(&mut file, &mut zip)?;
+        }
+    }
+    zip.finish().map_err(zip_error)?;
+    Ok(())
+}
+
+fn extract_tar<R: Read>(reader: R, target: &Path, options: &ArchiveOptions) -> io::Result<usize> {
+    let mut archive = tar::Archive::new(reader);
+    archive.set_preserve_permissions(true);
+    archive.set_preserve_mtime(true);
+    let mut count = 0;
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let rel_path = entry.path()?.into_owned();
+        check_entry_path(&rel_path)?;
+        let is_dir = entry.header().entry_type().is_dir();
+        if !allow_entry(&options.filter, &rel_path, is_dir) {
+            continue;
+        }
+        if entry.unpack_in(target)? {
+            count += 1;
+        } else {
+            warn!("Skip unsafe archive entry: {}", rel_path.display());
+        }
+    }
+    Ok(count)
+}
+
+fn extract_zip<R: Read + Seek>(
+    reader: R,
+    target: &Path,
+    options: &ArchiveOptions,
+) -> io::Result<usize> {
+    let mut archive = zip::ZipArchive::new(reader).map_err(zip_error)?;
+    let mut count = 0;
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i).map_err(zip_error)?;
+        let rel_path = match file.enclosed_name() {
+            Some(p) => p,
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("unsafe path in archive: {}", file.name()),
+                ))
+            }
+        };
+        check_entry_path(&rel_path)?;
+        if !allow_entry(&options.filter, &rel_path, file.is_dir()) {
+            continue;
+        }
+        let out_path = target.join(&rel_path);
+        if file.is_dir() {
+            fs::create_dir_all(&out_path)?;
+        } else {
+            if let Some(parent) = out_path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            let mut out = fs::File::create(&out_path)?;
+            io::copy(&mut file, &mut out)?;
+            #[cfg(unix)]
+            if let Some(mode) = file.unix_mode() {
+                use std::os::unix::fs::PermissionsExt;
+                fs::set_permissions(&out_path, fs::Permissions::from_mode(mode & 0o7777))?;
+            }
+        }
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// 拒绝绝对路径和包含 `..` 的条目
+fn check_entry_path(path: &Path) -> io::Result<()> {
+    for component in path.components() {
+        match component {
+            Component::Normal(_) | Component::CurDir => {}
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("unsafe path in archive: {}", path.display()),
+                ))
+            }
+        }
+    }
+    Ok(())
+}
+
+fn allow_entry(filter: &FileFilter, rel_path: &Path, is_dir: bool) -> bool {
+    if is_dir {
+        filter.allow_dir(rel_path)
+    } else {
+        filter.allow_path(rel_path)
+    }
+}
+
+fn unix_seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(unix)]
+fn unix_mode(metadata: &fs::Metadata) -> Option<u32> {
+    use std::os::unix::fs::PermissionsExt;
+    Some(metadata.permissions().mode() & 0o7777)
+}
+
+#[cfg(not(unix))]
+fn unix_mode(_metadata: &fs::Metadata) -> Option<u32> {
+    None
+}
+
+/// 将时间转换为 zip 使用的 DOS 时间（UTC）
+fn zip_datetime(time: SystemTime) -> zip::DateTime {
+    let secs = unix_seconds(time).max(DETERMINISTIC_MTIME);
+    let days = (secs / 86_400) as i64;
+    let rem = secs % 86_400;
+    // 由天数推算公历日期
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u8;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u8;
+    let year = (yoe + era * 400 + i64::from(month <= 2)) as u16;
+    zip::DateTime::from_date_and_time(
+        year,
+        month,
+        day,
+        (rem / 3_600) as u8,
+        (rem % 3_600 / 60) as u8,
+        (rem % 60) as u8,
+    )
+    .unwrap_or_default()
+}
+
+fn zip_error(e: zip::result::ZipError) -> io::Error {
+    io::Error::other(e)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_entry_path_rejects_traversal() {
+        for path in ["a.txt", "dir/b.txt", "./dir/c.txt", "dir/"] {
+            assert!(check_entry_path(Path::new(path)).is_ok(), "{}", path);
+        }
+        for path in ["../evil", "dir/../../evil", "/etc/passwd", "dir/.."] {
+            let err = check_entry_path(Path::new(path)).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{}", path);
+        }
+    }
+
+    #[test]
+    fn extract_rejects_zip_slip_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = dir.path().join("evil.zip");
+        let mut writer = zip::ZipWriter::new(fs::File::create(&archive).unwrap());
+        writer
+            .start_file("../evil.txt", zip::write::SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(b"evil").unwrap();
+        writer.finish().unwrap();
+
+        let target = dir.path().join("out");
+        let err = extract_archive(
+            &archive,
+            &target,
+            ArchiveFormat::Zip,
+            &ArchiveOptions::new(),
+        )
+        .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(!dir.path().join("evil.txt").exists());
+    }
+
+    #[test]
+    fn create_and_extract_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("src");
+        fs::create_dir_all(source.join("sub")).unwrap();
+        fs::write(source.join("a.txt"), "a").unwrap();
+        fs::write(source.join("sub/b.txt"), "b").unwrap();
+
+        for format in [
+            ArchiveFormat::Tar,
+            ArchiveFormat::TarGz,
+            ArchiveFormat::TarZst,
+            ArchiveFormat::Zip,
+        ] {
+            let archive = dir.path().join(format!("archive.{}", format.extension()));
+            assert_eq!(ArchiveFormat::from_path(&archive), Some(format));
+            let options = ArchiveOptions::new().deterministic();
+            create_archive(&source, &archive, format, &options).unwrap();
+            let target = dir.path().join(format!("out-{}", format.extension()));
+            extract_archive(&archive, &target, format, &options).unwrap();
+            assert_eq!(fs::read_to_string(target.join("a.txt")).unwrap(), "a");
+            assert_eq!(fs::read_to_string(target.join("sub/b.txt")).unwrap(), "b");
+        }
+    }
+
+    #[test]
+    fn deterministic_archives_are_identical() {
+        let dir = tempfile::tempdir().unwrap();
+        let files = [("a.txt", "a"), ("sub/b.txt", "b"), ("sub/deep/c.txt", "c")];
+        // 两棵内容相同的目录树，创建顺序和修改时间不同
+        let trees = [
+            (
+                dir.path().join("one"),
+                UNIX_EPOCH + Duration::from_secs(1_000_000_000),
+            ),
+            (
+                dir.path().join("two"),
+                UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+            ),
+        ];
+        for (index, (root, mtime)) in trees.iter().enumerate() {
+            let ordered: Vec<_> = if index == 0 {
+                files.iter().collect()
+            } else {
+                files.iter().rev().collect()
+            };
+            for (name, contents) in ordered {
+                let path = root.join(name);
+                fs::create_dir_all(path.parent().unwrap()).unwrap();
+                fs::write(&path, contents).unwrap();
+                fs::File::options()
+                    .write(true)
+                    .open(&path)
+                    .unwrap()
+                    .set_modified(*mtime)
+                    .unwrap();
+            }
+        }
+
+        for format in [
+            ArchiveFormat::Tar,
+            ArchiveFormat::TarGz,
+            ArchiveFormat::TarZst,
+            ArchiveFormat::Zip,
+        ] {
+            let options = ArchiveOptions::new().deterministic();
+            let archives: Vec<Vec<u8>> = trees
+                .iter()
+                .map(|(root, _)| {
+                    let archive = root.with_extension(format.extension());
+                    create_archive(root, &archive, format, &options).unwrap();
+                    fs::read(&archive).unwrap()
+                })
+                .collect();
+            assert_eq!(archives[0], archives[1], "{}", format.extension());
+        }
+    }
+}
@@ -0,0 +1,646 @@
+use crate::file_utils::file_data::FileData;
+use crate::file_utils::filter::FileFilter;
+use crate::file_utils::hash::{hash_file, ContentHasher, HashAlgorithm};
+use crate::file_utils::traverse_dir_files_with_filter;
+use log::{error, info};
+use std::collections::HashSet;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static TEMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// 原子替换选项
+#[derive(Debug, Clone)]
+pub struct ReplaceOptions {
+    backup: bool,
+    preserve_permissions: bool,
+    preserve_timestamps: bool,
+    algorithm: HashAlgorithm,
+}
+
+impl Default for ReplaceOptions {
+    fn default() -> Self {
+        ReplaceOptions {
+            backup: false,
+            preserve_permissions: true,
+            preserve_timestamps: true,
+            algorithm: HashAlgorithm::default(),
+        }
+    }
+}
+
+impl ReplaceOptions {
+    /// 默认选项：保留权限和时间戳，不备份，使用 SHA-256 校验
+    pub fn new() -> Self {
+        ReplaceOptions::default()
+    }
+
+    /// 替换前将原目标文件备份为 `<目标文件>.bak`
+    pub fn backup(mut self, backup: bool) -> Self {
+        self.backup = backup;
+        self
+    }
+
+    /// 是否保留源文件权限
+    pub fn preserve_permissions(mut self, preserve: bool) -> Self {
+        self.preserve_permissions = preserve;
+        self
+    }
+
+    /// 是否保留源文件的访问和修改时间
+    pub fn preserve_timestamps(mut self, preserve: bool) -> Self {
+        self.preserve_timestamps = preserve;
+        self
+    }
+
+    /// 校验和算法
+    pub fn algorithm(mut self, algorithm: HashAlgorithm) -> Self {
+        self.algorithm = algorithm;
+        self
+    }
+}
+
+/// 复制结果
+#[derive(Debug, Clone)]
+pub struct CopyReport {
+    /// 复制的字节数
+    pub bytes: u64,
+    /// 写入内容的校验和
+    pub checksum: String,
+    /// 备份文件路径，未备份时为 `None`
+    pub backup: Option<PathBuf>,
+}
+
+/// 原子替换目标文件
+///
+/// 先写入目标目录下的临时文件并刷盘，再重命名覆盖目标文件，
+/// 中途失败时目标文件保持原样，不会留下写了一半的文件。
+pub fn atomic_replace(
+    source: &Path,
+    target: &Path,
+    options: &ReplaceOptions,
+) -> io::Result<CopyReport> {
+    let source_metadata = fs::metadata(source)?;
+    if !source_metadata.is_file() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is not a file", source.display()),
+        ));
+    }
+
+    let temp_path = temp_path_for(target)?;
+    let (bytes, checksum) = match write_temp(source, &temp_path, &source_metadata, options) {
+        Ok(result) => result,
+        Err(e) => {
+            let _ = fs::remove_file(&temp_path);
+            return Err(e);
+        }
+    };
+
+    let backup = if options.backup && target.exists() {
+        match backup_file(target) {
+            Ok(path) => Some(path),
+            Err(e) => {
+                let _ = fs::remove_file(&temp_path);
+                return Err(e);
+            }
+        }
+    } else {
+        None
+    };
+
+    if let Err(e) = fs::rename(&temp_path, target) {
+        error!(
+            "Failed to rename {} to {}: {}",
+            temp_path.display(),
+            target.display(),
+            e
+        );
+        let _ = fs::remove_file(&temp_path);
+        return Err(e);
+    }
+    sync_parent_dir(target);
+
+    info!(
+        "替换文件 {} -> {}，{} 字节",
+        source.display(),
+        target.display(),
+        bytes
+    );
+    Ok(CopyReport {
+        bytes,
+        checksum,
+        backup,
+    })
+}
+
+/// 将源文件内容写入临时文件，返回写入字节数和校验和
+fn write_temp(
+    source: &Path,
+    temp_path: &Path,
+    source_metadata: &fs::Metadata,
+    options: &ReplaceOptions,
+) -> io::Result<(u64, String)> {
+    let mut reader = fs::File::open(source)?;
+    let mut writer = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(temp_path)?;
+    let mut hasher = ContentHasher::new(options.algorithm);
+    let mut buffer = [0u8; 64 * 1024];
+    let mut bytes = 0u64;
+    loop {
+        let n = reader.read(&mut buffer)?;
+        if n == 0 {
+            break;
+        }
+        writer.write_all(&buffer[..n])?;
+        hasher.update(&buffer[..n]);
+        bytes += n as u64;
+    }
+
+    if options.preserve_permissions {
+        writer.set_permissions(source_metadata.permissions())?;
+    }
+    if options.preserve_timestamps {
+        let mut times = fs::FileTimes::new();
+        if let Ok(accessed) = source_metadata.accessed() {
+            times = times.set_accessed(accessed);
+        }
+        if let Ok(modified) = source_metadata.modified() {
+            times = times.set_modified(modified);
+        }
+        writer.set_times(times)?;
+    }
+    writer.sync_all()?;
+    Ok((bytes, hasher.finalize()))
+}
+
+/// 在目标文件所在目录生成临时文件路径，保证重命名不跨文件系统
+fn temp_path_for(target: &Path) -> io::Result<PathBuf> {
+    let filename = target.file_name().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} has no file name", target.display()),
+        )
+    })?;
+    let mut temp_name = std::ffi::OsString::from(".");
+    temp_name.push(filename);
+    temp_name.push(format!(
+        ".{}.{}.tmp",
+        process::id(),
+        TEMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    Ok(target.with_file_name(temp_name))
+}
+
+/// 备份目标文件为 `<目标文件>.bak`，优先使用硬链接
+fn backup_file(target: &Path) -> io::Result<PathBuf> {
+    let mut backup_name = target.as_os_str().to_os_string();
+    backup_name.push(".bak");
+    let backup = PathBuf::from(backup_name);
+    if backup.exists() {
+        fs::remove_file(&backup)?;
+    }
+    if fs::hard_link(target, &backup).is_err() {
+        fs::copy(target, &backup)?;
+    }
+    Ok(backup)
+}
+
+/// 刷新目标所在目录，保证重命名操作落盘
+fn sync_parent_dir(target: &Path) {
+    #[cfg(unix)]
+    {
+        let parent = match target.parent() {
+            Some(p) if !p.as_os_str().is_empty() => p,
+            _ => Path::new("."),
+        };
+        if let Ok(dir) = fs::File::open(parent) {
+            let _ = dir.sync_all();
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = target;
+}
+
+/// 目标文件已存在时的处理策略
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    /// 总是覆盖
+    #[default]
+    Overwrite,
+    /// 跳过已存在的文件
+    Skip,
+    /// 源文件更新时才覆盖
+    UpdateIfNewer,
+}
+
+/// 目录复制过程中对单个条目执行的操作
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncAction {
+    Copied,
+    Skipped,
+    Deleted,
+}
+
+/// 进度回调参数
+#[derive(Debug)]
+pub struct CopyProgress<'a> {
+    /// 本次执行的操作
+    pub action: SyncAction,
+    /// 相对于根目录的路径
+    pub path: &'a Path,
+    /// 当前条目的字节数
+    pub bytes: u64,
+    /// 截至目前的统计
+    pub report: &'a SyncReport,
+}
+
+/// 目录复制/同步结果统计
+#[derive(Debug, Clone, Default)]
+pub struct SyncReport {
+    pub copied: usize,
+    pub skipped: usize,
+    pub deleted: usize,
+    pub bytes: u64,
+}
+
+/// 进度回调
+type ProgressCallback<'a> = Box<dyn FnMut(&CopyProgress) + 'a>;
+
+/// 目录复制选项
+#[derive(Default)]
+pub struct DirCopyOptions<'a> {
+    policy: ConflictPolicy,
+    delete_extraneous: bool,
+    filter: FileFilter,
+    progress: Option<ProgressCallback<'a>>,
+}
+
+impl<'a> DirCopyOptions<'a> {
+    /// 默认选项：覆盖已存在的文件，不删除目标目录中多余的文件
+    pub fn new() -> Self {
+        DirCopyOptions::default()
+    }
+
+    /// 目标文件已存在时的处理策略
+    pub fn policy(mut self, policy: ConflictPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// 删除目标目录中源目录不存在的文件，仅对 `sync_dir` 生效
+    pub fn delete_extraneous(mut self, delete: bool) -> Self {
+        self.delete_extraneous = delete;
+        self
+    }
+
+    /// 只处理满足过滤条件的文件
+    pub fn filter(mut self, filter: FileFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// 每处理一个文件调用一次的进度回调
+    pub fn progress(mut self, progress: impl FnMut(&CopyProgress) + 'a) -> Self {
+        self.progress = Some(Box::new(progress));
+        self
+    }
+
+    fn notify(&mut self, action: SyncAction, path: &Path, bytes: u64, report: &SyncReport) {
+        if let Some(progress) = self.progress.as_mut() {
+            progress(&CopyProgress {
+                action,
+                path,
+                bytes,
+                report,
+            });
+        }
+    }
+}
+
+/// 递归复制目录，不删除目标目录中已有的其他文件
+pub fn copy_dir(
+    source: &Path,
+    target: &Path,
+    options: &mut DirCopyOptions,
+) -> io::Result<SyncReport> {
+    let (report, _) = copy_tree(source, target, options)?;
+    Ok(report)
+}
+
+/// 移动目录
+///
+/// 目标不存在且未设置过滤条件时直接重命名，重命名后按目标目录中的文件统计并逐个回调进度；
+/// 否则先复制，再删除源目录中已复制或与目标内容相同的文件。
+/// 未被过滤条件选中的文件，以及按冲突策略跳过且内容与目标不同的文件会保留在源目录中。
+pub fn move_dir(
+    source: &Path,
+    target: &Path,
+    options: &mut DirCopyOptions,
+) -> io::Result<SyncReport> {
+    if !target.exists() && options.filter.is_empty() {
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        if fs::rename(source, target).is_ok() {
+            info!("移动目录 {} -> {}", source.display(), target.display());
+            let (_, files) = traverse_dir_files_with_filter(target, true, &options.filter)?;
+            let mut report = SyncReport::default();
+            for file in &files {
+                let rel_path = file.path().strip_prefix(target).unwrap_or(file.path());
+                let bytes = file.size().unwrap_or(0);
+                report.copied += 1;
+                report.bytes += bytes;
+                options.notify(SyncAction::Copied, rel_path, bytes, &report);
+            }
+            return Ok(report);
+        }
+    }
+
+    let (report, handled) = copy_tree(source, target, options)?;
+    for (rel_path, action) in &handled {
+        let source_path = source.join(rel_path);
+        let remove = match action {
+            SyncAction::Copied => true,
+            _ => same_content(&source_path, &target.join(rel_path))?,
+        };
+        if remove {
+            fs::remove_file(source_path)?;
+        }
+    }
+    remove_empty_dirs(source)?;
+    Ok(report)
+}
+
+/// 同步目录，类似 `rsync -r`
+///
+/// 按冲突策略复制文件，启用 `delete_extraneous` 时删除目标目录中多余的文件和目录。
+/// 只删除被过滤条件选中的文件，多余的目录在清空后才会删除。
+pub fn sync_dir(
+    source: &Path,
+    target: &Path,
+    options: &mut DirCopyOptions,
+) -> io::Result<SyncReport> {
+    let (mut report, handled) = copy_tree(source, target, options)?;
+    if !options.delete_extraneous {
+        return Ok(report);
+    }
+
+    let (source_dirs, _) = traverse_dir_files_with_filter(source, true, &options.filter)?;
+    let source_dirs: HashSet<PathBuf> = source_dirs
+        .iter()
+        .filter_map(|d| d.path().strip_prefix(source).ok().map(Path::to_path_buf))
+        .collect();
+    let handled: HashSet<PathBuf> = handled.into_iter().map(|(path, _)| path).collect();
+
+    let (target_dirs, target_files) =
+        traverse_dir_files_with_filter(target, true, &options.filter)?;
+    for file in &target_files {
+        let rel_path = file.path().strip_prefix(target).unwrap_or(file.path());
+        if handled.contains(rel_path) {
+            continue;
+        }
+        let bytes = file.size().unwrap_or(0);
+        fs::remove_file(file.path())?;
+        report.deleted += 1;
+        options.notify(SyncAction::Deleted, rel_path, bytes, &report);
+    }
+    // 先删除深层目录，只删除已清空的目录，保留其中未被过滤条件选中的文件
+    for dir in target_dirs.iter().rev() {
+        let rel_path = dir.path().strip_prefix(target).unwrap_or(dir.path());
+        if source_dirs.contains(rel_path) || fs::read_dir(dir.path())?.next().is_some() {
+            continue;
+        }
+        fs::remove_dir(dir.path())?;
+        report.deleted += 1;
+        options.notify(SyncAction::Deleted, rel_path, 0, &report);
+    }
+    info!(
+        "同步目录 {} -> {}：复制 {}，跳过 {}，删除 {}",
+        source.display(),
+        target.display(),
+        report.copied,
+        report.skipped,
+        report.deleted
+    );
+    Ok(report)
+}
+
+/// 复制目录树，返回统计结果和每个已处理文件的相对路径及执行的操作
+fn copy_tree(
+    source: &Path,
+    target: &Path,
+    options: &mut DirCopyOptions,
+) -> io::Result<(SyncReport, Vec<(PathBuf, SyncAction)>)> {
+    let (dirs, files) = traverse_dir_files_with_filter(source, true, &options.filter)?;
+    fs::create_dir_all(target)?;
+    for dir in &dirs {
+        let rel_path = dir.path().strip_prefix(source).unwrap_or(dir.path());
+        fs::create_dir_all(target.join(rel_path))?;
+    }
+
+    let replace_options = ReplaceOptions::new();
+    let mut report = SyncReport::default();
+    let mut handled = Vec::new();
+    for file in &files {
+        let rel_path = file.path().strip_prefix(source).unwrap_or(file.path());
+        let target_path = target.join(rel_path);
+        let action = if should_copy(file, &target_path, options.policy) {
+            let copied = atomic_replace(file.path(), &target_path, &replace_options)?;
+            report.copied += 1;
+            report.bytes += copied.bytes;
+            options.notify(SyncAction::Copied, rel_path, copied.bytes, &report);
+            SyncAction::Copied
+        } else {
+            report.skipped += 1;
+            options.notify(
+                SyncAction::Skipped,
+                rel_path,
+                file.size().unwrap_or(0),
+                &report,
+            );
+            SyncAction::Skipped
+        };
+        handled.push((rel_path.to_path_buf(), action));
+    }
+    Ok((report, handled))
+}
+
+/// 根据冲突策略判断是否需要复制
+fn should_copy(source: &FileData, target: &Path, policy: ConflictPolicy) -> bool {
+    let target_metadata = match fs::metadata(target) {
+        Ok(m) => m,
+        Err(_) => return true,
+    };
+    match policy {
+        ConflictPolicy::Overwrite => true,
+        ConflictPolicy::Skip => false,
+        ConflictPolicy::UpdateIfNewer => match (source.modified(), target_metadata.modified()) {
+            (Ok(source_time), Ok(target_time)) => source_time > target_time,
+            _ => true,
+        },
+    }
+}
+
+/// 两个文件大小和内容是否相同，目标不存在时返回 `false`
+fn same_content(source: &Path, target: &Path) -> io::Result<bool> {
+    let target_metadata = match fs::metadata(target) {
+        Ok(m) => m,
+        Err(_) => return Ok(false),
+    };
+    if fs::metadata(source)?.len() != target_metadata.len() {
+        return Ok(false);
+    }
+    let algorithm = HashAlgorithm::default();
+    Ok(hash_file(source, algorithm)? == hash_file(target, algorithm)?)
+}
+
+/// 删除目录下所有空目录，包括目录本身
+fn remove_empty_dirs(dir: &Path) -> io::Result<bool> {
+    let mut empty = true;
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            if !remove_empty_dirs(&entry.path())? {
+                empty = false;
+            }
+        } else {
+            empty = false;
+        }
+    }
+    if empty {
+        fs::remove_dir(dir)?;
+    }
+    Ok(empty)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(path: &Path, contents: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    #[test]
+    fn atomic_replace_backs_up_and_reports_checksum() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("new.txt");
+        let target = dir.path().join("target.txt");
+        write(&source, "hello");
+        write(&target, "old");
+
+        let options = ReplaceOptions::new().backup(true);
+        let report = atomic_replace(&source, &target, &options).unwrap();
+
+        assert_eq!(report.bytes, 5);
+        assert_eq!(
+            report.checksum,
+            hash_file(&source, HashAlgorithm::default()).unwrap()
+        );
+        assert_eq!(fs::read_to_string(&target).unwrap(), "hello");
+        let backup = report.backup.unwrap();
+        assert_eq!(backup, dir.path().join("target.txt.bak"));
+        assert_eq!(fs::read_to_string(backup).unwrap(), "old");
+        assert_eq!(
+            fs::metadata(&target).unwrap().modified().unwrap(),
+            fs::metadata(&source).unwrap().modified().unwrap()
+        );
+        // 不留下临时文件
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 3);
+    }
+
+    #[test]
+    fn atomic_replace_keeps_target_on_failure() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("target.txt");
+        write(&target, "old");
+
+        let options = ReplaceOptions::new();
+        assert!(atomic_replace(&dir.path().join("missing"), &target, &options).is_err());
+        let err = atomic_replace(dir.path(), &target, &options).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(fs::read_to_string(&target).unwrap(), "old");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn move_dir_keeps_skipped_files_that_differ() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("src");
+        let target = dir.path().join("dst");
+        write(&source.join("new.txt"), "new");
+        write(&source.join("same.txt"), "same");
+        write(&source.join("sub/conflict.txt"), "source");
+        write(&target.join("same.txt"), "same");
+        write(&target.join("sub/conflict.txt"), "target");
+
+        let mut options = DirCopyOptions::new().policy(ConflictPolicy::Skip);
+        let report = move_dir(&source, &target, &mut options).unwrap();
+
+        assert_eq!(report.copied, 1);
+        assert_eq!(report.skipped, 2);
+        assert!(!source.join("new.txt").exists());
+        assert!(!source.join("same.txt").exists());
+        assert_eq!(
+            fs::read_to_string(source.join("sub/conflict.txt")).unwrap(),
+            "source"
+        );
+        assert_eq!(
+            fs::read_to_string(target.join("sub/conflict.txt")).unwrap(),
+            "target"
+        );
+        assert_eq!(fs::read_to_string(target.join("new.txt")).unwrap(), "new");
+    }
+
+    #[test]
+    fn move_dir_rename_reports_moved_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("src");
+        let target = dir.path().join("nested/dst");
+        write(&source.join("a.txt"), "abc");
+        write(&source.join("sub/b.txt"), "de");
+
+        let mut seen = Vec::new();
+        let mut options = DirCopyOptions::new().progress(|p| seen.push(p.path.to_path_buf()));
+        let report = move_dir(&source, &target, &mut options).unwrap();
+        drop(options);
+
+        assert!(!source.exists());
+        assert_eq!(report.copied, 2);
+        assert_eq!(report.bytes, 5);
+        seen.sort();
+        assert_eq!(
+            seen,
+            vec![PathBuf::from("a.txt"), Path::new("sub").join("b.txt")]
+        );
+    }
+
+    #[test]
+    fn sync_dir_keeps_excluded_files_in_extraneous_dirs() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("src");
+        let target = dir.path().join("dst");
+        write(&source.join("keep.txt"), "keep");
+        write(&target.join("extra/old.txt"), "old");
+        write(&target.join("extra/notes.log"), "notes");
+        write(&target.join("gone/sub/x.txt"), "x");
+
+        let filter = FileFilter::new().exclude("*.log").unwrap();
+        let mut options = DirCopyOptions::new().delete_extraneous(true).filter(filter);
+        let report = sync_dir(&source, &target, &mut options).unwrap();
+
+        assert!(target.join("keep.txt").exists());
+        assert!(!target.join("extra/old.txt").exists());
+        assert!(target.join("extra/notes.log").exists());
+        assert!(!target.join("gone").exists());
+        // old.txt、x.txt 以及 gone/sub、gone 两个目录
+        assert_eq!(report.deleted, 4);
+    }
+}
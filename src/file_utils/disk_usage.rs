@@ -0,0 +1,467 @@
+use crate::file_utils::walker::ParallelWalker;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use log::{error, info};
+use std::collections::{BTreeMap, HashSet};
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// 目录占用统计
+#[derive(Debug, Clone, Default)]
+pub struct DirUsage {
+    pub path: PathBuf,
+    /// 目录下所有文件占用的字节数，包括所有层级
+    pub size: u64,
+    /// 目录下的文件数，包括所有层级
+    pub files: u64,
+    /// 深度限制内的子目录，按占用从大到小排列
+    pub children: Vec<DirUsage>,
+}
+
+/// 单个文件的占用
+#[derive(Debug, Clone)]
+pub struct FileUsage {
+    pub path: PathBuf,
+    pub size: u64,
+}
+
+/// 磁盘占用分析结果
+#[derive(Debug, Clone)]
+pub struct UsageReport {
+    pub root: DirUsage,
+    /// 占用最大的文件，从大到小排列
+    pub largest: Vec<FileUsage>,
+}
+
+/// 分析目录的磁盘占用
+///
+/// `max_depth` 限制输出的目录层级（0 表示只统计根目录），统计值始终包含所有层级；
+/// `top_n` 为返回的最大文件数量。硬链接只计算一次。
+pub fn analyze_usage(dir: &Path, max_depth: usize, top_n: usize) -> io::Result<UsageReport> {
+    let (_, files) = ParallelWalker::new().walk(dir)?;
+    let mut seen = HashSet::new();
+    let mut totals: BTreeMap<PathBuf, (u64, u64)> = BTreeMap::new();
+    let mut largest = Vec::new();
+    for file in &files {
+        if !file.metadata.is_file() || !seen.insert(file_id(&file.path, &file.metadata)) {
+            continue;
+        }
+        let size = disk_size(&file.metadata);
+        // 累加到深度限制内的每一级上级目录
+        let mut ancestor = PathBuf::new();
+        add_usage(&mut totals, &ancestor, size);
+        if let Some(parent) = file.rel_path.parent() {
+            for component in parent.components().take(max_depth) {
+                ancestor.push(component);
+                add_usage(&mut totals, &ancestor, size);
+            }
+        }
+        largest.push(FileUsage {
+            path: file.path.clone(),
+            size,
+        });
+    }
+    largest.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.path.cmp(&b.path)));
+    largest.truncate(top_n);
+
+    let root = build_tree(dir, Path::new(""), &totals);
+    info!(
+        "{} 占用 {}，共 {} 个文件",
+        dir.display(),
+        format_size(root.size),
+        root.files
+    );
+    Ok(UsageReport { root, largest })
+}
+
+/// 计算目录或文件占用的字节数
+pub fn dir_size(path: &Path) -> io::Result<u64> {
+    let metadata = fs::symlink_metadata(path)?;
+    if !metadata.is_dir() {
+        return Ok(disk_size(&metadata));
+    }
+    Ok(analyze_usage(path, 0, 0)?.root.size)
+}
+
+/// 以 KiB/MiB/GiB 格式化字节数
+pub fn format_size(size: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = size as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", size, UNITS[0])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+fn add_usage(totals: &mut BTreeMap<PathBuf, (u64, u64)>, path: &Path, size: u64) {
+    let entry = totals.entry(path.to_path_buf()).or_default();
+    entry.0 += size;
+    entry.1 += 1;
+}
+
+fn build_tree(root: &Path, rel_path: &Path, totals: &BTreeMap<PathBuf, (u64, u64)>) -> DirUsage {
+    let (size, files) = totals.get(rel_path).copied().unwrap_or_default();
+    let mut children: Vec<DirUsage> = totals
+        .keys()
+        .filter(|p| p.parent() == Some(rel_path) && !p.as_os_str().is_empty())
+        .map(|p| build_tree(root, p, totals))
+        .collect();
+    children.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.path.cmp(&b.path)));
+    DirUsage {
+        path: root.join(rel_path),
+        size,
+        files,
+        children,
+    }
+}
+
+/// 文件实际占用的磁盘空间，Unix 下按分配的块计算
+#[cfg(unix)]
+fn disk_size(metadata: &fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    metadata.blocks() * 512
+}
+
+#[cfg(not(unix))]
+fn disk_size(metadata: &fs::Metadata) -> u64 {
+    metadata.len()
+}
+
+#[cfg(unix)]
+fn file_id(_path: &Path, metadata: &fs::Metadata) -> (u64, u64) {
+    use std::os::unix::fs::MetadataExt;
+    (metadata.dev(), metadata.ino())
+}
+
+#[cfg(not(unix))]
+fn file_id(path: &Path, _metadata: &fs::Metadata) -> PathBuf {
+    path.to_path_buf()
+}
+
+/// 构建产物识别规则
+#[derive(Debug, Clone)]
+pub enum ArtifactRule {
+    /// 指定名称的目录，`markers` 不为空时同级目录下须存在其中任一文件，
+    /// 例如只有旁边存在 `Cargo.toml` 的 `target` 才被视为构建产物
+    Dir { name: String, markers: Vec<String> },
+    /// 匹配通配符的文件，通配符匹配相对于清理根目录的路径
+    File { pattern: String },
+}
+
+impl ArtifactRule {
+    /// 目录规则
+    pub fn dir(name: &str, markers: &[&str]) -> Self {
+        ArtifactRule::Dir {
+            name: name.to_string(),
+            markers: markers.iter().map(|m| m.to_string()).collect(),
+        }
+    }
+
+    /// 文件规则
+    pub fn file(pattern: &str) -> Self {
+        ArtifactRule::File {
+            pattern: pattern.to_string(),
+        }
+    }
+
+    /// 默认规则：Rust/Maven 的 `target`、Node 的 `node_modules` 与 `dist`，
+    /// 以及 `dm export` 导出到 `images` 目录下的镜像包
+    pub fn defaults() -> Vec<Self> {
+        vec![
+            ArtifactRule::dir("target", &["Cargo.toml", "pom.xml"]),
+            ArtifactRule::dir("node_modules", &["package.json"]),
+            ArtifactRule::dir("dist", &["package.json"]),
+            ArtifactRule::file("**/images/*.tar"),
+            ArtifactRule::file("**/images/*.tar.gz"),
+            ArtifactRule::file("**/images/*.tar.zst"),
+        ]
+    }
+}
+
+/// 清理策略
+///
+/// 同时设置多个条件时须全部满足才会清理。
+#[derive(Debug, Clone)]
+pub struct CleanPolicy {
+    rules: Vec<ArtifactRule>,
+    older_than: Option<Duration>,
+    larger_than: Option<u64>,
+    dry_run: bool,
+}
+
+impl Default for CleanPolicy {
+    fn default() -> Self {
+        CleanPolicy {
+            rules: ArtifactRule::defaults(),
+            older_than: None,
+            larger_than: None,
+            dry_run: false,
+        }
+    }
+}
+
+impl CleanPolicy {
+    /// 使用默认规则，清理所有识别出的构建产物
+    pub fn new() -> Self {
+        CleanPolicy::default()
+    }
+
+    /// 替换识别规则
+    pub fn rules(mut self, rules: Vec<ArtifactRule>) -> Self {
+        self.rules = rules;
+        self
+    }
+
+    /// 追加识别规则
+    pub fn rule(mut self, rule: ArtifactRule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    /// 只清理最后修改时间早于该时长的产物，目录以其中最新的文件为准
+    pub fn older_than(mut self, age: Duration) -> Self {
+        self.older_than = Some(age);
+        self
+    }
+
+    /// 只清理占用不小于该字节数的产物
+    pub fn larger_than(mut self, size: u64) -> Self {
+        self.larger_than = Some(size);
+        self
+    }
+
+    /// 只生成报告，不实际删除
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+}
+
+/// 识别出的构建产物
+#[derive(Debug, Clone)]
+pub struct CleanCandidate {
+    pub path: PathBuf,
+    pub size: u64,
+    /// 最后修改时间，目录取其中最新的文件
+    pub modified: Option<SystemTime>,
+    /// 是否满足清理策略
+    pub selected: bool,
+}
+
+/// 清理结果
+#[derive(Debug, Clone, Default)]
+pub struct CleanReport {
+    pub dry_run: bool,
+    /// 识别出的全部构建产物
+    pub candidates: Vec<CleanCandidate>,
+    /// 已删除（试运行时为将要删除）的路径
+    pub removed: Vec<PathBuf>,
+    /// 释放（试运行时为可释放）的字节数
+    pub freed: u64,
+}
+
+/// 按策略清理目录下的构建产物
+pub fn clean_artifacts(dir: &Path, policy: &CleanPolicy) -> Result<CleanReport, Box<dyn Error>> {
+    let mut dir_rules = Vec::new();
+    let mut file_globs = GlobSetBuilder::new();
+    for rule in &policy.rules {
+        match rule {
+            ArtifactRule::Dir { name, markers } => dir_rules.push((name, markers)),
+            ArtifactRule::File { pattern } => {
+                file_globs.add(Glob::new(pattern)?);
+            }
+        }
+    }
+    let file_globs = file_globs.build()?;
+
+    let mut found = Vec::new();
+    find_artifacts(dir, dir, &dir_rules, &file_globs, &mut found)?;
+
+    let now = SystemTime::now();
+    let mut report = CleanReport {
+        dry_run: policy.dry_run,
+        ..Default::default()
+    };
+    for path in found {
+        let (size, modified) = artifact_stats(&path)?;
+        let old_enough = policy.older_than.is_none_or(|age| {
+            modified
+                .and_then(|m| now.duration_since(m).ok())
+                .is_some_and(|elapsed| elapsed >= age)
+        });
+        let large_enough = policy.larger_than.is_none_or(|min| size >= min);
+        let selected = old_enough && large_enough;
+        if selected {
+            if policy.dry_run {
+                info!(
+                    "[dry-run] 将删除 {}（{}）",
+                    path.display(),
+                    format_size(size)
+                );
+            } else {
+                info!("删除 {}（{}）", path.display(), format_size(size));
+                let result = if path.is_dir() {
+                    fs::remove_dir_all(&path)
+                } else {
+                    fs::remove_file(&path)
+                };
+                if let Err(e) = result {
+                    error!("Failed to remove {}: {}", path.display(), e);
+                    continue;
+                }
+            }
+            report.removed.push(path.clone());
+            report.freed += size;
+        }
+        report.candidates.push(CleanCandidate {
+            path,
+            size,
+            modified,
+            selected,
+        });
+    }
+    info!(
+        "{}清理 {} 个产物，释放 {}",
+        if policy.dry_run { "[dry-run] " } else { "" },
+        report.removed.len(),
+        format_size(report.freed)
+    );
+    Ok(report)
+}
+
+/// 查找构建产物，命中的目录不再向下查找
+fn find_artifacts(
+    root: &Path,
+    dir: &Path,
+    dir_rules: &[(&String, &Vec<String>)],
+    file_globs: &GlobSet,
+    found: &mut Vec<PathBuf>,
+) -> io::Result<()> {
+    let mut entries = fs::read_dir(dir)?.collect::<Result<Vec<_>, _>>()?;
+    entries.sort_by_key(|e| e.file_name());
+    for entry in entries {
+        let path = entry.path();
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            let name = entry.file_name();
+            let is_artifact = dir_rules.iter().any(|(rule_name, markers)| {
+                name.to_str() == Some(rule_name.as_str())
+                    && (markers.is_empty() || markers.iter().any(|m| dir.join(m).exists()))
+            });
+            if is_artifact {
+                found.push(path);
+            } else if let Err(e) = find_artifacts(root, &path, dir_rules, file_globs, found) {
+                error!("Failed to scan {}: {}", path.display(), e);
+            }
+        } else if file_type.is_file() {
+            let rel_path = path.strip_prefix(root).unwrap_or(&path);
+            if file_globs.is_match(rel_path) {
+                found.push(path);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 产物的占用和最后修改时间
+fn artifact_stats(path: &Path) -> io::Result<(u64, Option<SystemTime>)> {
+    let metadata = fs::symlink_metadata(path)?;
+    if !metadata.is_dir() {
+        return Ok((disk_size(&metadata), metadata.modified().ok()));
+    }
+    let (_, files) = ParallelWalker::new().walk(path)?;
+    let mut seen = HashSet::new();
+    let mut size = 0;
+    let mut modified = metadata.modified().ok();
+    for file in &files {
+        if let Ok(m) = file.metadata.modified() {
+            modified = modified.max(Some(m));
+        }
+        if file.metadata.is_file() && seen.insert(file_id(&file.path, &file.metadata)) {
+            size += disk_size(&file.metadata);
+        }
+    }
+    Ok((size, modified))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(path: &Path, contents: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    #[test]
+    fn clean_artifacts_dry_run_keeps_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        write(&root.join("rust/Cargo.toml"), "");
+        write(&root.join("rust/target/debug/app"), "binary");
+        write(&root.join("orphan/target/keep.txt"), "not a build output");
+        write(&root.join("web/package.json"), "{}");
+        write(&root.join("web/node_modules/lib/index.js"), "js");
+        write(&root.join("deploy/images/app.tar"), "image");
+        write(&root.join("deploy/app.tar"), "not in images");
+
+        let report = clean_artifacts(root, &CleanPolicy::new().dry_run(true)).unwrap();
+        assert!(report.dry_run);
+        let mut removed = report.removed.clone();
+        removed.sort();
+        assert_eq!(
+            removed,
+            vec![
+                root.join("deploy/images/app.tar"),
+                root.join("rust/target"),
+                root.join("web/node_modules"),
+            ]
+        );
+        assert_eq!(
+            report.freed,
+            report.candidates.iter().map(|c| c.size).sum::<u64>()
+        );
+        for path in &removed {
+            assert!(path.exists(), "{}", path.display());
+        }
+
+        // 刚创建的产物不满足时间条件，不会被选中
+        let policy = CleanPolicy::new()
+            .dry_run(true)
+            .older_than(Duration::from_secs(3600));
+        let report = clean_artifacts(root, &policy).unwrap();
+        assert_eq!(report.candidates.len(), 3);
+        assert!(report.removed.is_empty());
+        assert!(report.candidates.iter().all(|c| !c.selected));
+    }
+
+    #[test]
+    fn clean_artifacts_removes_selected() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        write(&root.join("app/Cargo.toml"), "");
+        write(&root.join("app/target/out"), "x");
+
+        let policy = CleanPolicy::new().rules(vec![ArtifactRule::dir("target", &["Cargo.toml"])]);
+        let report = clean_artifacts(root, &policy).unwrap();
+        assert_eq!(report.removed, vec![root.join("app/target")]);
+        assert!(!root.join("app/target").exists());
+        assert!(root.join("app/Cargo.toml").exists());
+    }
+
+    #[test]
+    fn format_size_uses_binary_units() {
+        assert_eq!(format_size(0), "0 B");
+        assert_eq!(format_size(1023), "1023 B");
+        assert_eq!(format_size(1536), "1.5 KiB");
+        assert_eq!(format_size(5 * 1024 * 1024 * 1024), "5.0 GiB");
+    }
+}
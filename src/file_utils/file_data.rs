@@ -1,46 +1,280 @@
-use log::error;
-use std::error::Error;
+use std::ffi::{OsStr, OsString};
 use std::fs;
-use std::path::PathBuf;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::SystemTime;
 
+/// 文件类型
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FileKind {
+    File,
+    Dir,
+    /// 符号链接及其指向的路径
+    Symlink(PathBuf),
+    /// 设备、管道、套接字等其他类型
+    Other,
+}
+
+/// 文件所有者
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Owner {
+    pub uid: u32,
+    pub gid: u32,
+}
+
+/// 文件信息
+///
+/// 路径以 `PathBuf` 保存，支持非 UTF-8 文件名；元数据在第一次使用时才读取并缓存。
+#[derive(Debug)]
 pub struct FileData {
-    pub path: String,
-    pub path_buf: PathBuf,
-    pub abs_path: String,
-    pub filename: String,
-    pub metadata: fs::Metadata,
+    path: PathBuf,
+    metadata: OnceLock<fs::Metadata>,
+    symlink_metadata: OnceLock<fs::Metadata>,
 }
 
 impl FileData {
-    pub fn new(path: String) -> Result<Self, Box<dyn Error>> {
-        // 获取绝对路径
-        let path_buf = PathBuf::from(&path);
-        let abs_path_buf = match fs::canonicalize(&path_buf) {
-            Ok(pb) => pb,
-            Err(e) => {
-                error!("Can't canonicalize path {}: {}", path_buf.display(), e);
-                return Err(Box::new(e));
-            }
-        };
-        let abs_path = abs_path_buf.to_str().ok_or("无法转换为字符串")?.to_string();
-
-        // 获取文件名
-        let filename = abs_path_buf
-            .file_name()
-            .ok_or("无法获取文件名")?
-            .to_str()
-            .ok_or("文件名包含无效 UTF-8")?
-            .to_string();
-
-        // 获取文件元数据
-        let metadata = fs::metadata(&abs_path)?;
-
-        Ok(FileData {
-            path,
-            path_buf,
-            abs_path,
-            filename,
-            metadata,
+    /// 创建文件信息，不访问文件系统
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        FileData {
+            path: path.into(),
+            metadata: OnceLock::new(),
+            symlink_metadata: OnceLock::new(),
+        }
+    }
+
+    /// 打开已存在的文件或目录，路径不存在时返回错误
+    pub fn open(path: impl Into<PathBuf>) -> io::Result<Self> {
+        let data = FileData::new(path);
+        data.metadata()?;
+        Ok(data)
+    }
+
+    /// 使用已读取的元数据创建文件信息，避免重复的系统调用
+    pub fn with_metadata(path: impl Into<PathBuf>, metadata: fs::Metadata) -> Self {
+        let data = FileData::new(path);
+        if metadata.file_type().is_symlink() {
+            let _ = data.symlink_metadata.set(metadata);
+        } else {
+            let _ = data.symlink_metadata.set(metadata.clone());
+            let _ = data.metadata.set(metadata);
+        }
+        data
+    }
+
+    /// 文件路径
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// 转换为 `PathBuf`
+    pub fn into_path(self) -> PathBuf {
+        self.path
+    }
+
+    /// 绝对路径，会解析符号链接
+    pub fn abs_path(&self) -> io::Result<PathBuf> {
+        fs::canonicalize(&self.path)
+    }
+
+    /// 文件名，路径为 `.` 或 `..` 时取其绝对路径的文件名
+    pub fn file_name(&self) -> Option<OsString> {
+        if let Some(name) = self.path.file_name() {
+            return Some(name.to_os_string());
+        }
+        self.abs_path()
+            .ok()
+            .and_then(|p| p.file_name().map(OsStr::to_os_string))
+    }
+
+    /// 文件名，非 UTF-8 字符会被替换
+    pub fn file_name_lossy(&self) -> String {
+        self.file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    }
+
+    /// 扩展名
+    pub fn extension(&self) -> Option<&OsStr> {
+        self.path.extension()
+    }
+
+    /// 文件元数据，跟随符号链接
+    pub fn metadata(&self) -> io::Result<&fs::Metadata> {
+        if let Some(metadata) = self.metadata.get() {
+            return Ok(metadata);
+        }
+        let metadata = fs::metadata(&self.path)?;
+        Ok(self.metadata.get_or_init(|| metadata))
+    }
+
+    /// 文件元数据，不跟随符号链接
+    pub fn symlink_metadata(&self) -> io::Result<&fs::Metadata> {
+        if let Some(metadata) = self.symlink_metadata.get() {
+            return Ok(metadata);
+        }
+        let metadata = fs::symlink_metadata(&self.path)?;
+        Ok(self.symlink_metadata.get_or_init(|| metadata))
+    }
+
+    /// 路径是否存在
+    pub fn exists(&self) -> bool {
+        self.symlink_metadata().is_ok()
+    }
+
+    /// 是否为目录，跟随符号链接
+    pub fn is_dir(&self) -> bool {
+        self.metadata().is_ok_and(|m| m.is_dir())
+    }
+
+    /// 是否为普通文件，跟随符号链接
+    pub fn is_file(&self) -> bool {
+        self.metadata().is_ok_and(|m| m.is_file())
+    }
+
+    /// 是否为符号链接
+    pub fn is_symlink(&self) -> bool {
+        self.symlink_metadata()
+            .is_ok_and(|m| m.file_type().is_symlink())
+    }
+
+    /// 文件大小（字节）
+    pub fn size(&self) -> io::Result<u64> {
+        Ok(self.metadata()?.len())
+    }
+
+    /// 文件权限
+    pub fn permissions(&self) -> io::Result<fs::Permissions> {
+        Ok(self.metadata()?.permissions())
+    }
+
+    /// Unix 权限位，例如 `0o755`
+    #[cfg(unix)]
+    pub fn mode(&self) -> io::Result<u32> {
+        use std::os::unix::fs::PermissionsExt;
+        Ok(self.permissions()?.mode() & 0o7777)
+    }
+
+    /// 文件所有者
+    #[cfg(unix)]
+    pub fn owner(&self) -> io::Result<Owner> {
+        use std::os::unix::fs::MetadataExt;
+        let metadata = self.metadata()?;
+        Ok(Owner {
+            uid: metadata.uid(),
+            gid: metadata.gid(),
         })
     }
+
+    /// 最后修改时间
+    pub fn modified(&self) -> io::Result<SystemTime> {
+        self.metadata()?.modified()
+    }
+
+    /// 文件类型，符号链接会同时返回其指向的路径
+    pub fn file_type(&self) -> io::Result<FileKind> {
+        let file_type = self.symlink_metadata()?.file_type();
+        if file_type.is_symlink() {
+            Ok(FileKind::Symlink(fs::read_link(&self.path)?))
+        } else if file_type.is_dir() {
+            Ok(FileKind::Dir)
+        } else if file_type.is_file() {
+            Ok(FileKind::File)
+        } else {
+            Ok(FileKind::Other)
+        }
+    }
+
+    /// 符号链接指向的路径，非符号链接时返回 `None`
+    pub fn symlink_target(&self) -> io::Result<Option<PathBuf>> {
+        match self.file_type()? {
+            FileKind::Symlink(target) => Ok(Some(target)),
+            _ => Ok(None),
+        }
+    }
+
+    /// 根据文件头识别 MIME 类型，无法识别时返回 `None`
+    pub fn mime_type(&self) -> io::Result<Option<&'static str>> {
+        if !self.is_file() {
+            return Ok(None);
+        }
+        Ok(infer::get_from_path(&self.path)?.map(|t| t.mime_type()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_utils::traverse_dir_files;
+
+    #[test]
+    fn open_and_traverse_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.txt");
+        fs::write(&path, "abc").unwrap();
+
+        let data = FileData::open(&path).unwrap();
+        assert!(data.is_file());
+        assert!(!data.is_dir());
+        assert_eq!(data.size().unwrap(), 3);
+        assert_eq!(data.file_name(), Some(OsString::from("a.txt")));
+        assert_eq!(data.extension(), Some(OsStr::new("txt")));
+        assert_eq!(data.file_type().unwrap(), FileKind::File);
+        // 文件不能作为目录遍历
+        assert!(traverse_dir_files(&path, true).is_err());
+
+        let missing = FileData::open(dir.path().join("missing")).unwrap_err();
+        assert_eq!(missing.kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn open_and_traverse_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("sub")).unwrap();
+        fs::write(dir.path().join("a.txt"), "a").unwrap();
+        fs::write(dir.path().join("sub/b.txt"), "b").unwrap();
+
+        let data = FileData::open(dir.path()).unwrap();
+        assert!(data.is_dir());
+        assert_eq!(data.file_type().unwrap(), FileKind::Dir);
+        assert_eq!(
+            data.file_name(),
+            dir.path().file_name().map(OsStr::to_os_string)
+        );
+
+        let (dirs, files) = traverse_dir_files(dir.path(), true).unwrap();
+        assert_eq!(
+            dirs.iter().map(FileData::path).collect::<Vec<_>>(),
+            [dir.path().join("sub")]
+        );
+        let mut files: Vec<_> = files.into_iter().map(FileData::into_path).collect();
+        files.sort();
+        assert_eq!(
+            files,
+            [dir.path().join("a.txt"), dir.path().join("sub/b.txt")]
+        );
+
+        let (_, files) = traverse_dir_files(dir.path(), false).unwrap();
+        assert_eq!(files.len(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn non_utf8_names_are_kept() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let name = OsStr::from_bytes(b"caf\xe9.txt");
+        let path = dir.path().join(name);
+        fs::write(&path, "x").unwrap();
+
+        let data = FileData::open(&path).unwrap();
+        assert_eq!(data.file_name().as_deref(), Some(name));
+        assert_eq!(data.file_name_lossy(), "caf\u{fffd}.txt");
+
+        let (_, files) = traverse_dir_files(dir.path(), false).unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].path(), path);
+        assert!(files[0].is_file());
+    }
 }
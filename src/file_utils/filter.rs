@@ -0,0 +1,310 @@
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use log::warn;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::time::SystemTime;
+
+/// 文件过滤器
+///
+/// 用于在遍历目录时筛选文件，支持包含/排除通配符、扩展名、
+/// 文件大小、修改时间以及 `.gitignore`/`.dockerignore` 规则。
+/// 通配符匹配的是相对于遍历根目录的路径。
+#[derive(Debug, Default)]
+pub struct FileFilter {
+    include: Vec<String>,
+    exclude: Vec<String>,
+    extensions: Vec<String>,
+    min_size: Option<u64>,
+    max_size: Option<u64>,
+    modified_after: Option<SystemTime>,
+    modified_before: Option<SystemTime>,
+    ignore_files: Vec<String>,
+    include_set: Option<GlobSet>,
+    exclude_set: Option<GlobSet>,
+}
+
+impl FileFilter {
+    /// 创建一个不做任何过滤的过滤器
+    pub fn new() -> Self {
+        FileFilter::default()
+    }
+
+    /// 添加包含通配符，设置后只保留匹配任一通配符的文件
+    pub fn include(mut self, pattern: &str) -> Result<Self, Box<dyn Error>> {
+        self.include.push(pattern.to_string());
+        self.include_set = Some(build_glob_set(&self.include)?);
+        Ok(self)
+    }
+
+    /// 添加排除通配符，匹配的文件和目录都会被跳过
+    pub fn exclude(mut self, pattern: &str) -> Result<Self, Box<dyn Error>> {
+        self.exclude.push(pattern.to_string());
+        self.exclude_set = Some(build_glob_set(&self.exclude)?);
+        Ok(self)
+    }
+
+    /// 添加扩展名，支持 `tar` 或 `tar.gz` 这样的多段扩展名，不区分大小写
+    pub fn extension(mut self, ext: &str) -> Self {
+        self.extensions
+            .push(ext.trim_start_matches('.').to_lowercase());
+        self
+    }
+
+    /// 最小文件大小（字节）
+    pub fn min_size(mut self, size: u64) -> Self {
+        self.min_size = Some(size);
+        self
+    }
+
+    /// 最大文件大小（字节）
+    pub fn max_size(mut self, size: u64) -> Self {
+        self.max_size = Some(size);
+        self
+    }
+
+    /// 只保留在该时间之后修改的文件
+    pub fn modified_after(mut self, time: SystemTime) -> Self {
+        self.modified_after = Some(time);
+        self
+    }
+
+    /// 只保留在该时间之前修改的文件
+    pub fn modified_before(mut self, time: SystemTime) -> Self {
+        self.modified_before = Some(time);
+        self
+    }
+
+    /// 启用 `.gitignore` 规则，遍历时会读取每一级目录下的 `.gitignore`
+    pub fn gitignore(self) -> Self {
+        self.ignore_file(".gitignore")
+    }
+
+    /// 启用 `.dockerignore` 规则
+    pub fn dockerignore(self) -> Self {
+        self.ignore_file(".dockerignore")
+    }
+
+    /// 启用自定义名称的忽略文件，语法与 `.gitignore` 相同
+    pub fn ignore_file(mut self, name: &str) -> Self {
+        if !self.ignore_files.iter().any(|f| f == name) {
+            self.ignore_files.push(name.to_string());
+        }
+        self
+    }
+
+    /// 是否未设置任何过滤条件
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty()
+            && self.exclude.is_empty()
+            && self.extensions.is_empty()
+            && self.min_size.is_none()
+            && self.max_size.is_none()
+            && self.modified_after.is_none()
+            && self.modified_before.is_none()
+            && self.ignore_files.is_empty()
+    }
+
+    /// 判断目录是否需要进入，`rel_path` 为相对于遍历根目录的路径
+    pub fn allow_dir(&self, rel_path: &Path) -> bool {
+        !self.is_excluded(rel_path)
+    }
+
+    /// 判断文件是否满足所有过滤条件，`rel_path` 为相对于遍历根目录的路径
+    pub fn allow_file(&self, rel_path: &Path, metadata: &fs::Metadata) -> bool {
+        if !self.allow_path(rel_path) {
+            return false;
+        }
+        let size = metadata.len();
+        if self.min_size.is_some_and(|min| size < min) {
+            return false;
+        }
+        if self.max_size.is_some_and(|max| size > max) {
+            return false;
+        }
+        if self.modified_after.is_some() || self.modified_before.is_some() {
+            let modified = match metadata.modified() {
+                Ok(t) => t,
+                Err(_) => return false,
+            };
+            if self.modified_after.is_some_and(|t| modified < t) {
+                return false;
+            }
+            if self.modified_before.is_some_and(|t| modified > t) {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// 只按路径判断文件是否满足通配符和扩展名条件，用于没有元数据的场景，例如压缩包条目
+    pub fn allow_path(&self, rel_path: &Path) -> bool {
+        if self.is_excluded(rel_path) {
+            return false;
+        }
+        if let Some(set) = &self.include_set {
+            if !set.is_match(rel_path) {
+                return false;
+            }
+        }
+        if !self.extensions.is_empty() && !self.match_extension(rel_path) {
+            return false;
+        }
+        true
+    }
+
+    /// 读取目录下启用的忽略文件，目录中不存在忽略文件时返回 `None`
+    pub(crate) fn load_ignore(&self, dir: &Path) -> Option<Gitignore> {
+        if self.ignore_files.is_empty() {
+            return None;
+        }
+        let mut builder = GitignoreBuilder::new(dir);
+        let mut found = false;
+        for name in &self.ignore_files {
+            let file = dir.join(name);
+            if file.is_file() {
+                if let Some(e) = builder.add(&file) {
+                    warn!("Failed to parse ignore file {}: {}", file.display(), e);
+                }
+                found = true;
+            }
+        }
+        if !found {
+            return None;
+        }
+        match builder.build() {
+            Ok(gitignore) => Some(gitignore),
+            Err(e) => {
+                warn!("Failed to build ignore rules in {}: {}", dir.display(), e);
+                None
+            }
+        }
+    }
+
+    /// 启用的忽略文件名列表
+    pub(crate) fn ignore_files(&self) -> &[String] {
+        &self.ignore_files
+    }
+
+    fn is_excluded(&self, rel_path: &Path) -> bool {
+        self.exclude_set
+            .as_ref()
+            .is_some_and(|set| set.is_match(rel_path))
+    }
+
+    fn match_extension(&self, rel_path: &Path) -> bool {
+        let filename = match rel_path.file_name().and_then(|n| n.to_str()) {
+            Some(name) => name.to_lowercase(),
+            None => return false,
+        };
+        self.extensions
+            .iter()
+            .any(|ext| filename.ends_with(&format!(".{}", ext)))
+    }
+}
+
+/// 判断路径是否被忽略规则命中，`ignores` 按目录层级由浅到深排列
+pub(crate) fn is_ignored(ignores: &[Gitignore], path: &Path, is_dir: bool) -> bool {
+    // 深层目录的规则优先，可以用 `!` 重新包含上层忽略的文件
+    for gitignore in ignores.iter().rev() {
+        let m = gitignore.matched(path, is_dir);
+        if m.is_ignore() {
+            return true;
+        }
+        if m.is_whitelist() {
+            return false;
+        }
+    }
+    false
+}
+
+fn build_glob_set(patterns: &[String]) -> Result<GlobSet, Box<dyn Error>> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(Glob::new(pattern)?);
+    }
+    Ok(builder.build()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_utils::traverse_dir_files_with_filter;
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    #[test]
+    fn allow_path_applies_globs_and_extensions() {
+        let filter = FileFilter::new()
+            .include("src/**")
+            .unwrap()
+            .exclude("**/target/**")
+            .unwrap()
+            .extension(".RS")
+            .extension("tar.gz");
+        assert!(filter.allow_path(Path::new("src/main.rs")));
+        assert!(filter.allow_path(Path::new("src/lib/MOD.RS")));
+        assert!(filter.allow_path(Path::new("src/a.tar.gz")));
+        assert!(!filter.allow_path(Path::new("src/a.gz")));
+        assert!(!filter.allow_path(Path::new("tests/a.rs")));
+        assert!(!filter.allow_path(Path::new("src/target/debug/a.rs")));
+        assert!(!filter.allow_dir(Path::new("src/target/debug")));
+        assert!(filter.allow_dir(Path::new("tests")));
+        assert!(FileFilter::new().include("[").is_err());
+    }
+
+    #[test]
+    fn allow_file_checks_size_and_modified_time() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.txt");
+        fs::write(&path, "12345").unwrap();
+        let metadata = fs::metadata(&path).unwrap();
+        let modified = metadata.modified().unwrap();
+        let rel = Path::new("a.txt");
+
+        assert!(FileFilter::new().is_empty());
+        assert!(FileFilter::new().min_size(5).allow_file(rel, &metadata));
+        assert!(!FileFilter::new().min_size(6).allow_file(rel, &metadata));
+        assert!(!FileFilter::new().max_size(4).allow_file(rel, &metadata));
+        let hour = Duration::from_secs(3600);
+        assert!(FileFilter::new()
+            .modified_after(modified - hour)
+            .modified_before(modified + hour)
+            .allow_file(rel, &metadata));
+        assert!(!FileFilter::new()
+            .modified_after(modified + hour)
+            .allow_file(rel, &metadata));
+    }
+
+    #[test]
+    fn gitignore_rules_apply_per_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("sub/build")).unwrap();
+        fs::write(root.join(".gitignore"), "*.log\nbuild/\n").unwrap();
+        fs::write(root.join("sub/.gitignore"), "!keep.log\n").unwrap();
+        for file in [
+            "a.txt",
+            "a.log",
+            "sub/keep.log",
+            "sub/b.log",
+            "sub/build/c.txt",
+        ] {
+            fs::write(root.join(file), "x").unwrap();
+        }
+
+        let filter = FileFilter::new().gitignore();
+        let (_, files) = traverse_dir_files_with_filter(root, true, &filter).unwrap();
+        let mut files: Vec<PathBuf> = files
+            .iter()
+            .map(|f| f.path().strip_prefix(root).unwrap().to_path_buf())
+            .filter(|p| !p.ends_with(".gitignore"))
+            .collect();
+        files.sort();
+        assert_eq!(
+            files,
+            vec![PathBuf::from("a.txt"), Path::new("sub").join("keep.log")]
+        );
+    }
+}
@@ -0,0 +1,255 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+/// 文件内容哈希算法
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum HashAlgorithm {
+    #[default]
+    Sha256,
+    Blake3,
+}
+
+/// 增量哈希计算器，可边读取边计算
+pub enum ContentHasher {
+    Sha256(Sha256),
+    Blake3(Box<blake3::Hasher>),
+}
+
+impl ContentHasher {
+    /// 创建指定算法的哈希计算器
+    pub fn new(algorithm: HashAlgorithm) -> Self {
+        match algorithm {
+            HashAlgorithm::Sha256 => ContentHasher::Sha256(Sha256::new()),
+            HashAlgorithm::Blake3 => ContentHasher::Blake3(Box::new(blake3::Hasher::new())),
+        }
+    }
+
+    /// 追加数据
+    pub fn update(&mut self, data: &[u8]) {
+        match self {
+            ContentHasher::Sha256(hasher) => hasher.update(data),
+            ContentHasher::Blake3(hasher) => {
+                hasher.update(data);
+            }
+        }
+    }
+
+    /// 结束计算，返回十六进制字符串
+    pub fn finalize(self) -> String {
+        match self {
+            ContentHasher::Sha256(hasher) => hex::encode(hasher.finalize()),
+            ContentHasher::Blake3(hasher) => hasher.finalize().to_hex().to_string(),
+        }
+    }
+}
+
+/// 计算文件内容的哈希值，返回十六进制字符串
+pub fn hash_file(path: &Path, algorithm: HashAlgorithm) -> io::Result<String> {
+    let mut file = fs::File::open(path)?;
+    let mut buffer = [0u8; 64 * 1024];
+    let mut hasher = ContentHasher::new(algorithm);
+    loop {
+        let n = file.read(&mut buffer)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buffer[..n]);
+    }
+    Ok(hasher.finalize())
+}
+
+/// 缓存键，文件内容未变时 inode、大小和修改时间都不会变化
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct CacheKey {
+    #[cfg(unix)]
+    dev: u64,
+    #[cfg(unix)]
+    ino: u64,
+    #[cfg(not(unix))]
+    path: PathBuf,
+    len: u64,
+    modified: Option<SystemTime>,
+    algorithm: HashAlgorithm,
+}
+
+impl CacheKey {
+    #[allow(unused_variables)]
+    fn new(path: &Path, metadata: &fs::Metadata, algorithm: HashAlgorithm) -> Self {
+        #[cfg(unix)]
+        use std::os::unix::fs::MetadataExt;
+        CacheKey {
+            #[cfg(unix)]
+            dev: metadata.dev(),
+            #[cfg(unix)]
+            ino: metadata.ino(),
+            #[cfg(not(unix))]
+            path: path.to_path_buf(),
+            len: metadata.len(),
+            modified: metadata.modified().ok(),
+            algorithm,
+        }
+    }
+}
+
+/// 内存中的文件哈希缓存，以 inode 和修改时间为键
+///
+/// 可在多次遍历之间共享，文件未修改时直接返回上次计算的哈希值。
+#[derive(Debug, Default)]
+pub struct HashCache {
+    entries: Mutex<HashMap<CacheKey, String>>,
+}
+
+impl HashCache {
+    /// 创建一个空的缓存
+    pub fn new() -> Self {
+        HashCache::default()
+    }
+
+    /// 获取文件哈希，缓存未命中时计算并写入缓存
+    pub fn get_or_hash(
+        &self,
+        path: &Path,
+        metadata: &fs::Metadata,
+        algorithm: HashAlgorithm,
+    ) -> io::Result<String> {
+        let key = CacheKey::new(path, metadata, algorithm);
+        if let Some(hash) = self.entries.lock().unwrap().get(&key) {
+            return Ok(hash.clone());
+        }
+        let hash = hash_file(path, algorithm)?;
+        self.entries.lock().unwrap().insert(key, hash.clone());
+        Ok(hash)
+    }
+
+    /// 缓存条目数量
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    /// 缓存是否为空
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// 清空缓存
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+}
+
+/// 按哈希值查找内容重复的文件，返回每组重复文件的路径
+pub fn find_duplicates<'a, I>(entries: I) -> Vec<Vec<PathBuf>>
+where
+    I: IntoIterator<Item = (&'a Path, &'a str)>,
+{
+    let mut groups: HashMap<&str, Vec<PathBuf>> = HashMap::new();
+    for (path, hash) in entries {
+        groups.entry(hash).or_default().push(path.to_path_buf());
+    }
+    let mut duplicates: Vec<Vec<PathBuf>> = groups
+        .into_values()
+        .filter(|paths| paths.len() > 1)
+        .map(|mut paths| {
+            paths.sort();
+            paths
+        })
+        .collect();
+    duplicates.sort();
+    duplicates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn cache_hits_until_file_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.txt");
+        fs::write(&path, "abc").unwrap();
+        let cache = HashCache::new();
+        let algorithm = HashAlgorithm::Sha256;
+
+        let metadata = fs::metadata(&path).unwrap();
+        let first = cache.get_or_hash(&path, &metadata, algorithm).unwrap();
+        assert_eq!(first, hash_file(&path, algorithm).unwrap());
+        assert_eq!(cache.len(), 1);
+
+        // 同样的元数据直接命中缓存，不重新读取内容
+        fs::write(&path, "xyz").unwrap();
+        let file = fs::File::options().write(true).open(&path).unwrap();
+        file.set_modified(metadata.modified().unwrap()).unwrap();
+        let unchanged = fs::metadata(&path).unwrap();
+        assert_eq!(
+            cache.get_or_hash(&path, &unchanged, algorithm).unwrap(),
+            first
+        );
+        assert_eq!(cache.len(), 1);
+
+        // 修改时间变化
+        file.set_modified(metadata.modified().unwrap() + Duration::from_secs(10))
+            .unwrap();
+        let touched = fs::metadata(&path).unwrap();
+        let second = cache.get_or_hash(&path, &touched, algorithm).unwrap();
+        assert_eq!(second, hash_file(&path, algorithm).unwrap());
+        assert_ne!(second, first);
+
+        // 大小变化
+        fs::write(&path, "abcd").unwrap();
+        file.set_modified(touched.modified().unwrap()).unwrap();
+        let resized = fs::metadata(&path).unwrap();
+        assert_eq!(
+            cache.get_or_hash(&path, &resized, algorithm).unwrap(),
+            hash_file(&path, algorithm).unwrap()
+        );
+        assert_eq!(cache.len(), 3);
+
+        // 不同算法分别缓存
+        cache
+            .get_or_hash(&path, &resized, HashAlgorithm::Blake3)
+            .unwrap();
+        assert_eq!(cache.len(), 4);
+        cache.clear();
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn find_duplicates_groups_by_hash() {
+        let entries = [
+            (Path::new("b"), "1"),
+            (Path::new("a"), "1"),
+            (Path::new("c"), "2"),
+            (Path::new("e"), "3"),
+            (Path::new("d"), "3"),
+            (Path::new("f"), "3"),
+        ];
+        assert_eq!(
+            find_duplicates(entries),
+            [
+                vec![PathBuf::from("a"), PathBuf::from("b")],
+                vec![PathBuf::from("d"), PathBuf::from("e"), PathBuf::from("f")],
+            ]
+        );
+    }
+
+    #[test]
+    fn hasher_matches_known_digests() {
+        let mut hasher = ContentHasher::new(HashAlgorithm::Sha256);
+        hasher.update(b"ab");
+        hasher.update(b"c");
+        assert_eq!(
+            hasher.finalize(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        let mut hasher = ContentHasher::new(HashAlgorithm::Blake3);
+        hasher.update(b"abc");
+        assert_eq!(hasher.finalize(), blake3::hash(b"abc").to_hex().to_string());
+    }
+}
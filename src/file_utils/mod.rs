@@ -1,6 +1,18 @@
+pub mod archive;
+pub mod copy;
+pub mod disk_usage;
 pub mod file_data;
+pub mod filter;
+pub mod hash;
+pub mod snapshot;
+pub mod walker;
+pub mod watcher;
+pub mod workspace;
 
+use crate::file_utils::copy::ReplaceOptions;
 use crate::file_utils::file_data::FileData;
+use crate::file_utils::filter::{is_ignored, FileFilter};
+use ignore::gitignore::Gitignore;
 use log::error;
 use std::error::Error;
 use std::fs;
@@ -10,75 +22,109 @@ use std::path::Path;
 /// 遍历给定目录并返回文件路径列表
 /// `recursive` 参数表明是否递归遍历子目录
 pub fn traverse_dir_files(
-    dir: &str,
+    dir: impl AsRef<Path>,
     recursive: bool,
+) -> io::Result<(Vec<FileData>, Vec<FileData>)> {
+    traverse_dir_files_with_filter(dir, recursive, &FileFilter::new())
+}
+
+/// 按过滤条件遍历给定目录，返回 (目录列表, 文件列表)
+/// 被排除或被忽略规则命中的目录不会继续向下遍历
+pub fn traverse_dir_files_with_filter(
+    dir: impl AsRef<Path>,
+    recursive: bool,
+    filter: &FileFilter,
 ) -> io::Result<(Vec<FileData>, Vec<FileData>)> {
     let mut files = Vec::new();
     let mut dirs: Vec<FileData> = Vec::new();
 
-    let dir_file_data;
+    let root = dir.as_ref();
+    if let Err(e) = FileData::open(root) {
+        error!("Failed to open directory {}: {}", root.display(), e);
+        return Err(e);
+    }
 
-    match FileData::new(dir.to_string()) {
-        Ok(d) => dir_file_data = d,
-        Err(e) => {
-            error!("Failed to open file:{}", e);
-            return Err(io::Error::new(
-                io::ErrorKind::Other,
-                "Failed to open directory",
-            ));
+    let mut ignores = Vec::new();
+    traverse_filtered(
+        root,
+        root,
+        recursive,
+        filter,
+        &mut ignores,
+        &mut dirs,
+        &mut files,
+    )?;
+    Ok((dirs, files))
+}
+
+fn traverse_filtered(
+    root: &Path,
+    dir: &Path,
+    recursive: bool,
+    filter: &FileFilter,
+    ignores: &mut Vec<Gitignore>,
+    dirs: &mut Vec<FileData>,
+    files: &mut Vec<FileData>,
+) -> io::Result<()> {
+    let loaded = match filter.load_ignore(dir) {
+        Some(gitignore) => {
+            ignores.push(gitignore);
+            true
         }
-    }
+        None => false,
+    };
 
     //遍历目录
-    for entry in fs::read_dir(&dir_file_data.path_buf)? {
+    for entry in fs::read_dir(dir)? {
         let entry = entry?;
-        let path = match entry.path().canonicalize() {
-            Ok(p) => p,
-            Err(_) => continue,
+        let entry_path = entry.path();
+        let rel_path = entry_path.strip_prefix(root).unwrap_or(&entry_path);
+        let data = FileData::new(&entry_path);
+        let metadata = match data.metadata() {
+            Ok(m) => m,
+            Err(e) => {
+                error!("Failed to get file data {}: {}", entry_path.display(), e);
+                continue;
+            }
         };
-        if let Some(path_str) = path.to_str() {
-            match FileData::new(path_str.to_string()) {
-                Ok(data) => {
-                    if data.path_buf.is_dir() {
-                        dirs.push(data);
-                        if recursive {
-                            match traverse_dir_files(path.to_str().unwrap(), true) {
-                                Ok((sub_dirs, sub_files)) => {
-                                    dirs.extend(sub_dirs);
-                                    files.extend(sub_files);
-                                }
-                                Err(e) => {
-                                    error!("Failed to traverse subdirectory: {}", e);
-                                    continue;
-                                }
-                            }
-                        }
-                    } else {
-                        files.push(data);
-                    }
-                }
-                Err(e) => {
-                    error!("Failed to get file data:{}", e);
+        if metadata.is_dir() {
+            if is_ignored(ignores, &entry_path, true) || !filter.allow_dir(rel_path) {
+                continue;
+            }
+            // 不进入指向目录的符号链接，避免循环引用
+            let is_symlink = entry.file_type().is_ok_and(|t| t.is_symlink());
+            dirs.push(data);
+            if recursive && !is_symlink {
+                if let Err(e) =
+                    traverse_filtered(root, &entry_path, true, filter, ignores, dirs, files)
+                {
+                    error!("Failed to traverse subdirectory: {}", e);
                 }
             }
+        } else if !is_ignored(ignores, &entry_path, false) && filter.allow_file(rel_path, metadata)
+        {
+            files.push(data);
         }
     }
-    Ok((dirs, files))
+
+    if loaded {
+        ignores.pop();
+    }
+    Ok(())
 }
 
 /// 替换源文件到目标文件
+/// 以原子方式替换，失败时目标文件保持原样
 pub fn replace(source: &Path, target: &Path) -> Result<String, io::Error> {
-    match fs::copy(source, target) {
-        Ok(_) => Ok(String::from("文件替换成功！")),
-        Err(e) => Err(e),
-    }
+    copy::atomic_replace(source, target, &ReplaceOptions::new())?;
+    Ok(String::from("文件替换成功！"))
 }
 
-/// 创建文件夹
+/// 创建文件夹，父目录不存在时一并创建
 pub fn create_directory(path: &str) -> Result<FileData, Box<dyn Error>> {
     let dir = Path::new(path);
     if !dir.exists() {
-        fs::create_dir(dir)?;
+        fs::create_dir_all(dir)?;
     }
-    FileData::new(String::from(path))
+    Ok(FileData::open(path)?)
 }
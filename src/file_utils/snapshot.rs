@@ -0,0 +1,312 @@
+use crate::file_utils::filter::FileFilter;
+use crate::file_utils::hash::{HashAlgorithm, HashCache};
+use crate::file_utils::walker::ParallelWalker;
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+/// 快照中单个文件的信息
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotEntry {
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+}
+
+/// 快照选项
+#[derive(Debug, Default)]
+pub struct SnapshotOptions {
+    filter: FileFilter,
+    algorithm: Option<HashAlgorithm>,
+    cache: Option<Arc<HashCache>>,
+}
+
+impl SnapshotOptions {
+    /// 默认选项：记录全部文件的大小和修改时间，不计算哈希
+    pub fn new() -> Self {
+        SnapshotOptions::default()
+    }
+
+    /// 只记录满足过滤条件的文件
+    pub fn filter(mut self, filter: FileFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// 同时记录文件内容哈希，比较时以哈希判断内容是否变化
+    pub fn hash(mut self, algorithm: HashAlgorithm) -> Self {
+        self.algorithm = Some(algorithm);
+        self
+    }
+
+    /// 使用哈希缓存
+    pub fn cache(mut self, cache: Arc<HashCache>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+}
+
+/// 目录快照，键为相对于根目录的路径
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub root: PathBuf,
+    pub created_at: SystemTime,
+    #[serde(default)]
+    pub algorithm: Option<HashAlgorithm>,
+    pub files: BTreeMap<PathBuf, SnapshotEntry>,
+}
+
+impl Snapshot {
+    /// 对目录拍摄快照
+    pub fn capture(dir: &Path, options: SnapshotOptions) -> io::Result<Self> {
+        let mut walker = ParallelWalker::new().filter(options.filter);
+        if let Some(algorithm) = options.algorithm {
+            walker = walker.hash(algorithm);
+        }
+        if let Some(cache) = options.cache {
+            walker = walker.cache(cache);
+        }
+        let (_, files) = walker.walk(dir)?;
+        let files = files
+            .into_iter()
+            .map(|f| {
+                let entry = SnapshotEntry {
+                    size: f.metadata.len(),
+                    modified: f.metadata.modified().ok(),
+                    hash: f.hash,
+                };
+                (f.rel_path, entry)
+            })
+            .collect();
+        Ok(Snapshot {
+            root: dir.to_path_buf(),
+            created_at: SystemTime::now(),
+            algorithm: options.algorithm,
+            files,
+        })
+    }
+
+    /// 从 JSON 文件读取快照
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let data = fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&data)?)
+    }
+
+    /// 将快照保存为 JSON 文件
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let data = serde_json::to_string_pretty(self)?;
+        fs::write(path, data)?;
+        info!(
+            "保存快照 {}，共 {} 个文件",
+            path.display(),
+            self.files.len()
+        );
+        Ok(())
+    }
+
+    /// 与较新的快照比较
+    pub fn diff(&self, newer: &Snapshot) -> SnapshotDiff {
+        diff(self, newer)
+    }
+}
+
+/// 两个快照之间的差异
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotDiff {
+    pub added: Vec<PathBuf>,
+    pub removed: Vec<PathBuf>,
+    pub modified: Vec<PathBuf>,
+    /// 移动的文件，(原路径, 新路径)
+    pub moved: Vec<(PathBuf, PathBuf)>,
+}
+
+impl SnapshotDiff {
+    /// 是否没有任何差异
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.modified.is_empty()
+            && self.moved.is_empty()
+    }
+}
+
+/// 比较两个快照
+///
+/// 两边都有哈希时按哈希判断内容变化，否则按大小和修改时间判断。
+/// 被删除和新增的文件内容相同时识别为移动。
+pub fn diff(older: &Snapshot, newer: &Snapshot) -> SnapshotDiff {
+    let mut result = SnapshotDiff::default();
+    let mut removed = Vec::new();
+    for (path, old) in &older.files {
+        match newer.files.get(path) {
+            Some(new) if is_changed(old, new) => result.modified.push(path.clone()),
+            Some(_) => {}
+            None => removed.push(path),
+        }
+    }
+
+    // 按内容特征索引删除的文件，用于识别移动
+    let mut removed_by_key: HashMap<String, Vec<&PathBuf>> = HashMap::new();
+    for path in &removed {
+        if let Some(key) = move_key(&older.files[*path]) {
+            removed_by_key.entry(key).or_default().push(path);
+        }
+    }
+    let mut moved_from = Vec::new();
+    for (path, new) in &newer.files {
+        if older.files.contains_key(path) {
+            continue;
+        }
+        let from = move_key(new)
+            .and_then(|key| removed_by_key.get_mut(&key))
+            .and_then(|paths| (!paths.is_empty()).then(|| paths.remove(0)));
+        match from {
+            Some(from) => {
+                moved_from.push(from);
+                result.moved.push((from.clone(), path.clone()));
+            }
+            None => result.added.push(path.clone()),
+        }
+    }
+    result.removed = removed
+        .into_iter()
+        .filter(|p| !moved_from.contains(p))
+        .cloned()
+        .collect();
+    result
+}
+
+fn is_changed(old: &SnapshotEntry, new: &SnapshotEntry) -> bool {
+    if old.size != new.size {
+        return true;
+    }
+    match (&old.hash, &new.hash) {
+        (Some(a), Some(b)) => a != b,
+        _ => old.modified != new.modified,
+    }
+}
+
+/// 识别移动使用的内容特征，空文件不参与识别
+fn move_key(entry: &SnapshotEntry) -> Option<String> {
+    if entry.size == 0 {
+        return None;
+    }
+    match &entry.hash {
+        Some(hash) => Some(format!("{}:{}", entry.size, hash)),
+        None => entry.modified.map(|m| format!("{}:{:?}", entry.size, m)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn entry(size: u64, secs: u64, hash: Option<&str>) -> SnapshotEntry {
+        SnapshotEntry {
+            size,
+            modified: Some(SystemTime::UNIX_EPOCH + Duration::from_secs(secs)),
+            hash: hash.map(String::from),
+        }
+    }
+
+    fn snapshot(files: Vec<(&str, SnapshotEntry)>) -> Snapshot {
+        Snapshot {
+            root: PathBuf::from("/data"),
+            created_at: SystemTime::UNIX_EPOCH,
+            algorithm: None,
+            files: files
+                .into_iter()
+                .map(|(path, entry)| (PathBuf::from(path), entry))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn diff_detects_added_removed_modified_and_moved() {
+        let older = snapshot(vec![
+            ("same.txt", entry(1, 1, None)),
+            ("touched.txt", entry(2, 1, None)),
+            ("resized.txt", entry(3, 1, None)),
+            ("old/name.txt", entry(4, 1, None)),
+            ("deleted.txt", entry(5, 1, None)),
+            ("empty.txt", entry(0, 1, None)),
+        ]);
+        let newer = snapshot(vec![
+            ("same.txt", entry(1, 1, None)),
+            ("touched.txt", entry(2, 2, None)),
+            ("resized.txt", entry(30, 1, None)),
+            ("new/name.txt", entry(4, 1, None)),
+            ("added.txt", entry(6, 1, None)),
+            ("empty2.txt", entry(0, 1, None)),
+        ]);
+        let diff = older.diff(&newer);
+        assert_eq!(
+            diff.modified,
+            vec![PathBuf::from("resized.txt"), PathBuf::from("touched.txt")]
+        );
+        assert_eq!(
+            diff.moved,
+            vec![(PathBuf::from("old/name.txt"), PathBuf::from("new/name.txt"))]
+        );
+        // 空文件不参与移动识别
+        assert_eq!(
+            diff.added,
+            vec![PathBuf::from("added.txt"), PathBuf::from("empty2.txt")]
+        );
+        assert_eq!(
+            diff.removed,
+            vec![PathBuf::from("deleted.txt"), PathBuf::from("empty.txt")]
+        );
+        assert!(!diff.is_empty());
+        assert!(older.diff(&older).is_empty());
+    }
+
+    #[test]
+    fn diff_prefers_hashes_over_timestamps() {
+        let older = snapshot(vec![
+            ("a.txt", entry(3, 1, Some("aaa"))),
+            ("b.txt", entry(3, 1, Some("bbb"))),
+        ]);
+        let newer = snapshot(vec![
+            ("a.txt", entry(3, 9, Some("aaa"))),
+            ("b.txt", entry(3, 1, Some("ccc"))),
+        ]);
+        let diff = diff(&older, &newer);
+        assert_eq!(diff.modified, vec![PathBuf::from("b.txt")]);
+        assert!(diff.added.is_empty() && diff.removed.is_empty());
+    }
+
+    #[test]
+    fn capture_save_and_load_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("root");
+        fs::create_dir_all(root.join("sub")).unwrap();
+        fs::write(root.join("a.txt"), "a").unwrap();
+        fs::write(root.join("sub/b.txt"), "bb").unwrap();
+
+        let options = SnapshotOptions::new().hash(HashAlgorithm::default());
+        let before = Snapshot::capture(&root, options).unwrap();
+        assert_eq!(before.files.len(), 2);
+        assert!(before.files.values().all(|e| e.hash.is_some()));
+        let saved = dir.path().join("snapshot.json");
+        before.save(&saved).unwrap();
+        let loaded = Snapshot::load(&saved).unwrap();
+        assert_eq!(loaded.files, before.files);
+
+        fs::rename(root.join("sub/b.txt"), root.join("c.txt")).unwrap();
+        let after = Snapshot::capture(&root, SnapshotOptions::new().hash(HashAlgorithm::default()))
+            .unwrap();
+        let diff = loaded.diff(&after);
+        assert_eq!(
+            diff.moved,
+            vec![(Path::new("sub").join("b.txt"), PathBuf::from("c.txt"))]
+        );
+    }
+}
@@ -0,0 +1,294 @@
+use crate::file_utils::filter::FileFilter;
+use crate::file_utils::hash::{self, HashAlgorithm, HashCache};
+use ignore::{WalkBuilder, WalkState};
+use log::error;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// 并行遍历得到的条目
+#[derive(Debug)]
+pub struct WalkEntry {
+    /// 条目路径
+    pub path: PathBuf,
+    /// 相对于遍历根目录的路径
+    pub rel_path: PathBuf,
+    /// 文件元数据（不跟随符号链接）
+    pub metadata: fs::Metadata,
+    /// 文件内容哈希，仅在启用哈希时对文件计算
+    pub hash: Option<String>,
+}
+
+/// 并行目录遍历器
+///
+/// 基于工作线程池遍历目录，可选在遍历的同时计算文件内容哈希，
+/// 并通过共享的 `HashCache` 跳过未修改文件的重复计算。
+#[derive(Debug, Default)]
+pub struct ParallelWalker {
+    threads: usize,
+    recursive: bool,
+    filter: FileFilter,
+    algorithm: Option<HashAlgorithm>,
+    cache: Option<Arc<HashCache>>,
+}
+
+impl ParallelWalker {
+    /// 创建递归遍历器，线程数默认按 CPU 核数自动选择
+    pub fn new() -> Self {
+        ParallelWalker {
+            recursive: true,
+            ..Default::default()
+        }
+    }
+
+    /// 设置工作线程数，0 表示自动选择
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads;
+        self
+    }
+
+    /// 是否递归遍历子目录
+    pub fn recursive(mut self, recursive: bool) -> Self {
+        self.recursive = recursive;
+        self
+    }
+
+    /// 设置文件过滤器
+    pub fn filter(mut self, filter: FileFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// 遍历时计算文件内容哈希
+    pub fn hash(mut self, algorithm: HashAlgorithm) -> Self {
+        self.algorithm = Some(algorithm);
+        self
+    }
+
+    /// 使用哈希缓存，缓存可在多个遍历器之间共享
+    pub fn cache(mut self, cache: Arc<HashCache>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// 并行遍历给定目录，返回 (目录列表, 文件列表)，结果按路径排序
+    pub fn walk(&self, dir: impl AsRef<Path>) -> io::Result<(Vec<WalkEntry>, Vec<WalkEntry>)> {
+        let root = dir.as_ref();
+        if !root.is_dir() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{} is not a directory", root.display()),
+            ));
+        }
+
+        let mut builder = WalkBuilder::new(root);
+        builder
+            .standard_filters(false)
+            .threads(self.threads)
+            .max_depth(if self.recursive { None } else { Some(1) });
+        for name in self.filter.ignore_files() {
+            builder.add_custom_ignore_filename(name);
+        }
+
+        let dirs = Mutex::new(Vec::new());
+        let files = Mutex::new(Vec::new());
+        builder.build_parallel().run(|| {
+            Box::new(|result| {
+                let entry = match result {
+                    Ok(entry) => entry,
+                    Err(e) => {
+                        error!("Failed to read entry: {}", e);
+                        return WalkState::Continue;
+                    }
+                };
+                // 根目录本身不作为结果返回
+                if entry.depth() == 0 {
+                    return WalkState::Continue;
+                }
+                let metadata = match entry.metadata() {
+                    Ok(m) => m,
+                    Err(e) => {
+                        error!(
+                            "Failed to get metadata of {}: {}",
+                            entry.path().display(),
+                            e
+                        );
+                        return WalkState::Continue;
+                    }
+                };
+                let path = entry.into_path();
+                let rel_path = path.strip_prefix(root).unwrap_or(&path).to_path_buf();
+                if metadata.is_dir() {
+                    if !self.filter.allow_dir(&rel_path) {
+                        return WalkState::Skip;
+                    }
+                    dirs.lock().unwrap().push(WalkEntry {
+                        path,
+                        rel_path,
+                        metadata,
+                        hash: None,
+                    });
+                } else if self.filter.allow_file(&rel_path, &metadata) {
+                    let hash = self.hash_entry(&path, &metadata);
+                    files.lock().unwrap().push(WalkEntry {
+                        path,
+                        rel_path,
+                        metadata,
+                        hash,
+                    });
+                }
+                WalkState::Continue
+            })
+        });
+
+        let mut dirs = dirs.into_inner().unwrap();
+        let mut files = files.into_inner().unwrap();
+        dirs.sort_by(|a, b| a.path.cmp(&b.path));
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok((dirs, files))
+    }
+
+    fn hash_entry(&self, path: &Path, metadata: &fs::Metadata) -> Option<String> {
+        let algorithm = self.algorithm?;
+        if !metadata.is_file() {
+            return None;
+        }
+        let result = match &self.cache {
+            Some(cache) => cache.get_or_hash(path, metadata, algorithm),
+            None => hash::hash_file(path, algorithm),
+        };
+        match result {
+            Ok(hash) => Some(hash),
+            Err(e) => {
+                error!("Failed to hash file {}: {}", path.display(), e);
+                None
+            }
+        }
+    }
+}
+
+/// 在遍历结果中查找内容重复的文件，需要遍历时启用哈希
+///
+/// 空文件内容都相同，不作为重复文件返回。
+pub fn find_duplicate_files(files: &[WalkEntry]) -> Vec<Vec<PathBuf>> {
+    hash::find_duplicates(
+        files
+            .iter()
+            .filter(|f| f.metadata.len() > 0)
+            .filter_map(|f| f.hash.as_deref().map(|h| (f.path.as_path(), h))),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_utils::traverse_dir_files_with_filter;
+
+    fn write(path: &Path, contents: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    fn filter() -> FileFilter {
+        FileFilter::new().gitignore().exclude("*.log").unwrap()
+    }
+
+    #[test]
+    fn matches_serial_traversal() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        write(&root.join("a.txt"), "a");
+        write(&root.join("build.log"), "log");
+        write(&root.join("src/main.rs"), "fn main() {}");
+        write(&root.join("src/nested/lib.rs"), "");
+        write(&root.join("out/bin"), "binary");
+        write(&root.join(".gitignore"), "out/\n");
+
+        let (walk_dirs, walk_files) = ParallelWalker::new()
+            .threads(4)
+            .filter(filter())
+            .walk(root)
+            .unwrap();
+        let (dirs, files) = traverse_dir_files_with_filter(root, true, &filter()).unwrap();
+        let mut dirs: Vec<_> = dirs.iter().map(|d| d.path().to_path_buf()).collect();
+        let mut files: Vec<_> = files.iter().map(|f| f.path().to_path_buf()).collect();
+        dirs.sort();
+        files.sort();
+        assert_eq!(
+            walk_dirs.iter().map(|d| d.path.clone()).collect::<Vec<_>>(),
+            dirs
+        );
+        assert_eq!(
+            walk_files
+                .iter()
+                .map(|f| f.path.clone())
+                .collect::<Vec<_>>(),
+            files
+        );
+        assert!(!files.contains(&root.join("build.log")));
+        assert!(!files.contains(&root.join("out/bin")));
+        assert!(walk_files.iter().all(|f| f.hash.is_none()));
+
+        let (_, shallow) = ParallelWalker::new().recursive(false).walk(root).unwrap();
+        let names: Vec<_> = shallow.iter().map(|f| f.rel_path.clone()).collect();
+        assert_eq!(
+            names,
+            [
+                PathBuf::from(".gitignore"),
+                "a.txt".into(),
+                "build.log".into()
+            ]
+        );
+    }
+
+    #[test]
+    fn hashes_files_through_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        write(&root.join("a.txt"), "a");
+        write(&root.join("sub/b.txt"), "b");
+
+        let cache = Arc::new(HashCache::new());
+        let walker = ParallelWalker::new()
+            .hash(HashAlgorithm::Blake3)
+            .cache(cache.clone());
+        let (_, files) = walker.walk(root).unwrap();
+        for file in &files {
+            assert_eq!(
+                file.hash.as_deref(),
+                Some(
+                    hash::hash_file(&file.path, HashAlgorithm::Blake3)
+                        .unwrap()
+                        .as_str()
+                )
+            );
+        }
+        assert_eq!(cache.len(), 2);
+        walker.walk(root).unwrap();
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn duplicates_exclude_empty_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        write(&root.join("a.txt"), "same");
+        write(&root.join("sub/b.txt"), "same");
+        write(&root.join("c.txt"), "other");
+        write(&root.join("empty1"), "");
+        write(&root.join("sub/empty2"), "");
+
+        let (_, files) = ParallelWalker::new()
+            .hash(HashAlgorithm::Sha256)
+            .walk(root)
+            .unwrap();
+        assert_eq!(
+            find_duplicate_files(&files),
+            [vec![root.join("a.txt"), root.join("sub/b.txt")]]
+        );
+        // 未启用哈希时没有可比较的内容
+        let (_, files) = ParallelWalker::new().walk(root).unwrap();
+        assert!(find_duplicate_files(&files).is_empty());
+    }
+}
@@ -0,0 +1,478 @@
+use crate::file_utils::filter::FileFilter;
+use log::{error, info, warn};
+use notify::event::{ModifyKind, RenameMode};
+use notify::{Event, EventKind, PollWatcher, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::task::{Context, Poll};
+use std::thread;
+use std::time::Duration;
+use tokio::sync::mpsc as tokio_mpsc;
+use tokio_stream::Stream;
+
+/// 一个去抖窗口内合并后的文件变化
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChangeBatch {
+    pub created: Vec<PathBuf>,
+    pub modified: Vec<PathBuf>,
+    pub removed: Vec<PathBuf>,
+    /// 重命名，(原路径, 新路径)
+    pub renamed: Vec<(PathBuf, PathBuf)>,
+}
+
+impl ChangeBatch {
+    /// 是否没有任何变化
+    pub fn is_empty(&self) -> bool {
+        self.created.is_empty()
+            && self.modified.is_empty()
+            && self.removed.is_empty()
+            && self.renamed.is_empty()
+    }
+}
+
+/// 监听选项
+#[derive(Debug)]
+pub struct WatchOptions {
+    recursive: bool,
+    debounce: Duration,
+    filter: FileFilter,
+    poll: bool,
+    poll_interval: Duration,
+}
+
+impl Default for WatchOptions {
+    fn default() -> Self {
+        WatchOptions {
+            recursive: true,
+            debounce: Duration::from_millis(500),
+            filter: FileFilter::default(),
+            poll: false,
+            poll_interval: Duration::from_secs(2),
+        }
+    }
+}
+
+impl WatchOptions {
+    /// 默认选项：递归监听，去抖 500 毫秒，优先使用 inotify
+    pub fn new() -> Self {
+        WatchOptions::default()
+    }
+
+    /// 是否递归监听子目录
+    pub fn recursive(mut self, recursive: bool) -> Self {
+        self.recursive = recursive;
+        self
+    }
+
+    /// 去抖时间，最后一次变化后静默该时长才输出一批变化
+    pub fn debounce(mut self, debounce: Duration) -> Self {
+        self.debounce = debounce;
+        self
+    }
+
+    /// 过滤条件，被排除的路径及其子路径的变化会被忽略
+    pub fn filter(mut self, filter: FileFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// 强制使用轮询方式，适用于 NFS 等不支持 inotify 的文件系统
+    pub fn poll(mut self, poll: bool) -> Self {
+        self.poll = poll;
+        self
+    }
+
+    /// 轮询间隔
+    pub fn poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+}
+
+/// 文件系统监听器
+///
+/// 优先使用 inotify，创建失败（例如超出 `max_user_watches`）时退回轮询。
+/// 监听器被销毁时停止监听。
+pub struct FileWatcher {
+    _watcher: Box<dyn Watcher + Send>,
+}
+
+impl FileWatcher {
+    /// 监听目录，每个去抖窗口结束时以合并后的变化调用回调
+    pub fn watch<F>(path: &Path, options: WatchOptions, mut callback: F) -> io::Result<Self>
+    where
+        F: FnMut(ChangeBatch) + Send + 'static,
+    {
+        let root = path.canonicalize()?;
+        let (tx, rx) = mpsc::channel::<Event>();
+        let watcher = create_watcher(&root, &options, tx)?;
+        let debounce = options.debounce;
+        let filter = options.filter;
+        thread::spawn(move || {
+            let mut coalescer = Coalescer::default();
+            loop {
+                // 空闲时阻塞等待，收到变化后等待静默期结束
+                let result = if coalescer.is_empty() {
+                    rx.recv().map_err(|_| RecvTimeoutError::Disconnected)
+                } else {
+                    rx.recv_timeout(debounce)
+                };
+                match result {
+                    Ok(event) => coalescer.push(&root, &filter, event),
+                    Err(RecvTimeoutError::Timeout) => {
+                        let batch = coalescer.flush();
+                        if !batch.is_empty() {
+                            callback(batch);
+                        }
+                    }
+                    Err(RecvTimeoutError::Disconnected) => {
+                        let batch = coalescer.flush();
+                        if !batch.is_empty() {
+                            callback(batch);
+                        }
+                        break;
+                    }
+                }
+            }
+        });
+        info!("开始监听 {}", path.display());
+        Ok(FileWatcher { _watcher: watcher })
+    }
+
+    /// 监听目录，以 tokio 异步流的形式返回变化，流被销毁时停止监听
+    pub fn stream(path: &Path, options: WatchOptions) -> io::Result<ChangeStream> {
+        let (tx, rx) = tokio_mpsc::unbounded_channel();
+        let watcher = FileWatcher::watch(path, options, move |batch| {
+            let _ = tx.send(batch);
+        })?;
+        Ok(ChangeStream {
+            _watcher: watcher,
+            rx,
+        })
+    }
+}
+
+/// 文件变化的异步流
+pub struct ChangeStream {
+    _watcher: FileWatcher,
+    rx: tokio_mpsc::UnboundedReceiver<ChangeBatch>,
+}
+
+impl Stream for ChangeStream {
+    type Item = ChangeBatch;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.rx.poll_recv(cx)
+    }
+}
+
+fn create_watcher(
+    root: &Path,
+    options: &WatchOptions,
+    tx: mpsc::Sender<Event>,
+) -> io::Result<Box<dyn Watcher + Send>> {
+    let mode = if options.recursive {
+        RecursiveMode::Recursive
+    } else {
+        RecursiveMode::NonRecursive
+    };
+    let handler = move |result: notify::Result<Event>| match result {
+        Ok(event) => {
+            let _ = tx.send(event);
+        }
+        Err(e) => error!("Watch error: {}", e),
+    };
+
+    if !options.poll {
+        let native = RecommendedWatcher::new(handler.clone(), notify::Config::default())
+            .and_then(|mut w| w.watch(root, mode).map(|_| w));
+        match native {
+            Ok(watcher) => return Ok(Box::new(watcher)),
+            Err(e) => warn!(
+                "Failed to watch {} natively, fall back to polling: {}",
+                root.display(),
+                e
+            ),
+        }
+    }
+
+    let config = notify::Config::default().with_poll_interval(options.poll_interval);
+    let mut watcher = PollWatcher::new(handler, config).map_err(notify_error)?;
+    watcher.watch(root, mode).map_err(notify_error)?;
+    Ok(Box::new(watcher))
+}
+
+fn notify_error(e: notify::Error) -> io::Error {
+    match e.kind {
+        notify::ErrorKind::Io(e) => e,
+        _ => io::Error::other(e.to_string()),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Created,
+    Modified,
+    Removed,
+}
+
+/// 合并一个去抖窗口内的原始事件
+#[derive(Default)]
+struct Coalescer {
+    states: BTreeMap<PathBuf, State>,
+    renamed: Vec<(PathBuf, PathBuf)>,
+    pending_from: HashMap<usize, PathBuf>,
+    paired: HashSet<usize>,
+}
+
+impl Coalescer {
+    fn is_empty(&self) -> bool {
+        self.states.is_empty() && self.renamed.is_empty() && self.pending_from.is_empty()
+    }
+
+    fn push(&mut self, root: &Path, filter: &FileFilter, event: Event) {
+        let paths: Vec<PathBuf> = event
+            .paths
+            .iter()
+            .filter(|p| !is_filtered(root, filter, p))
+            .cloned()
+            .collect();
+        if paths.is_empty() {
+            return;
+        }
+        let tracker = event.attrs.tracker();
+        match event.kind {
+            EventKind::Create(_) => paths.into_iter().for_each(|p| self.create(p)),
+            EventKind::Remove(_) => paths.into_iter().for_each(|p| self.remove(p)),
+            EventKind::Modify(ModifyKind::Name(mode)) => self.rename(mode, tracker, paths),
+            EventKind::Modify(_) | EventKind::Any | EventKind::Other => {
+                paths.into_iter().for_each(|p| self.modify(p))
+            }
+            EventKind::Access(_) => {}
+        }
+    }
+
+    fn create(&mut self, path: PathBuf) {
+        let state = match self.states.get(&path) {
+            Some(State::Removed) | Some(State::Modified) => State::Modified,
+            _ => State::Created,
+        };
+        self.states.insert(path, state);
+    }
+
+    fn modify(&mut self, path: PathBuf) {
+        if self.states.get(&path) != Some(&State::Created) {
+            self.states.insert(path, State::Modified);
+        }
+    }
+
+    fn remove(&mut self, path: PathBuf) {
+        // 窗口内创建又删除的文件视为没有变化
+        if self.states.get(&path) == Some(&State::Created) {
+            self.states.remove(&path);
+        } else {
+            self.states.insert(path, State::Removed);
+        }
+    }
+
+    fn rename(&mut self, mode: RenameMode, tracker: Option<usize>, mut paths: Vec<PathBuf>) {
+        match mode {
+            RenameMode::Both if paths.len() == 2 => {
+                if tracker.is_some_and(|t| self.paired.contains(&t)) {
+                    return;
+                }
+                let to = paths.pop().unwrap();
+                let from = paths.pop().unwrap();
+                self.rename_pair(from, to);
+            }
+            RenameMode::From => match tracker {
+                Some(t) => {
+                    if let Some(path) = paths.pop() {
+                        self.pending_from.insert(t, path);
+                    }
+                }
+                None => paths.into_iter().for_each(|p| self.remove(p)),
+            },
+            RenameMode::To => {
+                let from = tracker.and_then(|t| self.pending_from.remove(&t).map(|p| (t, p)));
+                match (from, paths.pop()) {
+                    (Some((t, from)), Some(to)) => {
+                        self.paired.insert(t);
+                        self.rename_pair(from, to);
+                    }
+                    (None, Some(to)) => self.create(to),
+                    _ => {}
+                }
+            }
+            _ => {
+                // 无法区分新旧路径时按路径是否存在判断
+                for path in paths {
+                    if path.exists() {
+                        self.create(path);
+                    } else {
+                        self.remove(path);
+                    }
+                }
+            }
+        }
+    }
+
+    fn rename_pair(&mut self, from: PathBuf, to: PathBuf) {
+        // 窗口内新建的文件被重命名，等同于直接创建新路径
+        if self.states.get(&from) == Some(&State::Created) {
+            self.states.remove(&from);
+            self.create(to);
+        } else {
+            self.renamed.push((from, to));
+        }
+    }
+
+    fn flush(&mut self) -> ChangeBatch {
+        let mut batch = ChangeBatch::default();
+        for (_, path) in self.pending_from.drain() {
+            self.states.insert(path, State::Removed);
+        }
+        for (path, state) in std::mem::take(&mut self.states) {
+            match state {
+                State::Created => batch.created.push(path),
+                State::Modified => batch.modified.push(path),
+                State::Removed => batch.removed.push(path),
+            }
+        }
+        batch.renamed = std::mem::take(&mut self.renamed);
+        self.paired.clear();
+        batch
+    }
+}
+
+/// 路径是根目录本身，或路径及其任一上级目录被过滤条件排除时返回 `true`
+fn is_filtered(root: &Path, filter: &FileFilter, path: &Path) -> bool {
+    let rel_path = match path.strip_prefix(root) {
+        Ok(p) => p,
+        Err(_) => return false,
+    };
+    // 根目录自身的属性变化没有意义
+    if rel_path.as_os_str().is_empty() {
+        return true;
+    }
+    if rel_path
+        .ancestors()
+        .skip(1)
+        .filter(|p| !p.as_os_str().is_empty())
+        .any(|p| !filter.allow_dir(p))
+    {
+        return true;
+    }
+    if path.is_dir() {
+        !filter.allow_dir(rel_path)
+    } else {
+        !filter.allow_path(rel_path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use notify::event::{CreateKind, DataChange, RemoveKind};
+
+    const ROOT: &str = "/watched";
+
+    fn event(kind: EventKind, paths: &[&str]) -> Event {
+        paths
+            .iter()
+            .fold(Event::new(kind), |e, p| e.add_path(Path::new(ROOT).join(p)))
+    }
+
+    fn path(p: &str) -> PathBuf {
+        Path::new(ROOT).join(p)
+    }
+
+    fn coalesce(filter: &FileFilter, events: Vec<Event>) -> ChangeBatch {
+        let mut coalescer = Coalescer::default();
+        for event in events {
+            coalescer.push(Path::new(ROOT), filter, event);
+        }
+        let batch = coalescer.flush();
+        assert!(coalescer.is_empty());
+        batch
+    }
+
+    #[test]
+    fn coalescer_merges_events_in_one_window() {
+        let create = EventKind::Create(CreateKind::File);
+        let remove = EventKind::Remove(RemoveKind::File);
+        let write = EventKind::Modify(ModifyKind::Data(DataChange::Content));
+        let batch = coalesce(
+            &FileFilter::new(),
+            vec![
+                // 创建后修改仍是创建
+                event(create, &["new.txt"]),
+                event(write, &["new.txt"]),
+                // 创建后删除视为没有变化
+                event(create, &["tmp.txt"]),
+                event(remove, &["tmp.txt"]),
+                // 删除后重新创建视为修改
+                event(remove, &["saved.txt"]),
+                event(create, &["saved.txt"]),
+                event(remove, &["gone.txt"]),
+                event(write, &["a.txt"]),
+                event(write, &["a.txt"]),
+                // 根目录自身的事件被忽略
+                Event::new(write).add_path(PathBuf::from(ROOT)),
+            ],
+        );
+        assert_eq!(batch.created, vec![path("new.txt")]);
+        assert_eq!(batch.modified, vec![path("a.txt"), path("saved.txt")]);
+        assert_eq!(batch.removed, vec![path("gone.txt")]);
+        assert!(batch.renamed.is_empty());
+    }
+
+    #[test]
+    fn coalescer_pairs_renames_by_tracker() {
+        let from = EventKind::Modify(ModifyKind::Name(RenameMode::From));
+        let to = EventKind::Modify(ModifyKind::Name(RenameMode::To));
+        let both = EventKind::Modify(ModifyKind::Name(RenameMode::Both));
+        let batch = coalesce(
+            &FileFilter::new(),
+            vec![
+                event(from, &["old.txt"]).set_tracker(1),
+                event(to, &["new.txt"]).set_tracker(1),
+                // inotify 同时发送 Both，已配对的不重复记录
+                event(both, &["old.txt", "new.txt"]).set_tracker(1),
+                // 只有 From 没有 To，视为移出监听目录
+                event(from, &["moved-out.txt"]).set_tracker(2),
+                // 只有 To 没有 From，视为移入监听目录
+                event(to, &["moved-in.txt"]).set_tracker(3),
+                event(both, &["x.txt", "y.txt"]),
+            ],
+        );
+        assert_eq!(
+            batch.renamed,
+            vec![
+                (path("old.txt"), path("new.txt")),
+                (path("x.txt"), path("y.txt"))
+            ]
+        );
+        assert_eq!(batch.created, vec![path("moved-in.txt")]);
+        assert_eq!(batch.removed, vec![path("moved-out.txt")]);
+    }
+
+    #[test]
+    fn coalescer_skips_filtered_paths() {
+        let filter = FileFilter::new().exclude("target").unwrap().extension("rs");
+        let write = EventKind::Modify(ModifyKind::Data(DataChange::Content));
+        let batch = coalesce(
+            &filter,
+            vec![
+                event(write, &["src/main.rs"]),
+                event(write, &["target/debug/build.rs"]),
+                event(write, &["notes.txt"]),
+            ],
+        );
+        assert_eq!(batch.modified, vec![path("src/main.rs")]);
+        assert!(coalesce(&filter, vec![event(write, &["notes.txt"])]).is_empty());
+    }
+}
@@ -0,0 +1,195 @@
+use crate::command_utils;
+use log::info;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::process::Command;
+use tempfile::{NamedTempFile, TempDir};
+
+/// 创建临时目录，返回值被销毁时目录及其内容一并删除
+pub fn temp_dir(prefix: &str) -> io::Result<TempDir> {
+    tempfile::Builder::new().prefix(prefix).tempdir()
+}
+
+/// 在指定目录下创建临时目录，返回值被销毁时自动删除
+pub fn temp_dir_in(dir: &Path, prefix: &str) -> io::Result<TempDir> {
+    fs::create_dir_all(dir)?;
+    tempfile::Builder::new().prefix(prefix).tempdir_in(dir)
+}
+
+/// 创建临时文件，返回值被销毁时自动删除
+pub fn temp_file(prefix: &str, suffix: &str) -> io::Result<NamedTempFile> {
+    tempfile::Builder::new()
+        .prefix(prefix)
+        .suffix(suffix)
+        .tempfile()
+}
+
+/// 限定在某个根目录内的工作目录
+///
+/// 代替 `set_current_dir`，所有路径都基于根目录生成，执行命令时只设置子进程的工作目录，
+/// 不影响当前进程的全局状态。
+#[derive(Debug, Clone)]
+pub struct ScopedDir {
+    root: PathBuf,
+}
+
+impl ScopedDir {
+    /// 以给定目录为根，目录不存在时创建
+    pub fn new(root: impl AsRef<Path>) -> io::Result<Self> {
+        let root = root.as_ref();
+        fs::create_dir_all(root)?;
+        Ok(ScopedDir {
+            root: fs::canonicalize(root)?,
+        })
+    }
+
+    /// 根目录的绝对路径
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// 根目录下的路径，拒绝绝对路径和跳出根目录的 `..`
+    pub fn join(&self, rel_path: impl AsRef<Path>) -> io::Result<PathBuf> {
+        let rel_path = rel_path.as_ref();
+        let mut depth = 0usize;
+        for component in rel_path.components() {
+            match component {
+                Component::Normal(_) => depth += 1,
+                Component::CurDir => {}
+                Component::ParentDir if depth > 0 => depth -= 1,
+                _ => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("{} escapes {}", rel_path.display(), self.root.display()),
+                    ))
+                }
+            }
+        }
+        Ok(self.root.join(rel_path))
+    }
+
+    /// 根目录下的子目录，不存在时创建
+    pub fn create_dir(&self, rel_path: impl AsRef<Path>) -> io::Result<PathBuf> {
+        let path = self.join(rel_path)?;
+        fs::create_dir_all(&path)?;
+        Ok(path)
+    }
+
+    /// 在根目录下写入文件，父目录不存在时创建
+    pub fn write(
+        &self,
+        rel_path: impl AsRef<Path>,
+        contents: impl AsRef<[u8]>,
+    ) -> io::Result<PathBuf> {
+        let path = self.join(rel_path)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, contents)?;
+        Ok(path)
+    }
+
+    /// 以根目录为工作目录的命令
+    pub fn command(&self, program: &str) -> Command {
+        let mut command = Command::new(program);
+        command.current_dir(&self.root);
+        command
+    }
+
+    /// 在根目录下执行命令并返回标准输出
+    pub fn run_command(&self, name: &str, args: &[&str]) -> io::Result<String> {
+        command_utils::run_command_in(&self.root, name, args)
+    }
+}
+
+/// 项目工作区，对应 `projects/<项目名>` 下的代码检出目录
+#[derive(Debug, Clone)]
+pub struct Workspace {
+    name: String,
+    dir: ScopedDir,
+}
+
+impl Workspace {
+    /// 打开 `<base>/<name>` 工作区，目录不存在时创建
+    pub fn open(base: impl AsRef<Path>, name: &str) -> io::Result<Self> {
+        let mut components = Path::new(name).components();
+        let single = matches!(
+            (components.next(), components.next()),
+            (Some(Component::Normal(_)), None)
+        );
+        if !single {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid workspace name: {}", name),
+            ));
+        }
+        let dir = ScopedDir::new(base.as_ref().join(name))?;
+        Ok(Workspace {
+            name: name.to_string(),
+            dir,
+        })
+    }
+
+    /// 在当前目录的 `projects` 下打开工作区
+    pub fn open_in_projects(name: &str) -> io::Result<Self> {
+        Workspace::open("projects", name)
+    }
+
+    /// 工作区名称
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// 工作区根目录
+    pub fn path(&self) -> &Path {
+        self.dir.root()
+    }
+
+    /// 限定在工作区内的目录操作
+    pub fn dir(&self) -> &ScopedDir {
+        &self.dir
+    }
+
+    /// 工作区中是否已有 git 检出
+    pub fn is_checkout(&self) -> bool {
+        self.dir.root().join(".git").exists()
+    }
+
+    /// 清空工作区内容，保留工作区目录
+    pub fn clean(&self) -> io::Result<()> {
+        info!("清空工作区 {}", self.path().display());
+        for entry in fs::read_dir(self.path())? {
+            let path = entry?.path();
+            if path.is_dir() && !path.is_symlink() {
+                fs::remove_dir_all(&path)?;
+            } else {
+                fs::remove_file(&path)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// 删除整个工作区
+    pub fn remove(self) -> io::Result<()> {
+        info!("删除工作区 {}", self.path().display());
+        fs::remove_dir_all(self.path())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn open_rejects_names_outside_base() {
+        let base = tempfile::tempdir().unwrap();
+        for name in ["", ".", "..", "a/b", "../a", "/abs"] {
+            let err = Workspace::open(base.path(), name).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput, "{:?}", name);
+        }
+        let workspace = Workspace::open(base.path(), "app").unwrap();
+        assert!(base.path().join("app").is_dir());
+        assert_eq!(workspace.name(), "app");
+    }
+}
@@ -0,0 +1,333 @@
+use crate::git_utils::repo::{CommitInfo, GitRepo, Tag};
+use std::fmt;
+use std::io;
+
+/// 按 Conventional Commits 规范解析后的提交
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsedCommit {
+    pub commit: CommitInfo,
+    /// 提交类型，例如 `feat`、`fix`，不符合规范的提交为 `None`
+    pub kind: Option<String>,
+    pub scope: Option<String>,
+    /// 类型后带 `!` 或正文中有 `BREAKING CHANGE:` 脚注
+    pub breaking: bool,
+    /// 去掉类型和范围后的描述，不符合规范时为第一行
+    pub description: String,
+}
+
+impl ParsedCommit {
+    /// 解析提交信息
+    pub fn parse(commit: CommitInfo) -> Self {
+        let summary = commit.summary().trim().to_string();
+        let breaking_footer = commit.message.lines().skip(1).any(|line| {
+            line.starts_with("BREAKING CHANGE:") || line.starts_with("BREAKING-CHANGE:")
+        });
+        let (kind, scope, bang, description) = match parse_header(&summary) {
+            Some((kind, scope, bang, description)) => {
+                (Some(kind), scope, bang, description.to_string())
+            }
+            None => (None, None, false, summary.clone()),
+        };
+        ParsedCommit {
+            commit,
+            kind,
+            scope,
+            breaking: bang || breaking_footer,
+            description,
+        }
+    }
+
+    /// 变更日志中的分组
+    fn section(&self) -> &'static str {
+        if self.breaking {
+            return SECTIONS[0];
+        }
+        match self.kind.as_deref() {
+            Some("feat") => SECTIONS[1],
+            Some("fix") => SECTIONS[2],
+            _ => SECTIONS[3],
+        }
+    }
+}
+
+/// 变更日志的分组标题
+const SECTIONS: [&str; 4] = ["Breaking Changes", "Features", "Bug Fixes", "Other Changes"];
+
+/// 解析 `type(scope)!: description`
+fn parse_header(summary: &str) -> Option<(String, Option<String>, bool, &str)> {
+    let (head, description) = summary.split_once(": ")?;
+    let (head, bang) = match head.strip_suffix('!') {
+        Some(head) => (head, true),
+        None => (head, false),
+    };
+    let (kind, scope) = match head.split_once('(') {
+        Some((kind, rest)) => (kind, Some(rest.strip_suffix(')')?.to_string())),
+        None => (head, None),
+    };
+    if kind.is_empty() || !kind.chars().all(|c| c.is_ascii_alphabetic()) {
+        return None;
+    }
+    let description = description.trim();
+    if description.is_empty() {
+        return None;
+    }
+    Some((kind.to_ascii_lowercase(), scope, bang, description))
+}
+
+/// `to` 可达而 `from` 不可达的提交，从新到旧排列，并解析提交信息
+pub fn log(repo: &GitRepo, from: Option<&str>, to: &str) -> io::Result<Vec<ParsedCommit>> {
+    Ok(repo
+        .commits_between(from, to)?
+        .into_iter()
+        .map(ParsedCommit::parse)
+        .collect())
+}
+
+/// 语义化版本号，不含预发布和构建元数据
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Version {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+}
+
+impl Version {
+    pub fn new(major: u64, minor: u64, patch: u64) -> Self {
+        Version {
+            major,
+            minor,
+            patch,
+        }
+    }
+
+    /// 解析 `1.2.3` 或 `v1.2.3`，带预发布后缀的版本返回 `None`
+    pub fn parse(s: &str) -> Option<Self> {
+        let s = s.strip_prefix('v').unwrap_or(s);
+        let mut parts = s.split('.');
+        let version = Version::new(
+            parts.next()?.parse().ok()?,
+            parts.next()?.parse().ok()?,
+            parts.next()?.parse().ok()?,
+        );
+        match parts.next() {
+            Some(_) => None,
+            None => Some(version),
+        }
+    }
+
+    /// 按升级级别递增版本号
+    pub fn bump(&self, bump: Bump) -> Self {
+        match bump {
+            Bump::Major => Version::new(self.major + 1, 0, 0),
+            Bump::Minor => Version::new(self.major, self.minor + 1, 0),
+            Bump::Patch => Version::new(self.major, self.minor, self.patch + 1),
+        }
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// 版本升级级别
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Bump {
+    Patch,
+    Minor,
+    Major,
+}
+
+/// 根据提交计算升级级别：不兼容变更升级主版本，新功能升级次版本，其他升级修订号
+///
+/// 没有任何提交时返回 `None`。
+pub fn bump_for(commits: &[ParsedCommit]) -> Option<Bump> {
+    commits
+        .iter()
+        .map(|c| {
+            if c.breaking {
+                Bump::Major
+            } else if c.kind.as_deref() == Some("feat") {
+                Bump::Minor
+            } else {
+                Bump::Patch
+            }
+        })
+        .max()
+}
+
+/// 在 `to` 的历史中版本号最大的标签
+pub fn latest_version_tag(repo: &GitRepo, to: &str) -> io::Result<Option<(Version, Tag)>> {
+    let mut latest: Option<(Version, Tag)> = None;
+    for tag in repo.tags()? {
+        let version = match Version::parse(&tag.name) {
+            Some(version) => version,
+            None => continue,
+        };
+        if latest.as_ref().is_some_and(|(v, _)| *v >= version) {
+            continue;
+        }
+        if repo.is_ancestor(&tag.commit, to)? {
+            latest = Some((version, tag));
+        }
+    }
+    Ok(latest)
+}
+
+/// 发布说明
+#[derive(Debug, Clone)]
+pub struct ReleaseNotes {
+    /// 上一个版本标签
+    pub previous: Option<Tag>,
+    /// 下一个版本号，自上个版本以来没有提交时为 `None`
+    pub next_version: Option<Version>,
+    pub commits: Vec<ParsedCommit>,
+}
+
+impl ReleaseNotes {
+    /// 生成 `to` 相对上一个版本标签的发布说明，没有版本标签时从 `0.0.0` 开始计算
+    pub fn generate(repo: &GitRepo, to: &str) -> io::Result<Self> {
+        let latest = latest_version_tag(repo, to)?;
+        let from = latest.as_ref().map(|(_, tag)| tag.commit.as_str());
+        let commits = log(repo, from, to)?;
+        let current = latest.as_ref().map(|(v, _)| *v).unwrap_or_default();
+        Ok(ReleaseNotes {
+            next_version: bump_for(&commits).map(|bump| current.bump(bump)),
+            previous: latest.map(|(_, tag)| tag),
+            commits,
+        })
+    }
+
+    /// 渲染为 Markdown，按不兼容变更、新功能、问题修复和其他变更分组
+    pub fn to_markdown(&self) -> String {
+        let title = match &self.next_version {
+            Some(version) => format!("v{}", version),
+            None => "Unreleased".to_string(),
+        };
+        let mut markdown = format!("## {}\n", title);
+        if self.commits.is_empty() {
+            markdown.push_str("\nNo changes.\n");
+            return markdown;
+        }
+
+        for title in SECTIONS {
+            let commits: Vec<&ParsedCommit> = self
+                .commits
+                .iter()
+                .filter(|c| c.section() == title)
+                .collect();
+            if commits.is_empty() {
+                continue;
+            }
+            markdown.push_str(&format!("\n### {}\n\n", title));
+            for commit in commits {
+                let scope = match &commit.scope {
+                    Some(scope) => format!("**{}:** ", scope),
+                    None => String::new(),
+                };
+                markdown.push_str(&format!(
+                    "- {}{} ({})\n",
+                    scope, commit.description, commit.commit.short_id
+                ));
+            }
+        }
+        markdown
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commit(message: &str) -> ParsedCommit {
+        ParsedCommit::parse(CommitInfo {
+            id: "0123456789abcdef".to_string(),
+            short_id: "0123456".to_string(),
+            author: "test".to_string(),
+            email: "test@example.com".to_string(),
+            time: 0,
+            message: message.to_string(),
+        })
+    }
+
+    #[test]
+    fn parse_header_follows_conventional_commits() {
+        assert_eq!(
+            parse_header("feat(api)!: add endpoint"),
+            Some((
+                "feat".to_string(),
+                Some("api".to_string()),
+                true,
+                "add endpoint"
+            ))
+        );
+        assert_eq!(
+            parse_header("Fix:  trim  "),
+            Some(("fix".to_string(), None, false, "trim"))
+        );
+        for summary in [
+            "Update README",
+            "feat: ",
+            "feat(api: missing paren",
+            "feat-1: digits",
+            ": no type",
+            "feat:no space",
+        ] {
+            assert_eq!(parse_header(summary), None, "{}", summary);
+        }
+    }
+
+    #[test]
+    fn parse_detects_breaking_footer() {
+        let parsed = commit("refactor: drop v1\n\nBREAKING CHANGE: v1 removed");
+        assert_eq!(parsed.kind.as_deref(), Some("refactor"));
+        assert!(parsed.breaking);
+        let parsed = commit("Merge branch 'dev'");
+        assert_eq!(parsed.kind, None);
+        assert_eq!(parsed.description, "Merge branch 'dev'");
+        assert!(!parsed.breaking);
+    }
+
+    #[test]
+    fn bump_for_picks_highest_level() {
+        assert_eq!(bump_for(&[]), None);
+        assert_eq!(
+            bump_for(&[commit("fix: a"), commit("docs: b")]),
+            Some(Bump::Patch)
+        );
+        assert_eq!(
+            bump_for(&[commit("fix: a"), commit("feat: b")]),
+            Some(Bump::Minor)
+        );
+        assert_eq!(
+            bump_for(&[commit("feat: a"), commit("fix!: b")]),
+            Some(Bump::Major)
+        );
+        let version = Version::parse("v1.2.3").unwrap();
+        assert_eq!(version.bump(Bump::Minor).to_string(), "1.3.0");
+        assert_eq!(version.bump(Bump::Major).to_string(), "2.0.0");
+        assert_eq!(Version::parse("1.2.3-rc.1"), None);
+        assert_eq!(Version::parse("1.2"), None);
+    }
+
+    #[test]
+    fn to_markdown_groups_by_section() {
+        let notes = ReleaseNotes {
+            previous: None,
+            next_version: Some(Version::new(1, 0, 0)),
+            commits: vec![
+                commit("fix(db): reconnect"),
+                commit("feat!: new config"),
+                commit("chore: bump deps"),
+            ],
+        };
+        assert_eq!(
+            notes.to_markdown(),
+            "## v1.0.0\n\
+             \n### Breaking Changes\n\n- new config (0123456)\n\
+             \n### Bug Fixes\n\n- **db:** reconnect (0123456)\n\
+             \n### Other Changes\n\n- bump deps (0123456)\n"
+        );
+    }
+}
@@ -0,0 +1,222 @@
+use crate::command_utils::shell_quote;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// SSH 主机密钥校验策略，对应 `StrictHostKeyChecking`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum HostKeyPolicy {
+    /// 只接受 known_hosts 中已有的主机
+    Strict,
+    /// 自动接受首次连接的主机，已知主机的密钥变化时拒绝
+    AcceptNew,
+    /// 不校验主机密钥
+    Off,
+}
+
+impl HostKeyPolicy {
+    fn ssh_option(&self) -> &'static str {
+        match self {
+            HostKeyPolicy::Strict => "yes",
+            HostKeyPolicy::AcceptNew => "accept-new",
+            HostKeyPolicy::Off => "no",
+        }
+    }
+}
+
+/// 访问私有仓库的凭据
+///
+/// 密钥本身不写在配置中：HTTPS 令牌从环境变量或文件读取，
+/// 通过环境变量传给 git，不会出现在命令行和日志中。
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GitCredentials {
+    /// HTTPS 用户名，默认 `x-access-token`
+    #[serde(default)]
+    pub username: Option<String>,
+    /// 保存 HTTPS 令牌的环境变量名
+    #[serde(default)]
+    pub token_env: Option<String>,
+    /// 保存 HTTPS 令牌的文件
+    #[serde(default)]
+    pub token_file: Option<PathBuf>,
+    /// SSH 私钥文件
+    #[serde(default)]
+    pub ssh_key: Option<PathBuf>,
+    /// 完整的 `GIT_SSH_COMMAND`，设置后忽略其他 SSH 选项
+    #[serde(default)]
+    pub ssh_command: Option<String>,
+    /// SSH known_hosts 文件
+    #[serde(default)]
+    pub known_hosts: Option<PathBuf>,
+    /// SSH 主机密钥校验策略，未设置时使用 ssh 的默认配置
+    #[serde(default)]
+    pub host_key_policy: Option<HostKeyPolicy>,
+}
+
+impl GitCredentials {
+    /// 读取 HTTPS 令牌，环境变量优先于文件
+    pub fn token(&self) -> io::Result<Option<String>> {
+        if let Some(name) = &self.token_env {
+            match env::var(name) {
+                Ok(token) if !token.trim().is_empty() => return Ok(Some(token.trim().to_string())),
+                _ => {
+                    if self.token_file.is_none() {
+                        return Err(io::Error::new(
+                            io::ErrorKind::NotFound,
+                            format!("environment variable {} is not set", name),
+                        ));
+                    }
+                }
+            }
+        }
+        match &self.token_file {
+            Some(path) => Ok(Some(fs::read_to_string(path)?.trim().to_string())),
+            None => Ok(None),
+        }
+    }
+
+    /// 根据 SSH 选项生成的 `GIT_SSH_COMMAND`，没有任何 SSH 选项时返回 `None`
+    pub fn ssh_command(&self) -> Option<String> {
+        if let Some(command) = &self.ssh_command {
+            return Some(command.clone());
+        }
+        if self.ssh_key.is_none() && self.known_hosts.is_none() && self.host_key_policy.is_none() {
+            return None;
+        }
+        let mut command = String::from("ssh");
+        if let Some(key) = &self.ssh_key {
+            command.push_str(&format!(
+                " -i {} -o IdentitiesOnly=yes",
+                shell_quote(&key.to_string_lossy())
+            ));
+        }
+        if let Some(known_hosts) = &self.known_hosts {
+            command.push_str(&format!(
+                " -o UserKnownHostsFile={}",
+                shell_quote(&known_hosts.to_string_lossy())
+            ));
+        }
+        if let Some(policy) = &self.host_key_policy {
+            command.push_str(&format!(
+                " -o StrictHostKeyChecking={}",
+                policy.ssh_option()
+            ));
+        }
+        Some(command)
+    }
+
+    /// 为访问 `url` 的 git 命令设置凭据相关的环境变量
+    ///
+    /// 令牌以 `http.<地址>.extraHeader` 的形式通过 `GIT_CONFIG_*` 环境变量传入，
+    /// 只对该仓库所在的主机生效。
+    pub fn configure(&self, command: &mut Command, url: &str) -> io::Result<()> {
+        // 缺少凭据时直接失败，而不是等待终端输入
+        command.env("GIT_TERMINAL_PROMPT", "0");
+        if let Some(ssh_command) = self.ssh_command() {
+            command.env("GIT_SSH_COMMAND", ssh_command);
+        }
+        // 只有 HTTP(S) 地址才读取令牌，SSH 地址不要求令牌存在
+        let origin = match http_origin(url) {
+            Some(origin) => origin,
+            None => return Ok(()),
+        };
+        if let Some(token) = self.token()? {
+            let username = self.username.as_deref().unwrap_or("x-access-token");
+            let basic =
+                base64::engine::general_purpose::STANDARD.encode(format!("{}:{}", username, token));
+            command
+                .env("GIT_CONFIG_COUNT", "1")
+                .env("GIT_CONFIG_KEY_0", format!("http.{}/.extraHeader", origin))
+                .env(
+                    "GIT_CONFIG_VALUE_0",
+                    format!("Authorization: Basic {}", basic),
+                );
+        }
+        Ok(())
+    }
+}
+
+/// HTTP(S) 地址的 `scheme://host[:port]` 部分，去掉其中的用户名密码
+fn http_origin(url: &str) -> Option<String> {
+    let (scheme, rest) = url.split_once("://")?;
+    if !scheme.eq_ignore_ascii_case("http") && !scheme.eq_ignore_ascii_case("https") {
+        return None;
+    }
+    let authority = rest.split('/').next().unwrap_or_default();
+    let host = authority.rsplit('@').next().unwrap_or(authority);
+    Some(format!("{}://{}", scheme, host))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::OsStr;
+
+    fn env_value<'a>(command: &'a Command, key: &str) -> Option<&'a OsStr> {
+        command
+            .get_envs()
+            .find(|(k, _)| *k == OsStr::new(key))
+            .and_then(|(_, v)| v)
+    }
+
+    fn missing_token() -> GitCredentials {
+        GitCredentials {
+            token_env: Some("RS_UTILS_TEST_MISSING_TOKEN".to_string()),
+            ..GitCredentials::default()
+        }
+    }
+
+    #[test]
+    fn ssh_url_does_not_need_token() {
+        let mut command = Command::new("git");
+        missing_token()
+            .configure(&mut command, "git@example.com:team/repo.git")
+            .unwrap();
+        missing_token()
+            .configure(&mut command, "ssh://git@example.com/team/repo.git")
+            .unwrap();
+        assert_eq!(
+            env_value(&command, "GIT_TERMINAL_PROMPT"),
+            Some(OsStr::new("0"))
+        );
+        assert_eq!(env_value(&command, "GIT_CONFIG_COUNT"), None);
+    }
+
+    #[test]
+    fn https_url_requires_token() {
+        let mut command = Command::new("git");
+        let err = missing_token()
+            .configure(&mut command, "https://example.com/team/repo.git")
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn token_header_is_scoped_to_origin() {
+        let dir = tempfile::tempdir().unwrap();
+        let token_file = dir.path().join("token");
+        fs::write(&token_file, "secret\n").unwrap();
+        let credentials = GitCredentials {
+            token_file: Some(token_file),
+            ..GitCredentials::default()
+        };
+        let mut command = Command::new("git");
+        credentials
+            .configure(&mut command, "https://user@example.com:8443/team/repo.git")
+            .unwrap();
+        assert_eq!(
+            env_value(&command, "GIT_CONFIG_KEY_0"),
+            Some(OsStr::new("http.https://example.com:8443/.extraHeader"))
+        );
+        let basic = base64::engine::general_purpose::STANDARD.encode("x-access-token:secret");
+        assert_eq!(
+            env_value(&command, "GIT_CONFIG_VALUE_0"),
+            Some(OsStr::new(&format!("Authorization: Basic {}", basic)))
+        );
+    }
+}
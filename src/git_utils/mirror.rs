@@ -0,0 +1,195 @@
+use crate::command_utils;
+use crate::file_utils::hash::{ContentHasher, HashAlgorithm};
+use crate::git_utils::credentials::GitCredentials;
+use crate::git_utils::{git, git_with};
+use log::info;
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Mutex;
+
+/// 本地裸镜像缓存，同一仓库的多个检出共享对象
+///
+/// 每个远程地址对应一个 `git clone --mirror` 的裸仓库。项目检出以镜像为 `--reference`
+/// 克隆并加上 `--dissociate`，克隆完成后复制所需对象，不再依赖镜像，
+/// 因此镜像之后的 `fetch --prune` 和 gc 不会影响已有的检出。
+/// 每个镜像在一个缓存实例的生命周期内只刷新一次。
+#[derive(Debug)]
+pub struct MirrorCache {
+    root: PathBuf,
+    refreshed: Mutex<HashSet<PathBuf>>,
+}
+
+impl MirrorCache {
+    /// 以指定目录作为缓存根目录，目录不存在时创建
+    pub fn new(root: impl AsRef<Path>) -> io::Result<Self> {
+        let root = root.as_ref();
+        fs::create_dir_all(root)?;
+        Ok(MirrorCache {
+            root: fs::canonicalize(root)?,
+            refreshed: Mutex::new(HashSet::new()),
+        })
+    }
+
+    /// 缓存根目录
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// 远程地址对应的镜像目录，目录名由可读的地址和地址的哈希组成
+    pub fn mirror_path(&self, url: &str) -> PathBuf {
+        let readable: String = strip_userinfo(url)
+            .split_once("://")
+            .map_or(url, |(_, rest)| rest)
+            .trim_end_matches('/')
+            .trim_end_matches(".git")
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '-' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        let readable = readable.trim_matches('_');
+        let readable = &readable[readable.len().saturating_sub(48)..];
+        let mut hasher = ContentHasher::new(HashAlgorithm::Sha256);
+        hasher.update(url.as_bytes());
+        let hash = hasher.finalize();
+        self.root.join(format!("{}-{}.git", readable, &hash[..12]))
+    }
+
+    /// 确保镜像存在且在本次运行中已刷新，返回镜像目录
+    pub fn ensure(&self, url: &str, credentials: &GitCredentials) -> io::Result<PathBuf> {
+        let mirror = self.mirror_path(url);
+        let mut refreshed = self.refreshed.lock().unwrap();
+        if refreshed.contains(&mirror) {
+            return Ok(mirror);
+        }
+        if mirror.join("HEAD").exists() {
+            info!("刷新镜像 {}", mirror.display());
+            git_with(&mirror, &["fetch", "--prune", "origin"], credentials, url)?;
+        } else {
+            info!("创建镜像 {}", mirror.display());
+            // 先克隆到临时目录，避免中断后留下不完整的镜像
+            let partial = mirror.with_extension("partial");
+            if partial.exists() {
+                fs::remove_dir_all(&partial)?;
+            }
+            let mut command = Command::new("git");
+            command.args(["clone", "--mirror", url]).arg(&partial);
+            credentials.configure(&mut command, url)?;
+            command_utils::run_checked(command)?;
+            fs::rename(&partial, &mirror)?;
+        }
+        refreshed.insert(mirror.clone());
+        Ok(mirror)
+    }
+
+    /// 借助镜像把 `url` 的 `branch` 分支检出到 `dest`
+    ///
+    /// 直接从刚刷新过的镜像克隆，不访问远程。克隆完成后 `origin` 改回原远程地址，
+    /// 之后可以直接从远程拉取更新。
+    pub fn checkout(
+        &self,
+        url: &str,
+        branch: &str,
+        dest: &Path,
+        credentials: &GitCredentials,
+    ) -> io::Result<String> {
+        let mirror = self.ensure(url, credentials)?;
+        let mut command = Command::new("git");
+        command
+            .args(["clone", "--dissociate", "--branch", branch])
+            .arg("--reference")
+            .arg(&mirror)
+            .arg(&mirror)
+            .arg(dest);
+        let output = command_utils::run_checked(command)?;
+        git(dest, &["remote", "set-url", "origin", url])?;
+        Ok(output)
+    }
+}
+
+/// 去掉地址中的用户名密码
+fn strip_userinfo(url: &str) -> String {
+    match url.split_once("://") {
+        Some((scheme, rest)) => {
+            let end = rest.find('/').unwrap_or(rest.len());
+            match rest[..end].rfind('@') {
+                Some(at) => format!("{}://{}", scheme, &rest[at + 1..]),
+                None => url.to_string(),
+            }
+        }
+        None => url.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(dir: &Path, args: &[&str]) {
+        let mut command = Command::new("git");
+        command
+            .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+            .arg("-C")
+            .arg(dir)
+            .args(args);
+        command_utils::run_checked(command).unwrap();
+    }
+
+    /// 在临时目录中创建带 `main` 和 `feature` 两个分支的裸仓库
+    fn bare_repo(dir: &Path) -> PathBuf {
+        let work = dir.join("work");
+        fs::create_dir_all(&work).unwrap();
+        run(&work, &["init", "-b", "main"]);
+        fs::write(work.join("a.txt"), "main").unwrap();
+        run(&work, &["add", "."]);
+        run(&work, &["commit", "-m", "main"]);
+        run(&work, &["checkout", "-b", "feature"]);
+        fs::write(work.join("b.txt"), "feature").unwrap();
+        run(&work, &["add", "."]);
+        run(&work, &["commit", "-m", "feature"]);
+        let bare = dir.join("source.git");
+        run(dir, &["clone", "--bare", "work", "source.git"]);
+        bare
+    }
+
+    #[test]
+    fn checkout_branches_from_one_mirror() {
+        let dir = tempfile::tempdir().unwrap();
+        let bare = bare_repo(dir.path());
+        let url = format!("file://{}", bare.display());
+        let cache = MirrorCache::new(dir.path().join("mirrors")).unwrap();
+        let credentials = GitCredentials::default();
+
+        let main = dir.path().join("main");
+        cache.checkout(&url, "main", &main, &credentials).unwrap();
+        // 镜像已在本次运行中刷新，第二次检出不再访问远程
+        fs::rename(&bare, dir.path().join("moved.git")).unwrap();
+        let feature = dir.path().join("feature");
+        cache
+            .checkout(&url, "feature", &feature, &credentials)
+            .unwrap();
+
+        let mirrors: Vec<_> = fs::read_dir(cache.root()).unwrap().collect();
+        assert_eq!(mirrors.len(), 1);
+        assert_eq!(
+            cache.mirror_path(&url),
+            cache.ensure(&url, &credentials).unwrap()
+        );
+
+        assert!(main.join("a.txt").exists());
+        assert!(!main.join("b.txt").exists());
+        assert!(feature.join("b.txt").exists());
+        for checkout in [&main, &feature] {
+            let origin = git(checkout, &["remote", "get-url", "origin"]).unwrap();
+            assert_eq!(origin.trim(), url);
+            assert!(!checkout.join(".git/objects/info/alternates").exists());
+        }
+    }
+}
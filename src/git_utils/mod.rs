@@ -1,14 +1,61 @@
+pub mod changelog;
+pub mod credentials;
+pub mod mirror;
+pub mod repo;
+pub mod sync;
+
 use crate::command_utils;
+use crate::git_utils::credentials::GitCredentials;
 use std::io::{self};
+use std::path::Path;
+use std::process::Command;
+
+/// 在指定仓库目录下执行 git 命令，相当于 `git -C <repo> ...`
+pub fn git(repo: &Path, args: &[&str]) -> Result<String, io::Error> {
+    let mut command = Command::new("git");
+    command.arg("-C").arg(repo).args(args);
+    command_utils::run_checked(command)
+}
+
+/// 带凭据执行访问远程仓库 `url` 的 git 命令，相当于 `git -C <repo> ...`
+pub fn git_with(
+    repo: &Path,
+    args: &[&str],
+    credentials: &GitCredentials,
+    url: &str,
+) -> Result<String, io::Error> {
+    let mut command = Command::new("git");
+    command.arg("-C").arg(repo).args(args);
+    credentials.configure(&mut command, url)?;
+    command_utils::run_checked(command)
+}
+
+/// 带凭据克隆单个分支，`depth` 为 `None` 时克隆完整历史
+pub fn clone_with(
+    url: &str,
+    branch: &str,
+    dir: &Path,
+    depth: Option<u32>,
+    credentials: &GitCredentials,
+) -> Result<String, io::Error> {
+    let mut command = Command::new("git");
+    command.args(["clone", "--single-branch", "--branch", branch]);
+    if let Some(depth) = depth {
+        command.arg("--depth").arg(depth.to_string());
+    }
+    command.arg(url).arg(dir);
+    credentials.configure(&mut command, url)?;
+    command_utils::run_checked(command)
+}
 
 pub fn clone_default(url: &str, branch: &str, dir: &str) -> Result<String, io::Error> {
     let args = &["clone", "--branch", branch, url, dir];
-    command_utils::run_command("git", args)
+    command_utils::run_command_checked("git", args)
 }
 
 pub fn clone_single_branch(url: &str, branch: &str, dir: &str) -> Result<String, io::Error> {
     let args = &["clone", "--single-branch", "--branch", branch, url, dir];
-    command_utils::run_command("git", args)
+    command_utils::run_command_checked("git", args)
 }
 
 pub fn clone_latest(url: &str, branch: &str, dir: &str) -> Result<String, io::Error> {
@@ -22,15 +69,109 @@ pub fn clone_latest(url: &str, branch: &str, dir: &str) -> Result<String, io::Er
         url,
         dir,
     ];
-    command_utils::run_command("git", args)
+    command_utils::run_command_checked("git", args)
+}
+
+pub fn pull(repo: &Path) -> Result<String, io::Error> {
+    git(repo, &["pull"])
+}
+
+pub fn fetch(repo: &Path) -> Result<String, io::Error> {
+    git(repo, &["fetch", "--force"])
+}
+
+/// 检出分支、标签或提交，标签和提交会处于分离 HEAD 状态
+pub fn checkout(repo: &Path, rev: &str) -> Result<String, io::Error> {
+    git(repo, &["checkout", rev])
 }
 
-pub fn pull() -> Result<String, io::Error> {
-    let args = &["pull"];
-    command_utils::run_command("git", args)
+/// 强制重置到指定引用，例如 `origin/main`，丢弃所有本地修改
+pub fn reset_hard(repo: &Path, rev: &str) -> Result<String, io::Error> {
+    git(repo, &["reset", "--hard", rev])
 }
 
-pub fn fetch() -> Result<String, io::Error> {
-    let args = &["fetch", "--force"];
-    command_utils::run_command("git", args)
+/// 删除未跟踪的文件和目录，`include_ignored` 为 `true` 时一并删除被忽略的文件
+pub fn clean(repo: &Path, include_ignored: bool) -> Result<String, io::Error> {
+    if include_ignored {
+        git(repo, &["clean", "-fdx"])
+    } else {
+        git(repo, &["clean", "-fd"])
+    }
+}
+
+/// 初始化子模块
+pub fn submodule_init(repo: &Path) -> Result<String, io::Error> {
+    git(repo, &["submodule", "init"])
+}
+
+/// 初始化并递归更新子模块到父仓库记录的版本
+pub fn submodule_update(repo: &Path) -> Result<String, io::Error> {
+    git(repo, &["submodule", "update", "--init", "--recursive"])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    /// 在临时目录中创建仓库，提交 `a.txt` 和忽略 `*.log` 的 `.gitignore`
+    fn init(dir: &Path) {
+        git(dir, &["init", "-q", "-b", "main"]).unwrap();
+        fs::write(dir.join("a.txt"), "a").unwrap();
+        fs::write(dir.join(".gitignore"), "*.log\n").unwrap();
+        git(dir, &["add", "."]).unwrap();
+        git(
+            dir,
+            &[
+                "-c",
+                "user.name=test",
+                "-c",
+                "user.email=test@example.com",
+                "commit",
+                "-q",
+                "-m",
+                "init",
+            ],
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn clean_keeps_ignored_files_unless_asked() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        init(root);
+        fs::write(root.join("untracked.txt"), "x").unwrap();
+        fs::create_dir_all(root.join("tmp")).unwrap();
+        fs::write(root.join("tmp/nested.txt"), "x").unwrap();
+        fs::write(root.join("build.log"), "log").unwrap();
+
+        clean(root, false).unwrap();
+        assert!(!root.join("untracked.txt").exists());
+        assert!(!root.join("tmp").exists());
+        assert!(root.join("build.log").exists());
+        assert!(root.join("a.txt").exists());
+
+        clean(root, true).unwrap();
+        assert!(!root.join("build.log").exists());
+        assert!(root.join("a.txt").exists());
+        assert!(root.join(".gitignore").exists());
+    }
+
+    #[test]
+    fn reset_hard_restores_tracked_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        init(root);
+        fs::write(root.join("a.txt"), "changed").unwrap();
+        fs::remove_file(root.join(".gitignore")).unwrap();
+        fs::write(root.join("untracked.txt"), "x").unwrap();
+
+        reset_hard(root, "HEAD").unwrap();
+        assert_eq!(fs::read_to_string(root.join("a.txt")).unwrap(), "a");
+        assert!(root.join(".gitignore").exists());
+        // 未跟踪的文件不受影响
+        assert!(root.join("untracked.txt").exists());
+        assert_eq!(git(root, &["status", "--porcelain", "-uno"]).unwrap(), "");
+    }
 }
@@ -0,0 +1,591 @@
+use git2::{BranchType, ErrorCode, Repository, Status, StatusOptions};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// 提交信息
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CommitInfo {
+    pub id: String,
+    pub short_id: String,
+    pub author: String,
+    pub email: String,
+    /// 提交时间，Unix 时间戳（秒）
+    pub time: i64,
+    pub message: String,
+}
+
+impl CommitInfo {
+    /// 提交信息的第一行
+    pub fn summary(&self) -> &str {
+        self.message.lines().next().unwrap_or_default()
+    }
+}
+
+/// 标签
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tag {
+    pub name: String,
+    /// 标签指向的提交 ID
+    pub commit: String,
+    /// 附注标签的说明，轻量标签为 `None`
+    pub message: Option<String>,
+}
+
+/// 远程仓库
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Remote {
+    pub name: String,
+    pub url: Option<String>,
+    pub push_url: Option<String>,
+}
+
+/// 工作区状态，路径均相对于仓库根目录
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RepoStatus {
+    /// 已暂存的变化
+    pub staged: Vec<PathBuf>,
+    /// 未暂存的修改
+    pub modified: Vec<PathBuf>,
+    /// 未暂存的删除
+    pub deleted: Vec<PathBuf>,
+    pub untracked: Vec<PathBuf>,
+    pub conflicted: Vec<PathBuf>,
+}
+
+impl RepoStatus {
+    /// 已跟踪文件是否有变化，不考虑未跟踪文件
+    pub fn is_dirty(&self) -> bool {
+        !(self.staged.is_empty()
+            && self.modified.is_empty()
+            && self.deleted.is_empty()
+            && self.conflicted.is_empty())
+    }
+
+    /// 是否没有任何变化，包括未跟踪文件
+    pub fn is_clean(&self) -> bool {
+        !self.is_dirty() && self.untracked.is_empty()
+    }
+}
+
+/// 构建时的代码版本
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Revision {
+    /// 分支名，分离 HEAD 时为空
+    pub branch: Option<String>,
+    pub commit: CommitInfo,
+    /// 已跟踪文件是否有未提交的修改
+    pub dirty: bool,
+}
+
+impl fmt::Display for Revision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.branch {
+            Some(branch) => write!(f, "{}@{}", branch, self.commit.short_id)?,
+            None => write!(f, "{}", self.commit.short_id)?,
+        }
+        if self.dirty {
+            write!(f, "-dirty")?;
+        }
+        Ok(())
+    }
+}
+
+/// 本地 git 仓库，直接读取仓库数据而不调用 `git` 命令
+pub struct GitRepo {
+    repo: Repository,
+}
+
+impl GitRepo {
+    /// 打开指定目录下的仓库
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let repo = Repository::open(path.as_ref()).map_err(git_error)?;
+        Ok(GitRepo { repo })
+    }
+
+    /// 从指定目录向上查找并打开仓库
+    pub fn discover(path: impl AsRef<Path>) -> io::Result<Self> {
+        let repo = Repository::discover(path.as_ref()).map_err(git_error)?;
+        Ok(GitRepo { repo })
+    }
+
+    /// 工作区根目录，裸仓库返回 `.git` 目录
+    pub fn path(&self) -> &Path {
+        self.repo.workdir().unwrap_or_else(|| self.repo.path())
+    }
+
+    /// 底层的 git2 仓库
+    pub fn inner(&self) -> &Repository {
+        &self.repo
+    }
+
+    /// HEAD 指向的提交 ID，仓库还没有任何提交时返回 `None`
+    pub fn head(&self) -> io::Result<Option<String>> {
+        match self.repo.head() {
+            Ok(head) => Ok(head.target().map(|id| id.to_string())),
+            Err(e) if is_unborn(&e) => Ok(None),
+            Err(e) => Err(git_error(e)),
+        }
+    }
+
+    /// 当前分支名，分离 HEAD 时返回 `None`
+    pub fn branch(&self) -> io::Result<Option<String>> {
+        if self.repo.head_detached().map_err(git_error)? {
+            return Ok(None);
+        }
+        match self.repo.head() {
+            Ok(head) => Ok(Some(head.shorthand().map_err(git_error)?.to_string())),
+            // 没有提交的新仓库，HEAD 仍指向分支
+            Err(e) if is_unborn(&e) => {
+                let head = self.repo.find_reference("HEAD").map_err(git_error)?;
+                Ok(head
+                    .symbolic_target()
+                    .map_err(git_error)?
+                    .and_then(|t| t.strip_prefix("refs/heads/"))
+                    .map(String::from))
+            }
+            Err(e) => Err(git_error(e)),
+        }
+    }
+
+    /// 所有远程仓库
+    pub fn remotes(&self) -> io::Result<Vec<Remote>> {
+        let names = self.repo.remotes().map_err(git_error)?;
+        let mut remotes = Vec::new();
+        for name in names.iter() {
+            let name = match name.map_err(git_error)? {
+                Some(name) => name,
+                None => continue,
+            };
+            let remote = self.repo.find_remote(name).map_err(git_error)?;
+            remotes.push(Remote {
+                name: name.to_string(),
+                url: remote.url().ok().map(String::from),
+                push_url: remote.pushurl().map_err(git_error)?.map(String::from),
+            });
+        }
+        Ok(remotes)
+    }
+
+    /// 指定远程仓库的地址
+    pub fn remote_url(&self, name: &str) -> io::Result<Option<String>> {
+        match self.repo.find_remote(name) {
+            Ok(remote) => Ok(remote.url().ok().map(String::from)),
+            Err(e) if e.code() == ErrorCode::NotFound => Ok(None),
+            Err(e) => Err(git_error(e)),
+        }
+    }
+
+    /// 当前分支的上游分支，例如 `origin/main`
+    pub fn upstream(&self) -> io::Result<Option<String>> {
+        let branch = match self.branch()? {
+            Some(branch) => branch,
+            None => return Ok(None),
+        };
+        let local = self
+            .repo
+            .find_branch(&branch, BranchType::Local)
+            .map_err(git_error)?;
+        match local.upstream() {
+            Ok(upstream) => Ok(upstream.name().map_err(git_error)?.map(String::from)),
+            Err(e) if e.code() == ErrorCode::NotFound => Ok(None),
+            Err(e) => Err(git_error(e)),
+        }
+    }
+
+    /// 当前分支相对上游分支领先和落后的提交数，没有上游分支时返回 `None`
+    pub fn ahead_behind(&self) -> io::Result<Option<(usize, usize)>> {
+        let upstream = match self.upstream()? {
+            Some(upstream) => upstream,
+            None => return Ok(None),
+        };
+        let local = match self.head()? {
+            Some(id) => git2::Oid::from_str(&id).map_err(git_error)?,
+            None => return Ok(None),
+        };
+        let remote = self
+            .repo
+            .refname_to_id(&format!("refs/remotes/{}", upstream))
+            .map_err(git_error)?;
+        let counts = self
+            .repo
+            .graph_ahead_behind(local, remote)
+            .map_err(git_error)?;
+        Ok(Some(counts))
+    }
+
+    /// 工作区状态
+    pub fn status(&self) -> io::Result<RepoStatus> {
+        let mut options = StatusOptions::new();
+        options
+            .include_untracked(true)
+            .recurse_untracked_dirs(true)
+            .include_ignored(false)
+            .exclude_submodules(true);
+        let statuses = self.repo.statuses(Some(&mut options)).map_err(git_error)?;
+
+        let staged = Status::INDEX_NEW
+            | Status::INDEX_MODIFIED
+            | Status::INDEX_DELETED
+            | Status::INDEX_RENAMED
+            | Status::INDEX_TYPECHANGE;
+        let mut status = RepoStatus::default();
+        for entry in statuses.iter() {
+            let path = match entry.path() {
+                Ok(path) => PathBuf::from(path),
+                Err(_) => continue,
+            };
+            let flags = entry.status();
+            if flags.is_conflicted() {
+                status.conflicted.push(path);
+                continue;
+            }
+            if flags.intersects(staged) {
+                status.staged.push(path.clone());
+            }
+            if flags.intersects(Status::WT_MODIFIED | Status::WT_RENAMED | Status::WT_TYPECHANGE) {
+                status.modified.push(path);
+            } else if flags.is_wt_deleted() {
+                status.deleted.push(path);
+            } else if flags.is_wt_new() {
+                status.untracked.push(path);
+            }
+        }
+        Ok(status)
+    }
+
+    /// 已跟踪文件是否有未提交的修改
+    pub fn is_dirty(&self) -> io::Result<bool> {
+        Ok(self.status()?.is_dirty())
+    }
+
+    /// 索引中已跟踪的文件，路径相对于仓库根目录，按路径排序
+    pub fn tracked_files(&self) -> io::Result<Vec<PathBuf>> {
+        let index = self.repo.index().map_err(git_error)?;
+        let mut files: Vec<PathBuf> = index
+            .iter()
+            .map(|entry| PathBuf::from(String::from_utf8_lossy(&entry.path).into_owned()))
+            .collect();
+        files.sort();
+        files.dedup();
+        Ok(files)
+    }
+
+    /// 指定修订版本（分支、标签、提交 ID 等）对应的提交
+    pub fn commit(&self, rev: &str) -> io::Result<CommitInfo> {
+        let object = self.repo.revparse_single(rev).map_err(git_error)?;
+        let commit = object.peel_to_commit().map_err(git_error)?;
+        Ok(commit_info(&commit))
+    }
+
+    /// 修订版本对应的提交 ID
+    pub fn resolve(&self, rev: &str) -> io::Result<String> {
+        let object = self.repo.revparse_single(rev).map_err(git_error)?;
+        let commit = object.peel_to_commit().map_err(git_error)?;
+        Ok(commit.id().to_string())
+    }
+
+    /// `ancestor` 是否是 `descendant` 的祖先，两者相同时也返回 `true`
+    pub fn is_ancestor(&self, ancestor: &str, descendant: &str) -> io::Result<bool> {
+        let ancestor = git2::Oid::from_str(&self.resolve(ancestor)?).map_err(git_error)?;
+        let descendant = git2::Oid::from_str(&self.resolve(descendant)?).map_err(git_error)?;
+        if ancestor == descendant {
+            return Ok(true);
+        }
+        self.repo
+            .graph_descendant_of(descendant, ancestor)
+            .map_err(git_error)
+    }
+
+    /// `to` 可达而 `from` 不可达的提交，从新到旧排列，相当于 `git log from..to`
+    ///
+    /// `from` 为 `None` 时返回 `to` 的全部历史。
+    pub fn commits_between(&self, from: Option<&str>, to: &str) -> io::Result<Vec<CommitInfo>> {
+        let mut walk = self.repo.revwalk().map_err(git_error)?;
+        walk.set_sorting(git2::Sort::TOPOLOGICAL | git2::Sort::TIME)
+            .map_err(git_error)?;
+        walk.push(git2::Oid::from_str(&self.resolve(to)?).map_err(git_error)?)
+            .map_err(git_error)?;
+        if let Some(from) = from {
+            walk.hide(git2::Oid::from_str(&self.resolve(from)?).map_err(git_error)?)
+                .map_err(git_error)?;
+        }
+        let mut commits = Vec::new();
+        for id in walk {
+            let commit = self
+                .repo
+                .find_commit(id.map_err(git_error)?)
+                .map_err(git_error)?;
+            commits.push(commit_info(&commit));
+        }
+        Ok(commits)
+    }
+
+    /// 所有指向提交的标签，按名称排序
+    pub fn tags(&self) -> io::Result<Vec<Tag>> {
+        let names = self.repo.tag_names(None).map_err(git_error)?;
+        let mut tags = Vec::new();
+        for name in names.iter() {
+            let name = match name.map_err(git_error)? {
+                Some(name) => name,
+                None => continue,
+            };
+            let reference = self
+                .repo
+                .find_reference(&format!("refs/tags/{}", name))
+                .map_err(git_error)?;
+            // 指向树或二进制对象的标签不是版本标签，忽略
+            let commit = match reference.peel_to_commit() {
+                Ok(commit) => commit,
+                Err(_) => continue,
+            };
+            let message = reference.peel_to_tag().ok().and_then(|tag| {
+                tag.message_bytes()
+                    .map(|m| String::from_utf8_lossy(m).into_owned())
+            });
+            tags.push(Tag {
+                name: name.to_string(),
+                commit: commit.id().to_string(),
+                message,
+            });
+        }
+        Ok(tags)
+    }
+
+    /// 在指定修订版本上创建标签，有说明时创建附注标签，否则创建轻量标签
+    ///
+    /// 附注标签的签名取自仓库的 `user.name` 和 `user.email` 配置。
+    pub fn create_tag(&self, name: &str, rev: &str, message: Option<&str>) -> io::Result<Tag> {
+        let object = self.repo.revparse_single(rev).map_err(git_error)?;
+        let commit = object.peel_to_commit().map_err(git_error)?;
+        match message {
+            Some(message) => {
+                let signature = self.repo.signature().map_err(git_error)?;
+                self.repo
+                    .tag(name, commit.as_object(), &signature, message, false)
+                    .map_err(git_error)?;
+            }
+            None => {
+                self.repo
+                    .tag_lightweight(name, commit.as_object(), false)
+                    .map_err(git_error)?;
+            }
+        }
+        Ok(Tag {
+            name: name.to_string(),
+            commit: commit.id().to_string(),
+            message: message.map(String::from),
+        })
+    }
+
+    /// HEAD 指向的最新提交，仓库还没有任何提交时返回 `None`
+    pub fn latest_commit(&self) -> io::Result<Option<CommitInfo>> {
+        match self.repo.head() {
+            Ok(head) => {
+                let commit = head.peel_to_commit().map_err(git_error)?;
+                Ok(Some(commit_info(&commit)))
+            }
+            Err(e) if is_unborn(&e) => Ok(None),
+            Err(e) => Err(git_error(e)),
+        }
+    }
+
+    /// 当前检出的版本，仓库还没有任何提交时返回 `None`
+    pub fn revision(&self) -> io::Result<Option<Revision>> {
+        let commit = match self.latest_commit()? {
+            Some(commit) => commit,
+            None => return Ok(None),
+        };
+        Ok(Some(Revision {
+            branch: self.branch()?,
+            commit,
+            dirty: self.is_dirty()?,
+        }))
+    }
+}
+
+fn commit_info(commit: &git2::Commit) -> CommitInfo {
+    let author = commit.author();
+    let id = commit.id().to_string();
+    CommitInfo {
+        short_id: id.chars().take(7).collect(),
+        id,
+        author: String::from_utf8_lossy(author.name_bytes()).into_owned(),
+        email: String::from_utf8_lossy(author.email_bytes()).into_owned(),
+        time: commit.time().seconds(),
+        message: String::from_utf8_lossy(commit.message_bytes()).into_owned(),
+    }
+}
+
+fn is_unborn(e: &git2::Error) -> bool {
+    matches!(e.code(), ErrorCode::UnbornBranch | ErrorCode::NotFound)
+}
+
+pub(crate) fn git_error(e: git2::Error) -> io::Error {
+    let kind = match e.code() {
+        ErrorCode::NotFound => io::ErrorKind::NotFound,
+        ErrorCode::Exists => io::ErrorKind::AlreadyExists,
+        ErrorCode::Auth | ErrorCode::Certificate => io::ErrorKind::PermissionDenied,
+        _ => io::ErrorKind::Other,
+    };
+    io::Error::new(kind, e.message().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command_utils;
+    use std::fs;
+    use std::process::Command;
+
+    fn run(dir: &Path, args: &[&str]) {
+        let mut command = Command::new("git");
+        command
+            .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+            .arg("-C")
+            .arg(dir)
+            .args(args);
+        command_utils::run_checked(command).unwrap();
+    }
+
+    fn commit(dir: &Path, file: &str, contents: &str, message: &str) {
+        fs::write(dir.join(file), contents).unwrap();
+        run(dir, &["add", "."]);
+        run(dir, &["commit", "-m", message]);
+    }
+
+    /// 在临时目录中创建仓库，`main` 分支上有两个提交
+    fn init(dir: &Path) -> GitRepo {
+        run(dir, &["init", "-b", "main"]);
+        commit(dir, "a.txt", "a", "first");
+        commit(dir, "b.txt", "b", "second");
+        GitRepo::open(dir).unwrap()
+    }
+
+    #[test]
+    fn status_reports_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = init(dir.path());
+        let status = repo.status().unwrap();
+        assert!(status.is_clean());
+
+        fs::write(dir.path().join("new.txt"), "new").unwrap();
+        let status = repo.status().unwrap();
+        assert_eq!(status.untracked, [PathBuf::from("new.txt")]);
+        assert!(!status.is_dirty());
+        assert!(!status.is_clean());
+
+        fs::write(dir.path().join("a.txt"), "changed").unwrap();
+        fs::remove_file(dir.path().join("b.txt")).unwrap();
+        fs::write(dir.path().join("c.txt"), "c").unwrap();
+        run(dir.path(), &["add", "c.txt"]);
+        let status = repo.status().unwrap();
+        assert_eq!(status.modified, [PathBuf::from("a.txt")]);
+        assert_eq!(status.deleted, [PathBuf::from("b.txt")]);
+        assert_eq!(status.staged, [PathBuf::from("c.txt")]);
+        assert!(repo.is_dirty().unwrap());
+        assert!(repo.revision().unwrap().unwrap().dirty);
+    }
+
+    #[test]
+    fn head_branch_and_history() {
+        let dir = tempfile::tempdir().unwrap();
+        run(dir.path(), &["init", "-b", "main"]);
+        let repo = GitRepo::open(dir.path()).unwrap();
+        // 还没有提交时 HEAD 仍指向分支
+        assert_eq!(repo.head().unwrap(), None);
+        assert_eq!(repo.branch().unwrap().as_deref(), Some("main"));
+        assert_eq!(repo.revision().unwrap(), None);
+
+        commit(dir.path(), "a.txt", "a", "first");
+        commit(dir.path(), "b.txt", "b", "second");
+        let head = repo.head().unwrap().unwrap();
+        let first = repo.resolve("HEAD~1").unwrap();
+        assert_eq!(repo.resolve("main").unwrap(), head);
+        assert!(repo.is_ancestor(&first, &head).unwrap());
+        assert!(!repo.is_ancestor(&head, &first).unwrap());
+        assert_eq!(
+            repo.tracked_files().unwrap(),
+            [PathBuf::from("a.txt"), PathBuf::from("b.txt")]
+        );
+
+        let all = repo.commits_between(None, "HEAD").unwrap();
+        let summaries: Vec<_> = all.iter().map(CommitInfo::summary).collect();
+        assert_eq!(summaries, ["second", "first"]);
+        assert_eq!(all[0].id, head);
+        assert_eq!(all[0].short_id, &head[..7]);
+        assert_eq!(all[0].author, "test");
+        let new = repo.commits_between(Some(&first), "HEAD").unwrap();
+        assert_eq!(new.len(), 1);
+        assert_eq!(new[0].id, head);
+
+        let revision = repo.revision().unwrap().unwrap();
+        assert_eq!(revision.to_string(), format!("main@{}", &head[..7]));
+
+        run(dir.path(), &["checkout", "--detach", "HEAD~1"]);
+        assert_eq!(repo.branch().unwrap(), None);
+        assert_eq!(repo.head().unwrap().as_deref(), Some(first.as_str()));
+        assert_eq!(repo.upstream().unwrap(), None);
+        assert_eq!(repo.ahead_behind().unwrap(), None);
+        assert_eq!(repo.revision().unwrap().unwrap().to_string(), &first[..7]);
+    }
+
+    #[test]
+    fn ahead_behind_local_upstream() {
+        let dir = tempfile::tempdir().unwrap();
+        let upstream = dir.path().join("upstream");
+        fs::create_dir_all(&upstream).unwrap();
+        init(&upstream);
+        run(dir.path(), &["clone", "upstream", "clone"]);
+        let clone = dir.path().join("clone");
+        let repo = GitRepo::open(&clone).unwrap();
+        assert_eq!(repo.upstream().unwrap().as_deref(), Some("origin/main"));
+        assert_eq!(
+            repo.remote_url("origin").unwrap().as_deref(),
+            Some(upstream.to_str().unwrap())
+        );
+        assert_eq!(repo.remote_url("missing").unwrap(), None);
+        assert_eq!(repo.ahead_behind().unwrap(), Some((0, 0)));
+
+        commit(&upstream, "c.txt", "c", "upstream");
+        run(&clone, &["fetch"]);
+        assert_eq!(repo.ahead_behind().unwrap(), Some((0, 1)));
+        commit(&clone, "d.txt", "d", "local 1");
+        commit(&clone, "e.txt", "e", "local 2");
+        assert_eq!(repo.ahead_behind().unwrap(), Some((2, 1)));
+    }
+
+    #[test]
+    fn tags_point_to_commits() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = init(dir.path());
+        run(dir.path(), &["config", "user.name", "test"]);
+        run(dir.path(), &["config", "user.email", "test@example.com"]);
+        let first = repo.resolve("HEAD~1").unwrap();
+        let head = repo.head().unwrap().unwrap();
+
+        repo.create_tag("v0.1.0", "HEAD~1", None).unwrap();
+        repo.create_tag("v0.2.0", "HEAD", Some("release 0.2.0"))
+            .unwrap();
+        // 指向树对象的标签会被忽略
+        run(dir.path(), &["tag", "tree", "HEAD^{tree}"]);
+
+        let tags = repo.tags().unwrap();
+        assert_eq!(
+            tags,
+            [
+                Tag {
+                    name: "v0.1.0".to_string(),
+                    commit: first,
+                    message: None,
+                },
+                Tag {
+                    name: "v0.2.0".to_string(),
+                    commit: head,
+                    message: Some("release 0.2.0".to_string()),
+                },
+            ]
+        );
+    }
+}
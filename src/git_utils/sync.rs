@@ -0,0 +1,332 @@
+use crate::command_utils;
+use crate::git_utils::credentials::GitCredentials;
+use crate::git_utils::repo::{CommitInfo, GitRepo};
+use crate::git_utils::{self, git, git_with};
+use log::{info, warn};
+use std::io;
+use std::path::Path;
+
+/// 同步选项
+#[derive(Debug, Clone)]
+pub struct SyncOptions {
+    remote: String,
+    fail_on_changes: bool,
+    credentials: GitCredentials,
+}
+
+impl Default for SyncOptions {
+    fn default() -> Self {
+        SyncOptions {
+            remote: "origin".to_string(),
+            fail_on_changes: false,
+            credentials: GitCredentials::default(),
+        }
+    }
+}
+
+impl SyncOptions {
+    /// 默认选项：同步 `origin`，本地修改会被丢弃
+    pub fn new() -> Self {
+        SyncOptions::default()
+    }
+
+    /// 远程仓库名称
+    pub fn remote(mut self, remote: &str) -> Self {
+        self.remote = remote.to_string();
+        self
+    }
+
+    /// 已跟踪文件有本地修改时返回错误，而不是丢弃修改
+    pub fn fail_on_changes(mut self, fail_on_changes: bool) -> Self {
+        self.fail_on_changes = fail_on_changes;
+        self
+    }
+
+    /// 拉取时使用的凭据
+    pub fn credentials(mut self, credentials: GitCredentials) -> Self {
+        self.credentials = credentials;
+        self
+    }
+}
+
+/// 同步结果
+#[derive(Debug, Clone, Default)]
+pub struct SyncReport {
+    /// 同步前的提交 ID
+    pub previous: Option<String>,
+    /// 同步后的提交 ID
+    pub current: String,
+    /// 远程仓库地址发生了变化
+    pub url_changed: bool,
+    /// 检出的分支发生了变化
+    pub branch_changed: bool,
+    /// 是否以快进方式更新，否则为强制重置
+    pub fast_forward: bool,
+    /// 新拉取的提交，从新到旧排列
+    pub commits: Vec<CommitInfo>,
+}
+
+impl SyncReport {
+    /// 同步前后版本是否相同
+    pub fn is_up_to_date(&self) -> bool {
+        self.previous.as_deref() == Some(self.current.as_str())
+    }
+}
+
+/// 将已有检出同步到远程分支的最新提交
+///
+/// 远程地址与 `url` 不同时先更新地址，然后只拉取 `branch`，
+/// 检出的分支不同则切换过去。本地分支是远程分支的祖先时快进，
+/// 否则（包括分支被强制推送或本地有修改）强制重置到远程分支。
+pub fn sync(repo: &Path, url: &str, branch: &str, options: &SyncOptions) -> io::Result<SyncReport> {
+    let git_repo = GitRepo::open(repo)?;
+    let remote = options.remote.as_str();
+    let mut report = SyncReport {
+        previous: git_repo.head()?,
+        ..SyncReport::default()
+    };
+
+    let status = git_repo.status()?;
+    let dirty = status.is_dirty();
+    if dirty {
+        if options.fail_on_changes {
+            return Err(io::Error::other(format!(
+                "{} has local modifications: {:?}",
+                repo.display(),
+                [
+                    status.staged,
+                    status.modified,
+                    status.deleted,
+                    status.conflicted
+                ]
+                .concat()
+            )));
+        }
+        warn!("{} 有本地修改，将被丢弃", repo.display());
+    }
+
+    match git_repo.remote_url(remote)? {
+        Some(current) if current == url => {}
+        Some(current) => {
+            info!(
+                "远程仓库地址变更：{} -> {}",
+                command_utils::redact(&current),
+                command_utils::redact(url)
+            );
+            git(repo, &["remote", "set-url", remote, url])?;
+            report.url_changed = true;
+        }
+        None => {
+            git(repo, &["remote", "add", remote, url])?;
+            report.url_changed = true;
+        }
+    }
+
+    // 显式指定引用映射，单分支克隆也能拉取其他分支
+    let remote_ref = format!("{}/{}", remote, branch);
+    let refspec = format!("+refs/heads/{}:refs/remotes/{}", branch, remote_ref);
+    git_with(
+        repo,
+        &["fetch", "--force", remote, &refspec],
+        &options.credentials,
+        url,
+    )?;
+    let target = git_repo.resolve(&remote_ref)?;
+
+    let current_branch = git_repo.branch()?;
+    if current_branch.as_deref() != Some(branch) {
+        info!(
+            "切换分支：{} -> {}",
+            current_branch.as_deref().unwrap_or("(detached)"),
+            branch
+        );
+        git(repo, &["checkout", "--force", "-B", branch, &remote_ref])?;
+        // 直接写配置，单分支克隆的 fetch 引用映射不包含其他分支
+        git(
+            repo,
+            &["config", &format!("branch.{}.remote", branch), remote],
+        )?;
+        let merge_ref = format!("refs/heads/{}", branch);
+        git(
+            repo,
+            &["config", &format!("branch.{}.merge", branch), &merge_ref],
+        )?;
+        report.branch_changed = true;
+    } else {
+        report.fast_forward = !dirty
+            && match &report.previous {
+                Some(previous) => git_repo.is_ancestor(previous, &target)?,
+                None => false,
+            };
+        if report.fast_forward {
+            git(repo, &["merge", "--ff-only", &remote_ref])?;
+        } else {
+            git_utils::reset_hard(repo, &remote_ref)?;
+        }
+    }
+
+    report.current = target;
+    report.commits = git_repo.commits_between(report.previous.as_deref(), &report.current)?;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::process::Command;
+
+    fn run(dir: &Path, args: &[&str]) {
+        let mut command = Command::new("git");
+        command
+            .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+            .arg("-C")
+            .arg(dir)
+            .args(args);
+        command_utils::run_checked(command).unwrap();
+    }
+
+    fn commit(work: &Path, file: &str, contents: &str, message: &str) {
+        fs::write(work.join(file), contents).unwrap();
+        run(work, &["add", "."]);
+        run(work, &["commit", "-m", message]);
+    }
+
+    /// 创建用于提交的工作仓库和带 `main`、`feature` 分支的裸仓库，返回两者路径
+    fn upstream(dir: &Path) -> (PathBuf, PathBuf) {
+        let work = dir.join("work");
+        fs::create_dir_all(&work).unwrap();
+        run(&work, &["init", "-b", "main"]);
+        commit(&work, "a.txt", "main", "main");
+        run(&work, &["checkout", "-b", "feature"]);
+        commit(&work, "b.txt", "feature", "feature");
+        run(&work, &["checkout", "main"]);
+        let bare = dir.join("source.git");
+        run(dir, &["clone", "--bare", "work", "source.git"]);
+        (work, bare)
+    }
+
+    fn file_url(path: &Path) -> String {
+        format!("file://{}", path.display())
+    }
+
+    /// 在工作仓库提交并推送到裸仓库的 `main`
+    fn push(work: &Path, bare: &Path, file: &str, message: &str) {
+        commit(work, file, message, message);
+        run(work, &["push", "--force", bare.to_str().unwrap(), "main"]);
+    }
+
+    fn head(repo: &Path) -> String {
+        GitRepo::open(repo).unwrap().head().unwrap().unwrap()
+    }
+
+    #[test]
+    fn fast_forwards_and_reports_new_commits() {
+        let dir = tempfile::tempdir().unwrap();
+        let (work, bare) = upstream(dir.path());
+        let url = file_url(&bare);
+        let checkout = dir.path().join("checkout");
+        run(dir.path(), &["clone", &url, "checkout"]);
+        let previous = head(&checkout);
+
+        push(&work, &bare, "c.txt", "second");
+        push(&work, &bare, "d.txt", "third");
+        let report = sync(&checkout, &url, "main", &SyncOptions::new()).unwrap();
+        assert!(report.fast_forward);
+        assert!(!report.branch_changed);
+        assert!(!report.url_changed);
+        assert_eq!(report.previous.as_deref(), Some(previous.as_str()));
+        assert_eq!(report.current, head(&bare));
+        assert_eq!(head(&checkout), report.current);
+        let summaries: Vec<_> = report.commits.iter().map(|c| c.summary()).collect();
+        assert_eq!(summaries, ["third", "second"]);
+
+        let report = sync(&checkout, &url, "main", &SyncOptions::new()).unwrap();
+        assert!(report.is_up_to_date());
+        assert!(report.commits.is_empty());
+    }
+
+    #[test]
+    fn resets_after_force_push() {
+        let dir = tempfile::tempdir().unwrap();
+        let (work, bare) = upstream(dir.path());
+        let url = file_url(&bare);
+        push(&work, &bare, "c.txt", "second");
+        let checkout = dir.path().join("checkout");
+        run(dir.path(), &["clone", &url, "checkout"]);
+
+        run(&work, &["reset", "--hard", "HEAD~1"]);
+        push(&work, &bare, "c.txt", "rewritten");
+        let report = sync(&checkout, &url, "main", &SyncOptions::new()).unwrap();
+        assert!(!report.fast_forward);
+        assert_eq!(head(&checkout), head(&bare));
+        assert_eq!(
+            fs::read_to_string(checkout.join("c.txt")).unwrap(),
+            "rewritten"
+        );
+        let summaries: Vec<_> = report.commits.iter().map(|c| c.summary()).collect();
+        assert_eq!(summaries, ["rewritten"]);
+    }
+
+    #[test]
+    fn switches_branch() {
+        let dir = tempfile::tempdir().unwrap();
+        let (_work, bare) = upstream(dir.path());
+        let url = file_url(&bare);
+        let checkout = dir.path().join("checkout");
+        run(
+            dir.path(),
+            &["clone", "--single-branch", "-b", "main", &url, "checkout"],
+        );
+
+        let report = sync(&checkout, &url, "feature", &SyncOptions::new()).unwrap();
+        assert!(report.branch_changed);
+        let repo = GitRepo::open(&checkout).unwrap();
+        assert_eq!(repo.branch().unwrap().as_deref(), Some("feature"));
+        assert_eq!(report.current, repo.resolve("refs/heads/feature").unwrap());
+        assert!(checkout.join("b.txt").exists());
+        let merge = git(&checkout, &["config", "branch.feature.merge"]).unwrap();
+        assert_eq!(merge.trim(), "refs/heads/feature");
+    }
+
+    #[test]
+    fn updates_changed_remote_url() {
+        let dir = tempfile::tempdir().unwrap();
+        let (_work, bare) = upstream(dir.path());
+        let checkout = dir.path().join("checkout");
+        run(dir.path(), &["clone", &file_url(&bare), "checkout"]);
+
+        let moved = dir.path().join("moved.git");
+        fs::rename(&bare, &moved).unwrap();
+        let url = file_url(&moved);
+        let report = sync(&checkout, &url, "main", &SyncOptions::new()).unwrap();
+        assert!(report.url_changed);
+        assert!(report.is_up_to_date());
+        let origin = git(&checkout, &["remote", "get-url", "origin"]).unwrap();
+        assert_eq!(origin.trim(), url);
+    }
+
+    #[test]
+    fn fail_on_changes_keeps_dirty_tree() {
+        let dir = tempfile::tempdir().unwrap();
+        let (work, bare) = upstream(dir.path());
+        let url = file_url(&bare);
+        let checkout = dir.path().join("checkout");
+        run(dir.path(), &["clone", &url, "checkout"]);
+        let previous = head(&checkout);
+        push(&work, &bare, "c.txt", "second");
+        fs::write(checkout.join("a.txt"), "local").unwrap();
+
+        let options = SyncOptions::new().fail_on_changes(true);
+        assert!(sync(&checkout, &url, "main", &options).is_err());
+        assert_eq!(head(&checkout), previous);
+        assert_eq!(fs::read_to_string(checkout.join("a.txt")).unwrap(), "local");
+
+        // 默认丢弃本地修改并重置
+        let report = sync(&checkout, &url, "main", &SyncOptions::new()).unwrap();
+        assert!(!report.fast_forward);
+        assert_eq!(fs::read_to_string(checkout.join("a.txt")).unwrap(), "main");
+        assert_eq!(head(&checkout), head(&bare));
+    }
+}
@@ -24,12 +24,12 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         Ok((files, dirs)) => {
             info!("Files:");
             for file in files {
-                info!("{}", file.path_buf.display());
+                info!("{}", file.path().display());
             }
 
             info!("Directories:");
             for dir in dirs {
-                info!("{}", dir.path_buf.display());
+                info!("{}", dir.path().display());
             }
         }
         Err(e) => error!("Error:\n{}", e),